@@ -1,5 +1,3 @@
-use std::{borrow::BorrowMut, mem};
-
 use super::list_node::ListNode;
 
 pub fn reverse(head: Option<Box<ListNode<i32>>>) -> Option<Box<ListNode<i32>>> {
@@ -29,13 +27,13 @@ pub fn reverse_list(head: Option<Box<ListNode<i32>>>) -> Vec<i32> {
 pub fn reverse_list_by_recursive(head: Option<Box<ListNode<i32>>>) -> Vec<i32> {
     if let Some(head) = head {
         let mut list: Vec<i32> = vec![];
-        fn recursive(node: Box<ListNode<i32>>, list: &mut Vec<i32>) {
+        fn recursive(node: ListNode<i32>, list: &mut Vec<i32>) {
             if let Some(next) = node.next {
-                recursive(next, list);
+                recursive(*next, list);
             }
             list.push(node.val);
         }
-        recursive(head, &mut list);
+        recursive(*head, &mut list);
         return list;
     }
     vec![]
@@ -47,17 +45,15 @@ pub fn reverse_list_by_recursive(head: Option<Box<ListNode<i32>>>) -> Vec<i32> {
 /// Arguments:
 ///
 /// * `head`: The `head` parameter is an `Option` type that represents the head of a linked list. It is
-/// a box that contains a `ListNode` struct with a generic type `i32`.
+///   a box that contains a `ListNode` struct with a generic type `i32`.
 /// * `val`: The `val` parameter represents the value of the node that needs to be deleted from the
-/// linked list.
+///   linked list.
 ///
 /// Returns:
 ///
 /// The function `delete_node` returns an `Option<Box<ListNode<i32>>>`.
 pub fn delete_node(head: Option<Box<ListNode<i32>>>, val: i32) -> Option<Box<ListNode<i32>>> {
-    if head.is_none() {
-        return None;
-    }
+    head.as_ref()?;
     let mut head = head;
     let mut current = &mut head;
     loop {
@@ -80,10 +76,10 @@ pub fn delete_node(head: Option<Box<ListNode<i32>>>, val: i32) -> Option<Box<Lis
 /// Arguments:
 ///
 /// * `head`: The `head` parameter is an `Option` containing a `Box` that holds a `ListNode` of type
-/// `i32`. It represents the head of a linked list.
+///   `i32`. It represents the head of a linked list.
 /// * `cnt`: The `cnt` parameter represents the position from the end of the linked list that we want to
-/// find. For example, if `cnt` is 0, it means we want to find the last element of the linked list. If
-/// `cnt` is 1, it means we want to find the last node of the linked list.
+///   find. For example, if `cnt` is 0, it means we want to find the last element of the linked list. If
+///   `cnt` is 1, it means we want to find the last node of the linked list.
 ///
 /// Returns:
 ///
@@ -120,11 +116,11 @@ pub fn find_nth_from_end(
 /// Arguments:
 ///
 /// * `head`: The `head` parameter is an `Option` that represents the head of a linked list. It is of
-/// type `Option<Box<ListNode<i32>>>`, which means it can either be `Some` containing a boxed `ListNode`
-/// or `None` representing an empty list.
+///   type `Option<Box<ListNode<i32>>>`, which means it can either be `Some` containing a boxed `ListNode`
+///   or `None` representing an empty list.
 /// * `cnt`: The `cnt` parameter represents the position of the node from the end of the linked list
-/// that we want to find. For example, if `cnt` is 1, it means we want to find the last node of the
-/// linked list.
+///   that we want to find. For example, if `cnt` is 1, it means we want to find the last node of the
+///   linked list.
 ///
 /// Returns:
 ///
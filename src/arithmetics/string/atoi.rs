@@ -3,7 +3,7 @@ use std::{cell::Cell, num::IntErrorKind};
 
 /// 字符串转换整数 (atoi)
 /// https://leetcode.cn/problems/string-to-integer-atoi/description/
-
+///
 /// 使用确定性有限自动机（Deterministic Finite Automation DFA）解决
 
 #[derive(Debug, Clone, Copy)]
@@ -52,11 +52,11 @@ impl Atoi {
                 res.push(c);
                 AtoiState::Start
             }
-            (AtoiState::Start, c) if c.is_digit(10) => {
+            (AtoiState::Start, c) if c.is_ascii_digit() => {
                 res.push(c);
                 AtoiState::Integer
             }
-            (AtoiState::Integer, c) if c.is_digit(10) => {
+            (AtoiState::Integer, c) if c.is_ascii_digit() => {
                 res.push(c);
                 AtoiState::Integer
             }
@@ -0,0 +1,35 @@
+use std::rc::Rc;
+
+use super::environment::Environment;
+use super::function::NativeFunction;
+use super::interpreter::{RuntimeError, RuntimeErrorKind};
+use super::literal::Literal;
+
+/// 新的全局作用域，外层是保存内置函数的作用域，因此列出全局变量时不包括内置函数
+pub fn globals() -> Rc<Environment> {
+    let builtins = Environment::new();
+    define(&builtins, NativeFunction::new("len", 1, len));
+    Rc::new(Environment::new_enclosed(Rc::new(builtins)))
+}
+
+fn define(environment: &Environment, function: NativeFunction) {
+    let name = function.name().to_string();
+    environment.define(&name, Literal::Native(Rc::new(function)));
+}
+
+/// `len(value)`：列表的元素个数、映射的键值对个数或字符串的字符个数
+fn len(arguments: &[Literal]) -> Result<Literal, RuntimeError> {
+    let len = match &arguments[0] {
+        Literal::List(list) => list.len(),
+        Literal::Map(map) => map.len(),
+        Literal::String(text) => text.chars().count(),
+        other => {
+            return Err(RuntimeError::new(RuntimeErrorKind::ArgumentType {
+                position: 1,
+                expected: "List, Map or String",
+                found: other.type_name(),
+            }))
+        }
+    };
+    Ok(Literal::Integer(len as i64))
+}
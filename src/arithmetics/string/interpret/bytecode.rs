@@ -0,0 +1,408 @@
+use std::fmt::Write;
+use std::rc::Rc;
+
+use super::literal::Literal;
+use super::stmt::FunctionDecl;
+use super::token::Span;
+
+/// 反汇编时代替越界操作数的标记
+const INVALID: &str = "<invalid>";
+
+/// 虚拟机指令，操作数紧跟在操作码之后，多字节操作数按大端序存放
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// `u16` 常量下标，压入常量
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// `u16` 变量名常量下标，按名称访问全局变量，以下三条相同
+    GetVar,
+    SetVar,
+    DefineVar,
+    /// `u16` 向外的作用域层数与 `u16` 槽位下标，读取编译期确定位置的变量
+    GetLocal,
+    /// 操作数同 `GetLocal`，给变量赋值
+    SetLocal,
+    /// `u16` 槽位下标，在当前作用域中定义变量
+    DefineLocal,
+    /// `u16` 作用域下标，以 `Chunk::scopes` 中的槽位进入新的块作用域
+    PushScope,
+    /// 回到外层作用域
+    PopScope,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Negate,
+    Not,
+    /// 将栈顶替换为其真值
+    Truthy,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Print,
+    /// `u16` 片段个数，拼接为字符串
+    Interpolate,
+    /// `u16` 元素个数，创建列表
+    BuildList,
+    /// `u16` 键值对个数，栈中按键、值交替存放，创建映射
+    BuildMap,
+    /// 弹出下标与对象，压入元素
+    GetIndex,
+    /// 弹出值、下标与对象，给元素赋值后压入值
+    SetIndex,
+    /// 将栈顶替换为待遍历的值的列表，再压入下标 0
+    Iterate,
+    /// `u16` 向前跳转的距离；遍历未结束时压入下一个值，结束时跳转
+    ForNext,
+    /// `u16` 向前跳转的距离
+    Jump,
+    /// `u16` 向前跳转的距离，弹出条件，为假时跳转
+    JumpIfFalse,
+    /// `u16` 向后跳转的距离
+    Loop,
+    /// `u8` 实参个数
+    Call,
+    /// `u16` 函数原型下标，以当前作用域创建闭包
+    Closure,
+    Return,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 40] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetVar,
+        OpCode::SetVar,
+        OpCode::DefineVar,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::DefineLocal,
+        OpCode::PushScope,
+        OpCode::PopScope,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Negate,
+        OpCode::Not,
+        OpCode::Truthy,
+        OpCode::Equal,
+        OpCode::NotEqual,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Print,
+        OpCode::Interpolate,
+        OpCode::BuildList,
+        OpCode::BuildMap,
+        OpCode::GetIndex,
+        OpCode::SetIndex,
+        OpCode::Iterate,
+        OpCode::ForNext,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::Return,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+
+    /// 操作数占用的字节数
+    pub fn operand_len(self) -> usize {
+        match self {
+            OpCode::Constant
+            | OpCode::GetVar
+            | OpCode::SetVar
+            | OpCode::DefineVar
+            | OpCode::DefineLocal
+            | OpCode::PushScope
+            | OpCode::Interpolate
+            | OpCode::BuildList
+            | OpCode::BuildMap
+            | OpCode::ForNext
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::Loop
+            | OpCode::Closure => 2,
+            OpCode::GetLocal | OpCode::SetLocal => 4,
+            OpCode::Call => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// 编译后的函数：函数体的字节码以及定义它的语法树
+#[derive(Debug, Clone)]
+pub struct Prototype {
+    pub declaration: Rc<FunctionDecl>,
+    pub chunk: Rc<Chunk>,
+}
+
+/// 一段字节码及其常量池、函数原型与行号表
+#[derive(Debug, Default)]
+pub struct Chunk {
+    /// 函数名，顶层代码为 `<script>`
+    pub name: String,
+    pub code: Vec<u8>,
+    pub constants: Vec<Literal>,
+    pub functions: Vec<Prototype>,
+    /// 块作用域的槽位名称，由 `PushScope` 引用
+    pub scopes: Vec<Rc<[String]>>,
+    /// 函数体顶层作用域的槽位名称，形参在前
+    pub locals: Rc<[String]>,
+    /// 各形参的槽位下标
+    pub params: Vec<usize>,
+    /// 行号表：`(指令偏移, 源码区间)`，区间变化时才追加，用于报告运行时错误
+    pub spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn new(name: &str) -> Self {
+        Chunk {
+            name: name.to_string(),
+            ..Chunk::default()
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, span: Span) {
+        if self.spans.last().map(|&(_, last)| last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+        self.code.push(byte);
+    }
+
+    /// 字符串常量（多为变量名）会复用已有的下标
+    pub fn add_constant(&mut self, value: Literal) -> usize {
+        if let Literal::String(text) = &value {
+            let existing = self
+                .constants
+                .iter()
+                .position(|constant| matches!(constant, Literal::String(other) if other == text));
+            if let Some(index) = existing {
+                return index;
+            }
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// `offset` 处的两字节操作数，超出字节码末尾时为 `None`
+    pub fn read_u16(&self, offset: usize) -> Option<u16> {
+        match self.code.get(offset..offset + 2)? {
+            &[high, low] => Some(u16::from_be_bytes([high, low])),
+            _ => None,
+        }
+    }
+
+    /// `offset` 处指令对应的源码区间
+    pub fn span(&self, offset: usize) -> Option<Span> {
+        let index = self.spans.partition_point(|&(start, _)| start <= offset);
+        index.checked_sub(1).map(|index| self.spans[index].1)
+    }
+
+    /// 反汇编为可读的文本，函数原型紧随其后
+    pub fn disassemble(&self) -> String {
+        let mut text = String::new();
+        self.disassemble_into(&mut text);
+        text
+    }
+
+    fn disassemble_into(&self, text: &mut String) {
+        let _ = writeln!(text, "== {} ==", self.name);
+        let mut offset = 0;
+        while offset < self.code.len() {
+            offset = self.disassemble_instruction(offset, text);
+        }
+        for function in &self.functions {
+            function.chunk.disassemble_into(text);
+        }
+    }
+
+    /// 写出一条指令，返回下一条指令的偏移
+    pub fn disassemble_instruction(&self, offset: usize, text: &mut String) -> usize {
+        let _ = write!(text, "{:04} ", offset);
+        let span = self.span(offset).unwrap_or_default();
+        if offset > 0 && self.span(offset - 1) == Some(span) {
+            let _ = write!(text, "{:>9} ", "|");
+        } else {
+            let _ = write!(text, "{:>9} ", format!("{}..{}", span.start, span.end));
+        }
+        let op = match OpCode::from_byte(self.code[offset]) {
+            Some(op) => op,
+            None => {
+                let _ = writeln!(text, "Unknown {}", self.code[offset]);
+                return offset + 1;
+            }
+        };
+        let next = offset + 1 + op.operand_len();
+        let name = format!("{:?}", op);
+        // 手工构造的字节码可能截断操作数或引用不存在的常量，写作 `<invalid>` 而不是 panic
+        let invalid = || INVALID.to_string();
+        let operand = |index: usize| self.read_u16(offset + 1 + 2 * index).map(usize::from);
+        match op {
+            OpCode::Constant | OpCode::GetVar | OpCode::SetVar | OpCode::DefineVar => {
+                let (index, value) = match operand(0) {
+                    Some(index) => (
+                        index.to_string(),
+                        self.constants
+                            .get(index)
+                            .map_or_else(invalid, |value| format!("'{}'", value)),
+                    ),
+                    None => (invalid(), invalid()),
+                };
+                let _ = writeln!(text, "{:<16} {:>4} {}", name, index, value);
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                let show =
+                    |index: usize| operand(index).map_or_else(invalid, |value| value.to_string());
+                let _ = writeln!(text, "{:<16} {:>4} {:>4}", name, show(0), show(1));
+            }
+            OpCode::PushScope => {
+                let (index, names) = match operand(0) {
+                    Some(index) => (
+                        index.to_string(),
+                        self.scopes
+                            .get(index)
+                            .map_or_else(invalid, |names| format!("[{}]", names.join(", "))),
+                    ),
+                    None => (invalid(), invalid()),
+                };
+                let _ = writeln!(text, "{:<16} {:>4} {}", name, index, names);
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::ForNext | OpCode::Loop => {
+                let (distance, target) = match operand(0) {
+                    Some(distance) => {
+                        let target = match op {
+                            OpCode::Loop => next.checked_sub(distance),
+                            _ => Some(next + distance),
+                        };
+                        let target = target
+                            .filter(|target| *target <= self.code.len())
+                            .map_or_else(invalid, |target| target.to_string());
+                        (distance.to_string(), target)
+                    }
+                    None => (invalid(), invalid()),
+                };
+                let _ = writeln!(text, "{:<16} {:>4} -> {}", name, distance, target);
+            }
+            OpCode::Closure => {
+                let (index, function) = match operand(0) {
+                    Some(index) => (
+                        index.to_string(),
+                        self.functions
+                            .get(index)
+                            .map_or_else(invalid, |function| format!("<{}>", function.chunk.name)),
+                    ),
+                    None => (invalid(), invalid()),
+                };
+                let _ = writeln!(text, "{:<16} {:>4} {}", name, index, function);
+            }
+            OpCode::Interpolate | OpCode::BuildList | OpCode::BuildMap | OpCode::DefineLocal => {
+                let count = operand(0).map_or_else(invalid, |count| count.to_string());
+                let _ = writeln!(text, "{:<16} {:>4}", name, count);
+            }
+            OpCode::Call => {
+                let count = self
+                    .code
+                    .get(offset + 1)
+                    .map_or_else(invalid, |count| count.to_string());
+                let _ = writeln!(text, "{:<16} {:>4}", name, count);
+            }
+            _ => {
+                let _ = writeln!(text, "{}", name);
+            }
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn opcode_from_byte() {
+        for op in OpCode::ALL {
+            assert_eq!(Some(op), OpCode::from_byte(op as u8));
+        }
+        assert_eq!(None, OpCode::from_byte(OpCode::ALL.len() as u8));
+    }
+
+    #[test]
+    fn span_table() {
+        let mut chunk = Chunk::new("test");
+        chunk.write(OpCode::Nil as u8, Span::new(0, 3));
+        chunk.write(OpCode::Nil as u8, Span::new(0, 3));
+        chunk.write(OpCode::Pop as u8, Span::new(4, 5));
+        assert_eq!(2, chunk.spans.len());
+        assert_eq!(Some(Span::new(0, 3)), chunk.span(1));
+        assert_eq!(Some(Span::new(4, 5)), chunk.span(2));
+    }
+
+    #[test]
+    fn reuse_string_constants() {
+        let mut chunk = Chunk::new("test");
+        let name = chunk.add_constant(Literal::String("a".to_string()));
+        chunk.add_constant(Literal::Integer(1));
+        assert_eq!(name, chunk.add_constant(Literal::String("a".to_string())));
+        assert_eq!(2, chunk.add_constant(Literal::Integer(1)));
+    }
+
+    #[test]
+    fn disassemble_invalid_chunk() {
+        let mut chunk = Chunk::new("test");
+        for op in [OpCode::Constant, OpCode::PushScope, OpCode::Closure] {
+            chunk.write(op as u8, Span::default());
+            chunk.write(0, Span::default());
+            chunk.write(7, Span::default());
+        }
+        for byte in [OpCode::Loop as u8, 0, 100, 0xff, OpCode::GetLocal as u8, 0] {
+            chunk.write(byte, Span::default());
+        }
+        let text = chunk.disassemble();
+        let lines = text.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(6, lines.len(), "{}", text);
+        assert!(
+            lines[0].ends_with("Constant            7 <invalid>"),
+            "{}",
+            text
+        );
+        assert!(
+            lines[1].ends_with("PushScope           7 <invalid>"),
+            "{}",
+            text
+        );
+        assert!(
+            lines[2].ends_with("Closure             7 <invalid>"),
+            "{}",
+            text
+        );
+        assert!(
+            lines[3].ends_with("Loop              100 -> <invalid>"),
+            "{}",
+            text
+        );
+        assert!(lines[4].ends_with("Unknown 255"), "{}", text);
+        assert!(
+            lines[5].ends_with("GetLocal         <invalid> <invalid>"),
+            "{}",
+            text
+        );
+        assert_eq!(None, chunk.read_u16(chunk.code.len() - 1));
+    }
+}
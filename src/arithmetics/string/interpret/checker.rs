@@ -0,0 +1,895 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use super::expr::{Expr, ExprVisitor, InterpolationPart};
+use super::literal::{Literal, NumericMode};
+use super::stmt::{FunctionDecl, Stmt, StmtVisitor};
+use super::token::{Span, Token, TokenType};
+
+/// 静态类型：运行时可能出现的类型的集合，如 `Integer | String`
+///
+/// `BigInt` 归入 `Integer`，因为整数运算溢出时会在二者之间转换。
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub struct Type(u16);
+
+impl Type {
+    /// 空集，不会产生值
+    pub const NEVER: Type = Type(0);
+    pub const INTEGER: Type = Type(1);
+    pub const RATIONAL: Type = Type(1 << 1);
+    pub const FLOAT: Type = Type(1 << 2);
+    pub const STRING: Type = Type(1 << 3);
+    pub const BOOL: Type = Type(1 << 4);
+    pub const NONE: Type = Type(1 << 5);
+    pub const FUNCTION: Type = Type(1 << 6);
+    pub const LIST: Type = Type(1 << 7);
+    pub const MAP: Type = Type(1 << 8);
+    pub const NUMBER: Type = Type(Type::INTEGER.0 | Type::RATIONAL.0 | Type::FLOAT.0);
+    /// 可以下标与遍历的类型
+    pub const COLLECTION: Type = Type(Type::LIST.0 | Type::MAP.0);
+    /// 无法推断，可能是任何类型
+    pub const ANY: Type = Type((1 << 9) - 1);
+
+    const NAMES: [(Type, &'static str); 9] = [
+        (Type::INTEGER, "Integer"),
+        (Type::RATIONAL, "Rational"),
+        (Type::FLOAT, "Float"),
+        (Type::STRING, "String"),
+        (Type::BOOL, "Bool"),
+        (Type::NONE, "None"),
+        (Type::FUNCTION, "Function"),
+        (Type::LIST, "List"),
+        (Type::MAP, "Map"),
+    ];
+
+    pub fn of(literal: &Literal) -> Type {
+        match literal {
+            Literal::Integer(_) | Literal::BigInt(_) => Type::INTEGER,
+            Literal::Rational(_) => Type::RATIONAL,
+            Literal::Float(_) => Type::FLOAT,
+            Literal::String(_) => Type::STRING,
+            Literal::Bool(_) => Type::BOOL,
+            Literal::None => Type::NONE,
+            Literal::List(_) => Type::LIST,
+            Literal::Map(_) => Type::MAP,
+            Literal::Function(_) | Literal::Native(_) => Type::FUNCTION,
+        }
+    }
+
+    pub fn union(self, other: Type) -> Type {
+        Type(self.0 | other.0)
+    }
+
+    /// `other` 中的每种类型都属于 `self`
+    pub fn contains(self, other: Type) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: Type) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// 拆分为单一类型
+    fn members(self) -> impl Iterator<Item = Type> {
+        Type::NAMES
+            .into_iter()
+            .map(|(member, _)| member)
+            .filter(move |&member| self.contains(member))
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Type::ANY => f.write_str("Any"),
+            Type::NEVER => f.write_str("Never"),
+            _ => {
+                let names: Vec<&str> = Type::NAMES
+                    .iter()
+                    .filter(|(member, _)| self.contains(*member))
+                    .map(|(_, name)| *name)
+                    .collect();
+                f.write_str(&names.join(" | "))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Type({})", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeErrorKind {
+    /// 二元运算符不支持任何一组可能的操作数类型
+    BinaryOperands {
+        operator: &'static str,
+        left: Type,
+        right: Type,
+    },
+    UnaryOperand {
+        operator: &'static str,
+        operand: Type,
+    },
+    /// 被调用的值一定不是函数
+    NotCallable(Type),
+    /// 取下标的值一定不是列表或映射
+    NotIndexable(Type),
+    /// `for (x in value)` 的值一定不是列表或映射
+    NotIterable(Type),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    /// 运算符或调用在源码中的区间
+    pub span: Span,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TypeErrorKind::BinaryOperands {
+                operator,
+                left,
+                right,
+            } => write!(f, "cannot apply `{}` to {} and {}", operator, left, right),
+            TypeErrorKind::UnaryOperand { operator, operand } => {
+                write!(f, "cannot apply unary `{}` to {}", operator, operand)
+            }
+            TypeErrorKind::NotCallable(callee) => {
+                write!(f, "cannot call a value of type {}", callee)
+            }
+            TypeErrorKind::NotIndexable(object) => {
+                write!(f, "cannot index into a value of type {}", object)
+            }
+            TypeErrorKind::NotIterable(iterable) => {
+                write!(f, "cannot iterate over a value of type {}", iterable)
+            }
+        }
+    }
+}
+
+/// 各层作用域中变量的类型
+type Scopes = Vec<HashMap<String, Type>>;
+
+/// 正在检查的循环，`break`/`continue` 处的变量类型分别并入循环出口与下一次迭代
+struct Loop {
+    /// 循环所在的作用域层数，更内层的作用域在跳出时已经结束
+    depth: usize,
+    breaks: Option<Scopes>,
+    continues: Option<Scopes>,
+}
+
+/// 在执行前推断每个表达式的类型，报告运算符与操作数类型一定不匹配的错误
+///
+/// 变量的类型随赋值改变，分支与循环结束处合并各条路径的类型。只有所有可能的
+/// 类型组合都会出错时才报告，无法确定的类型（参数、函数返回值、被函数修改的
+/// 外层变量）为 `Any`，因此不会误报。
+pub struct TypeChecker {
+    mode: NumericMode,
+    scopes: RefCell<Scopes>,
+    loops: RefCell<Vec<Loop>>,
+    /// 在函数体内被赋值的外层变量，调用后类型未知
+    escaped: RefCell<HashSet<String>>,
+    /// 大于 0 时处于循环的不动点迭代中，不记录错误与类型
+    silent: Cell<usize>,
+    errors: RefCell<Vec<TypeError>>,
+    types: RefCell<Vec<(Span, Type)>>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        TypeChecker {
+            mode: NumericMode::default(),
+            scopes: RefCell::new(vec![HashMap::new()]),
+            loops: RefCell::new(Vec::new()),
+            escaped: RefCell::new(HashSet::new()),
+            silent: Cell::new(0),
+            errors: RefCell::new(Vec::new()),
+            types: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker::default()
+    }
+
+    /// 整数运算的结果类型取决于 `NumericMode`，应与执行时一致
+    pub fn with_mode(mut self, mode: NumericMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// 声明全局变量的类型，如 REPL 中已有的变量
+    pub fn define(&self, name: &str, value: Type) {
+        if let Some(scope) = self.scopes.borrow_mut().first_mut() {
+            scope.insert(name.to_string(), value);
+        }
+    }
+
+    /// 检查一段程序，全局变量的类型在多次调用之间保留
+    pub fn check(&self, statements: &[Stmt]) -> Result<(), Vec<TypeError>> {
+        self.types.borrow_mut().clear();
+        for statement in statements {
+            statement.accept(self);
+        }
+        self.finish(())
+    }
+
+    pub fn infer(&self, expr: &Expr) -> Result<Type, Vec<TypeError>> {
+        self.types.borrow_mut().clear();
+        let value = self.node(expr);
+        self.finish(value)
+    }
+
+    /// 最近一次检查中每个表达式的区间与类型，子表达式在前
+    pub fn types(&self) -> Vec<(Span, Type)> {
+        self.types.borrow().clone()
+    }
+
+    fn finish<T>(&self, value: T) -> Result<T, Vec<TypeError>> {
+        let errors = self.errors.take();
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn node(&self, expr: &Expr) -> Type {
+        let value = expr.accept(self);
+        if self.silent.get() == 0 {
+            self.types.borrow_mut().push((expr.span(), value));
+        }
+        value
+    }
+
+    /// 报告错误，出错的表达式按 `Any` 继续检查以免连带报错
+    fn error(&self, kind: TypeErrorKind, span: Span) -> Type {
+        if self.silent.get() == 0 {
+            self.errors.borrow_mut().push(TypeError { kind, span });
+        }
+        Type::ANY
+    }
+
+    fn snapshot(&self) -> Scopes {
+        self.scopes.borrow().clone()
+    }
+
+    fn restore(&self, scopes: Scopes) {
+        self.scopes.replace(scopes);
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        if self.escaped.borrow().contains(name) {
+            return Type::ANY;
+        }
+        let scopes = self.scopes.borrow();
+        scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .unwrap_or(Type::ANY)
+    }
+
+    fn declare(&self, name: &Token, value: Type) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope.insert(identifier(name).to_string(), value);
+        }
+    }
+
+    fn with_scope(&self, check: impl FnOnce()) {
+        self.scopes.borrow_mut().push(HashMap::new());
+        check();
+        self.scopes.borrow_mut().pop();
+    }
+
+    /// 函数体只能看到参数与自身的局部变量，外层变量按 `Any` 处理
+    fn check_function(&self, declaration: &FunctionDecl) {
+        let params = declaration
+            .params
+            .iter()
+            .map(|param| (identifier(param).to_string(), Type::ANY))
+            .collect();
+        let scopes = self.scopes.replace(vec![HashMap::new(), params]);
+        let loops = self.loops.take();
+        for statement in &declaration.body {
+            statement.accept(self);
+        }
+        self.scopes.replace(scopes);
+        self.loops.replace(loops);
+    }
+
+    /// 先迭代到变量类型不再变化，再以稳定后的类型检查一遍并记录错误
+    fn check_loop(&self, condition: Option<&Expr>, body: &Stmt, increment: Option<&Expr>) {
+        self.silent.set(self.silent.get() + 1);
+        let mut head = self.snapshot();
+        loop {
+            let (end, _) = self.loop_pass(condition, body, increment);
+            let next = merge(&head, &end);
+            if next == head {
+                break;
+            }
+            self.restore(next.clone());
+            head = next;
+        }
+        self.silent.set(self.silent.get() - 1);
+        self.restore(head);
+        let (_, exit) = self.loop_pass(condition, body, increment);
+        self.restore(exit);
+    }
+
+    /// 检查一次迭代，返回迭代结束与跳出循环时的变量类型
+    fn loop_pass(
+        &self,
+        condition: Option<&Expr>,
+        body: &Stmt,
+        increment: Option<&Expr>,
+    ) -> (Scopes, Scopes) {
+        if let Some(condition) = condition {
+            self.node(condition);
+        }
+        let after_condition = self.snapshot();
+        self.loops.borrow_mut().push(Loop {
+            depth: after_condition.len(),
+            breaks: None,
+            continues: None,
+        });
+        body.accept(self);
+        let context = self.loops.borrow_mut().pop().expect("loop context");
+        self.restore(merge_optional(&self.snapshot(), context.continues.as_ref()));
+        if let Some(increment) = increment {
+            self.node(increment);
+        }
+        let exit = merge_optional(&after_condition, context.breaks.as_ref());
+        (self.snapshot(), exit)
+    }
+
+    /// 记录 `break`/`continue` 处的变量类型
+    fn jump(&self, record: impl FnOnce(&mut Loop) -> &mut Option<Scopes>) {
+        let mut loops = self.loops.borrow_mut();
+        if let Some(context) = loops.last_mut() {
+            let mut scopes = self.snapshot();
+            scopes.truncate(context.depth);
+            let slot = record(context);
+            *slot = Some(merge_optional(&scopes, slot.as_ref()));
+        }
+    }
+
+    fn arithmetic(&self, operator: TokenType, left: Type, right: Type) -> Type {
+        if operator == TokenType::Plus && left == Type::STRING && right == Type::STRING {
+            return Type::STRING;
+        }
+        if !Type::NUMBER.contains(left) || !Type::NUMBER.contains(right) {
+            return Type::NEVER;
+        }
+        if left == Type::FLOAT || right == Type::FLOAT {
+            return Type::FLOAT;
+        }
+        // 有理数运算的结果为整数时规约为 `Integer`
+        if left == Type::RATIONAL || right == Type::RATIONAL {
+            return Type::INTEGER.union(Type::RATIONAL);
+        }
+        match self.mode {
+            NumericMode::PromoteFloat => Type::INTEGER.union(Type::FLOAT),
+            NumericMode::Exact if operator == TokenType::Slash => {
+                Type::INTEGER.union(Type::RATIONAL)
+            }
+            _ => Type::INTEGER,
+        }
+    }
+
+    /// 单一类型的操作数运算的结果类型，不支持时为 `NEVER`
+    fn binary(&self, operator: TokenType, left: Type, right: Type) -> Type {
+        match operator {
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+                self.arithmetic(operator, left, right)
+            }
+            TokenType::EqualEqual | TokenType::BangEqual => Type::BOOL,
+            _ if left == right && (left == Type::STRING || left == Type::BOOL) => Type::BOOL,
+            _ if Type::NUMBER.contains(left) && Type::NUMBER.contains(right) => Type::BOOL,
+            _ => Type::NEVER,
+        }
+    }
+
+    fn negate(&self, operand: Type) -> Type {
+        match operand {
+            // 只有 `i64::MIN` 取反会溢出
+            Type::INTEGER if self.mode == NumericMode::PromoteFloat => {
+                Type::INTEGER.union(Type::FLOAT)
+            }
+            Type::INTEGER | Type::RATIONAL | Type::FLOAT => operand,
+            _ => Type::NEVER,
+        }
+    }
+}
+
+fn identifier(name: &Token) -> &str {
+    name.lexeme.as_deref().unwrap_or_default()
+}
+
+fn operator_symbol(operator: &Token) -> &'static str {
+    operator.token_type.symbol().unwrap_or_default()
+}
+
+/// 合并两条路径的变量类型，只在一条路径上出现的变量保持原样
+fn merge(left: &Scopes, right: &Scopes) -> Scopes {
+    let mut merged = left.clone();
+    for (scope, other) in merged.iter_mut().zip(right) {
+        for (name, value) in other {
+            let entry = scope.entry(name.clone()).or_insert(*value);
+            *entry = entry.union(*value);
+        }
+    }
+    merged
+}
+
+fn merge_optional(left: &Scopes, right: Option<&Scopes>) -> Scopes {
+    match right {
+        Some(right) => merge(left, right),
+        None => left.clone(),
+    }
+}
+
+impl ExprVisitor for TypeChecker {
+    type ReturnType = Type;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let left = self.node(left);
+        let right = self.node(right);
+        let mut result = Type::NEVER;
+        for l in left.members() {
+            for r in right.members() {
+                result = result.union(self.binary(operator.token_type, l, r));
+            }
+        }
+        if result == Type::NEVER {
+            return self.error(
+                TypeErrorKind::BinaryOperands {
+                    operator: operator_symbol(operator),
+                    left,
+                    right,
+                },
+                operator.span,
+            );
+        }
+        result
+    }
+
+    /// 右侧可能不执行，其中的赋值与不执行的路径合并
+    fn visit_logical(&self, left: &Expr, _operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.node(left);
+        let skipped = self.snapshot();
+        self.node(right);
+        self.restore(merge(&self.snapshot(), &skipped));
+        Type::BOOL
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        self.node(expr)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let operand = self.node(right);
+        if operator.token_type == TokenType::Bang {
+            return Type::BOOL;
+        }
+        let result = operand.members().fold(Type::NEVER, |result, member| {
+            result.union(self.negate(member))
+        });
+        if result == Type::NEVER {
+            return self.error(
+                TypeErrorKind::UnaryOperand {
+                    operator: operator_symbol(operator),
+                    operand,
+                },
+                operator.span,
+            );
+        }
+        result
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        Type::of(literal)
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        self.lookup(identifier(name))
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        let value = self.node(value);
+        let name = identifier(name);
+        let mut scopes = self.scopes.borrow_mut();
+        match scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+        {
+            Some(slot) => *slot = value,
+            // 函数体内给外层变量赋值
+            None => {
+                self.escaped.borrow_mut().insert(name.to_string());
+            }
+        }
+        value
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        let callee_type = self.node(callee);
+        for argument in arguments {
+            self.node(argument);
+        }
+        if !callee_type.intersects(Type::FUNCTION) {
+            return self.error(
+                TypeErrorKind::NotCallable(callee_type),
+                callee.span().merge(paren.span),
+            );
+        }
+        Type::ANY
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.check_function(declaration);
+        Type::FUNCTION
+    }
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        for part in parts {
+            if let InterpolationPart::Expr(expr) = part {
+                self.node(expr);
+            }
+        }
+        Type::STRING
+    }
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        for element in elements {
+            self.node(element);
+        }
+        Type::LIST
+    }
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        for (key, value) in entries {
+            self.node(key);
+            self.node(value);
+        }
+        Type::MAP
+    }
+
+    /// 不记录元素的类型，取出的元素为 `Any`
+    fn visit_index(&self, object: &Expr, bracket: &Token, index: &Expr) -> Self::ReturnType {
+        let object_type = self.node(object);
+        self.node(index);
+        if !object_type.intersects(Type::COLLECTION) {
+            return self.error(
+                TypeErrorKind::NotIndexable(object_type),
+                object.span().merge(bracket.span),
+            );
+        }
+        Type::ANY
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        let object_type = self.node(object);
+        self.node(index);
+        let value = self.node(value);
+        if !object_type.intersects(Type::COLLECTION) {
+            return self.error(
+                TypeErrorKind::NotIndexable(object_type),
+                object.span().merge(bracket.span),
+            );
+        }
+        value
+    }
+}
+
+impl StmtVisitor for TypeChecker {
+    type ReturnType = ();
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        self.node(expr);
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        self.node(expr);
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        let value = initializer.map_or(Type::NONE, |initializer| self.node(initializer));
+        self.declare(name, value);
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        self.with_scope(|| {
+            for statement in statements {
+                statement.accept(self);
+            }
+        });
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        self.node(condition);
+        let before = self.snapshot();
+        then_branch.accept(self);
+        let then_scopes = self.scopes.replace(before);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+        self.restore(merge(&then_scopes, &self.snapshot()));
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        self.check_loop(Some(condition), body, None);
+    }
+
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        self.with_scope(|| {
+            if let Some(initializer) = initializer {
+                initializer.accept(self);
+            }
+            self.check_loop(condition, body, increment);
+        });
+    }
+
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        let iterable_type = self.node(iterable);
+        if !iterable_type.intersects(Type::COLLECTION) {
+            self.error(TypeErrorKind::NotIterable(iterable_type), iterable.span());
+        }
+        self.with_scope(|| {
+            self.declare(name, Type::ANY);
+            self.check_loop(None, body, None);
+        });
+    }
+
+    fn visit_break(&self, _keyword: &Token) -> Self::ReturnType {
+        self.jump(|context| &mut context.breaks);
+    }
+
+    fn visit_continue(&self, _keyword: &Token) -> Self::ReturnType {
+        self.jump(|context| &mut context.continues);
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        if let Some(name) = &declaration.name {
+            self.declare(name, Type::FUNCTION);
+        }
+        self.check_function(declaration);
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        if let Some(value) = value {
+            self.node(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::interpreter::Interpreter;
+    use super::super::testing::{parse_expr, parse_program};
+    use super::*;
+
+    fn infer_with(source: &str, mode: NumericMode) -> Result<Type, Vec<TypeError>> {
+        TypeChecker::new()
+            .with_mode(mode)
+            .infer(&parse_expr(source))
+    }
+
+    fn infer(source: &str) -> Result<Type, Vec<TypeError>> {
+        infer_with(source, NumericMode::Checked)
+    }
+
+    /// 报告的错误信息
+    fn check(source: &str) -> Vec<String> {
+        match TypeChecker::new().check(&parse_program(source)) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn type_display() {
+        assert_eq!("Integer", Type::INTEGER.to_string());
+        assert_eq!(
+            "Integer | String",
+            Type::STRING.union(Type::INTEGER).to_string()
+        );
+        assert_eq!("Any", Type::ANY.to_string());
+        assert_eq!("Never", Type::NEVER.to_string());
+        assert_eq!("List | Map", Type::COLLECTION.to_string());
+        assert!(Type::NUMBER.contains(Type::FLOAT));
+        assert!(!Type::NUMBER.contains(Type::FLOAT.union(Type::NONE)));
+    }
+
+    #[test]
+    fn infer_expressions() {
+        assert_eq!(Ok(Type::INTEGER), infer("(1 + 1) * 3"));
+        assert_eq!(Ok(Type::FLOAT), infer("1 + 2.5 / 2"));
+        assert_eq!(Ok(Type::STRING), infer("\"a\" + \"b\""));
+        assert_eq!(Ok(Type::BOOL), infer("1 < 2 == !nil"));
+        assert_eq!(Ok(Type::BOOL), infer("1 || \"a\""));
+        assert_eq!(Ok(Type::STRING), infer("\"${1 + 1}\""));
+        assert_eq!(Ok(Type::NONE), infer("nil"));
+        assert_eq!(Ok(Type::FUNCTION), infer("fn (x) { return x; }"));
+        assert_eq!(Ok(Type::ANY), infer("x"));
+        assert_eq!(Ok(Type::NUMBER), infer("x - 1"));
+        assert_eq!(Ok(Type::ANY), infer("f(1)"));
+        assert_eq!(Ok(Type::LIST), infer("[1, \"a\"]"));
+        assert_eq!(Ok(Type::MAP), infer("{1: 2}"));
+        assert_eq!(Ok(Type::ANY), infer("[1][0]"));
+        assert_eq!(Ok(Type::STRING), infer("x[0] = \"a\""));
+        assert_eq!(
+            Ok(Type::INTEGER.union(Type::RATIONAL)),
+            infer_with("1 / 3", NumericMode::Exact)
+        );
+        assert_eq!(
+            Ok(Type::INTEGER.union(Type::FLOAT)),
+            infer_with("-1 * 2", NumericMode::PromoteFloat)
+        );
+    }
+
+    #[test]
+    fn report_operand_mismatch() {
+        let errors = infer("\"abc\" * 2").unwrap_err();
+        assert_eq!(
+            vec![TypeError {
+                kind: TypeErrorKind::BinaryOperands {
+                    operator: "*",
+                    left: Type::STRING,
+                    right: Type::INTEGER,
+                },
+                span: Span::new(6, 7),
+            }],
+            errors
+        );
+        assert_eq!(
+            vec!["cannot apply unary `-` to Bool".to_string()],
+            infer("-(1 < 2)")
+                .unwrap_err()
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["cannot apply `<` to String and Integer".to_string()],
+            check("print \"a\" < 1;")
+        );
+        assert_eq!(
+            vec!["cannot call a value of type Integer".to_string()],
+            check("let f = 1; f();")
+        );
+    }
+
+    #[test]
+    fn report_collection_misuse() {
+        assert_eq!(
+            vec![
+                "cannot index into a value of type Integer".to_string(),
+                "cannot index into a value of type String".to_string(),
+                "cannot iterate over a value of type Integer | None".to_string(),
+            ],
+            check(
+                "print 1[0]; let s = \"a\"; s[0] = 1; let n = nil; if (c) n = 1; for (x in n) {}"
+            )
+        );
+        assert!(
+            check("let xs = [1]; for (x in xs) print x * 2; for (k in {}) print xs[k];").is_empty()
+        );
+    }
+
+    #[test]
+    fn report_each_error_once() {
+        assert_eq!(
+            vec![
+                "cannot apply `-` to String and Integer".to_string(),
+                "cannot apply `+` to Bool and Integer".to_string()
+            ],
+            check(
+                "for (let i = 0; i < 3; i = i + 1) { while (true) { print \"a\" - 1; break; } }\
+                 print true + 1;"
+            )
+        );
+    }
+
+    #[test]
+    fn variables_follow_assignments() {
+        assert_eq!(
+            vec!["cannot apply `*` to String and Integer".to_string()],
+            check("let x = 1; x = \"a\"; print x * 2;")
+        );
+        // 分支合并后 `x` 可能是整数
+        assert!(check("let x = \"a\"; if (c) x = 1; print x * 2;").is_empty());
+        assert!(check("let x = 1; { let x = \"a\"; } print x * 2;").is_empty());
+        assert!(check("let x = \"a\"; c || (x = 1); print x * 2;").is_empty());
+    }
+
+    #[test]
+    fn loops_reach_fixed_point() {
+        // 第二次迭代时 `x` 为整数
+        assert!(check(
+            "let x = \"a\"; for (let i = 0; i < 2; i = i + 1) { if (i > 0) print x * 2; x = 1; }"
+        )
+        .is_empty());
+        // `break` 时 `x` 为整数
+        assert!(
+            check("let x = \"s\"; while (c) { x = 1; if (d) break; x = \"t\"; } print x * 2;")
+                .is_empty()
+        );
+        assert_eq!(
+            vec!["cannot apply `*` to String and Integer".to_string()],
+            check("let x = \"s\"; while (c) { x = \"t\"; } print x * 2;")
+        );
+    }
+
+    #[test]
+    fn functions_do_not_cause_false_positives() {
+        assert!(check("fn f(a) { return a * 2; } print f(\"a\");").is_empty());
+        assert!(check("let x = \"a\"; fn f() { return x * 2; } x = 1; print f();").is_empty());
+        assert!(check("let x = \"a\"; fn f() { x = 1; } f(); print x * 2;").is_empty());
+        assert_eq!(
+            vec!["cannot apply `-` to String and Integer".to_string()],
+            check("fn f() { let s = \"a\"; return s - 1; }")
+        );
+    }
+
+    #[test]
+    fn record_node_types() {
+        let checker = TypeChecker::new();
+        checker.infer(&parse_expr("1 + 2.0")).unwrap();
+        assert_eq!(
+            vec![
+                (Span::new(0, 1), Type::INTEGER),
+                (Span::new(4, 7), Type::FLOAT),
+                (Span::new(0, 7), Type::FLOAT)
+            ],
+            checker.types()
+        );
+    }
+
+    /// 能正常执行的程序不会报告类型错误
+    #[test]
+    fn no_errors_for_valid_programs() {
+        let programs = [
+            "fn fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);",
+            "fn counter() { let n = 0; return fn () { n = n + 1; return n; }; }\
+             let next = counter(); print next() + next();",
+            "let s = \"\"; for (let i = 0; i < 3; i = i + 1) { s = s + \"${i}\"; } print s;",
+            "let total = 0; let i = 0; while (i < 5) { i = i + 1; if (i == 2) continue; \
+             total = total + i * 1.5; } print total;",
+            "let v = nil; v = 1; print -v; print v / 2 >= 0.5 && \"x\" != nil;",
+        ];
+        for program in programs {
+            let statements = parse_program(program);
+            assert_eq!(Ok(()), TypeChecker::new().check(&statements), "{}", program);
+            let interpreter = Interpreter::new().with_output(Box::new(std::io::sink()));
+            assert_eq!(Ok(()), interpreter.execute(&statements), "{}", program);
+        }
+    }
+}
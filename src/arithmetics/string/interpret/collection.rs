@@ -0,0 +1,237 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::ptr;
+
+use super::literal::Literal;
+
+/// 列表，多个变量可以共享并修改同一个列表
+#[derive(Default)]
+pub struct List(RefCell<Vec<Literal>>);
+
+impl List {
+    pub fn new(values: Vec<Literal>) -> Self {
+        List(RefCell::new(values))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Literal> {
+        self.0.borrow().get(index).cloned()
+    }
+
+    /// 下标越界时返回 `false`
+    pub fn set(&self, index: usize, value: Literal) -> bool {
+        match self.0.borrow_mut().get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn push(&self, value: Literal) {
+        self.0.borrow_mut().push(value);
+    }
+
+    /// 当前元素的副本，遍历时修改列表不影响已取出的元素
+    pub fn values(&self) -> Vec<Literal> {
+        self.0.borrow().clone()
+    }
+}
+
+/// 映射，按插入顺序保存键值对
+///
+/// 键按语言中的 `==` 比较（`1` 与 `1.0` 是同一个键），`Float` 不能作为
+/// `HashMap` 的键，因此查找是线性的。
+#[derive(Default)]
+pub struct Map(RefCell<Vec<(Literal, Literal)>>);
+
+impl Map {
+    pub fn new() -> Self {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    pub fn get(&self, key: &Literal) -> Option<Literal> {
+        self.0
+            .borrow()
+            .iter()
+            .find(|(existing, _)| existing.equals(key))
+            .map(|(_, value)| value.clone())
+    }
+
+    /// 已有的键保持原来的位置，只替换值
+    pub fn insert(&self, key: Literal, value: Literal) {
+        let mut entries = self.0.borrow_mut();
+        match entries
+            .iter_mut()
+            .find(|(existing, _)| existing.equals(&key))
+        {
+            Some((_, slot)) => *slot = value,
+            None => entries.push((key, value)),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Literal> {
+        self.0.borrow().iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn entries(&self) -> Vec<(Literal, Literal)> {
+        self.0.borrow().clone()
+    }
+}
+
+/// 与函数一样，列表与映射只与自身相等
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+/// 正在输出的列表与映射的地址，用于发现引用自身的集合
+type Seen = Vec<*const ()>;
+
+/// 嵌套的字符串加引号，引用自身的集合显示为 `[...]` 或 `{...}`
+fn write_element(f: &mut fmt::Formatter<'_>, value: &Literal, seen: &mut Seen) -> fmt::Result {
+    match value {
+        Literal::String(text) => write!(f, "{:?}", text),
+        Literal::List(list) => list.write(f, seen),
+        Literal::Map(map) => map.write(f, seen),
+        _ => write!(f, "{}", value),
+    }
+}
+
+/// 值在集合中的显示形式，字符串带引号，如 `"a"`
+pub fn quoted(value: &Literal) -> String {
+    match value {
+        Literal::String(text) => format!("{:?}", text),
+        _ => value.to_string(),
+    }
+}
+
+impl List {
+    fn write(&self, f: &mut fmt::Formatter<'_>, seen: &mut Seen) -> fmt::Result {
+        let address = self as *const List as *const ();
+        if seen.contains(&address) {
+            return f.write_str("[...]");
+        }
+        seen.push(address);
+        f.write_str("[")?;
+        for (index, value) in self.0.borrow().iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write_element(f, value, seen)?;
+        }
+        seen.pop();
+        f.write_str("]")
+    }
+}
+
+impl Map {
+    fn write(&self, f: &mut fmt::Formatter<'_>, seen: &mut Seen) -> fmt::Result {
+        let address = self as *const Map as *const ();
+        if seen.contains(&address) {
+            return f.write_str("{...}");
+        }
+        seen.push(address);
+        f.write_str("{")?;
+        for (index, (key, value)) in self.0.borrow().iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write_element(f, key, seen)?;
+            f.write_str(": ")?;
+            write_element(f, value, seen)?;
+        }
+        seen.pop();
+        f.write_str("}")
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+// 集合可能包含自身，派生的 Debug 会无限递归
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "List({})", self)
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Map({})", self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn map_keys_use_language_equality() {
+        let map = Map::new();
+        map.insert(Literal::Integer(1), Literal::String("a".to_string()));
+        map.insert(Literal::String("k".to_string()), Literal::None);
+        map.insert(Literal::Float(1.0), Literal::String("b".to_string()));
+        assert_eq!(2, map.len());
+        assert_eq!(
+            Some(Literal::String("b".to_string())),
+            map.get(&Literal::Integer(1))
+        );
+        assert_eq!(None, map.get(&Literal::String("1".to_string())));
+        assert_eq!("{1: \"b\", \"k\": nil}", map.to_string());
+    }
+
+    #[test]
+    fn display_self_reference() {
+        let list = Rc::new(List::new(vec![Literal::Integer(1)]));
+        list.push(Literal::List(list.clone()));
+        let map = Rc::new(Map::new());
+        map.insert(
+            Literal::String("list".to_string()),
+            Literal::List(list.clone()),
+        );
+        map.insert(
+            Literal::String("self".to_string()),
+            Literal::Map(map.clone()),
+        );
+        assert_eq!("[1, [...]]", list.to_string());
+        assert_eq!("{\"list\": [1, [...]], \"self\": {...}}", map.to_string());
+        // 打破引用环，避免测试泄漏内存
+        list.set(1, Literal::None);
+        map.insert(Literal::String("self".to_string()), Literal::None);
+    }
+}
@@ -0,0 +1,819 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+use std::slice;
+
+use super::bytecode::{Chunk, OpCode, Prototype};
+use super::expr::{Expr, ExprVisitor, InterpolationPart};
+use super::literal::Literal;
+use super::stmt::{FunctionDecl, Stmt, StmtVisitor};
+use super::token::{Span, Token, TokenType};
+
+/// 超出字节码的表示范围，如常量过多或跳转距离过远
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl CompileError {
+    pub fn new(message: &str, span: Span) -> Self {
+        CompileError {
+            message: message.to_string(),
+            span,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// 正在编译的循环
+struct Loop {
+    /// 循环体外的作用域层数，`break`/`continue` 跳转前需要退出更内层的作用域
+    scope_depth: usize,
+    /// `while` 的 `continue` 跳回条件判断；`for` 的增量表达式在循环体之后，为 `None`
+    continue_target: Option<usize>,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// 将语句编译为 `Chunk`，每个函数体编译为单独的 `Chunk`
+///
+/// 块与函数体中声明的变量在进入作用域时分配槽位，按（层数, 下标）访问；
+/// 不在任何块中声明的变量按名称访问全局作用域。
+pub struct Compiler {
+    chunk: RefCell<Chunk>,
+    /// 外层函数与当前函数的各层作用域的槽位名称，由外向内
+    scopes: RefCell<Vec<Rc<[String]>>>,
+    /// 当前函数内运行时创建的块作用域层数
+    scope_depth: Cell<usize>,
+    loops: RefCell<Vec<Loop>>,
+    /// 正在编译的表达式的区间，没有运算符的指令使用该区间
+    span: Cell<Span>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::named("<script>", Vec::new())
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler::default()
+    }
+
+    fn named(name: &str, scopes: Vec<Rc<[String]>>) -> Self {
+        Compiler {
+            chunk: RefCell::new(Chunk::new(name)),
+            scopes: RefCell::new(scopes),
+            scope_depth: Cell::new(0),
+            loops: RefCell::new(Vec::new()),
+            span: Cell::new(Span::default()),
+        }
+    }
+
+    /// 编译一段程序，执行结果为 `nil`
+    pub fn compile(self, statements: &[Stmt]) -> Result<Chunk, CompileError> {
+        for statement in statements {
+            statement.accept(&self)?;
+        }
+        self.emit(OpCode::Nil, self.span.get());
+        self.emit(OpCode::Return, self.span.get());
+        Ok(self.chunk.into_inner())
+    }
+
+    /// 编译单个表达式，执行结果为表达式的值
+    pub fn compile_expr(self, expr: &Expr) -> Result<Chunk, CompileError> {
+        self.expression(expr)?;
+        self.emit(OpCode::Return, expr.span());
+        Ok(self.chunk.into_inner())
+    }
+
+    fn function(&self, declaration: &Rc<FunctionDecl>) -> Result<Prototype, CompileError> {
+        let name = declaration
+            .name
+            .as_ref()
+            .map_or("<fn>", |name| identifier(name));
+        let mut names = Vec::new();
+        for param in &declaration.params {
+            declare(&mut names, identifier(param));
+        }
+        hoist(&mut names, &declaration.body);
+        let locals: Rc<[String]> = Rc::from(names);
+        let mut scopes = self.scopes.borrow().clone();
+        scopes.push(locals.clone());
+        let compiler = Compiler::named(name, scopes);
+        compiler.span.set(declaration.span);
+        {
+            let mut chunk = compiler.chunk.borrow_mut();
+            chunk.params = declaration
+                .params
+                .iter()
+                .map(|param| slot(&locals, identifier(param)))
+                .collect();
+            chunk.locals = locals;
+        }
+        let chunk = compiler.compile(&declaration.body)?;
+        Ok(Prototype {
+            declaration: declaration.clone(),
+            chunk: Rc::new(chunk),
+        })
+    }
+
+    fn expression(&self, expr: &Expr) -> Result<(), CompileError> {
+        let previous = self.span.replace(expr.span());
+        let result = expr.accept(self);
+        self.span.set(previous);
+        result
+    }
+
+    fn emit(&self, op: OpCode, span: Span) {
+        self.chunk.borrow_mut().write(op as u8, span);
+    }
+
+    fn emit_u16(&self, op: OpCode, operand: u16, span: Span) {
+        let mut chunk = self.chunk.borrow_mut();
+        chunk.write(op as u8, span);
+        for byte in operand.to_be_bytes() {
+            chunk.write(byte, span);
+        }
+    }
+
+    fn operand(index: usize, message: &str, span: Span) -> Result<u16, CompileError> {
+        u16::try_from(index).map_err(|_| CompileError::new(message, span))
+    }
+
+    fn emit_constant(&self, op: OpCode, value: Literal, span: Span) -> Result<(), CompileError> {
+        let index = self.chunk.borrow_mut().add_constant(value);
+        let index = Compiler::operand(index, "Too many constants in one chunk.", span)?;
+        self.emit_u16(op, index, span);
+        Ok(())
+    }
+
+    fn emit_name(&self, op: OpCode, name: &Token) -> Result<(), CompileError> {
+        let value = Literal::String(identifier(name).to_string());
+        self.emit_constant(op, value, name.span)
+    }
+
+    /// 由内向外查找声明了 `name` 的作用域，返回（向外的层数, 槽位下标）
+    fn resolve(&self, name: &str) -> Option<(usize, usize)> {
+        let scopes = self.scopes.borrow();
+        scopes.iter().rev().enumerate().find_map(|(depth, scope)| {
+            let index = scope.iter().position(|slot| slot == name)?;
+            Some((depth, index))
+        })
+    }
+
+    /// 读取或赋值：`op` 为 `GetVar` 或 `SetVar`，局部变量改用对应的槽位指令
+    fn emit_variable(&self, op: OpCode, name: &Token) -> Result<(), CompileError> {
+        let (depth, index) = match self.resolve(identifier(name)) {
+            Some(local) => local,
+            None => return self.emit_name(op, name),
+        };
+        let op = match op {
+            OpCode::GetVar => OpCode::GetLocal,
+            _ => OpCode::SetLocal,
+        };
+        let depth = Compiler::operand(depth, "Too many nested scopes.", name.span)?;
+        let index = Compiler::operand(index, "Too many local variables.", name.span)?;
+        self.emit_u16(op, depth, name.span);
+        let mut chunk = self.chunk.borrow_mut();
+        for byte in index.to_be_bytes() {
+            chunk.write(byte, name.span);
+        }
+        Ok(())
+    }
+
+    /// 在当前作用域定义变量，块中的变量已在进入作用域时分配槽位
+    fn emit_define(&self, name: &str, span: Span) -> Result<(), CompileError> {
+        let index = match self.scopes.borrow().last() {
+            Some(scope) => slot(scope, name),
+            None => {
+                let value = Literal::String(name.to_string());
+                return self.emit_constant(OpCode::DefineVar, value, span);
+            }
+        };
+        let index = Compiler::operand(index, "Too many local variables.", span)?;
+        self.emit_u16(OpCode::DefineLocal, index, span);
+        Ok(())
+    }
+
+    /// 写入跳转指令，返回待回填的操作数偏移
+    fn emit_jump(&self, op: OpCode) -> usize {
+        self.emit_u16(op, u16::MAX, self.span.get());
+        self.chunk.borrow().code.len() - 2
+    }
+
+    /// 让 `offset` 处的跳转指向当前位置
+    fn patch_jump(&self, offset: usize) -> Result<(), CompileError> {
+        let mut chunk = self.chunk.borrow_mut();
+        let distance = chunk.code.len() - offset - 2;
+        let distance = Compiler::operand(distance, "Too much code to jump over.", self.span.get())?;
+        chunk.code[offset..offset + 2].copy_from_slice(&distance.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_loop(&self, start: usize) -> Result<(), CompileError> {
+        let distance = self.chunk.borrow().code.len() + 3 - start;
+        let distance = Compiler::operand(distance, "Loop body too large.", self.span.get())?;
+        self.emit_u16(OpCode::Loop, distance, self.span.get());
+        Ok(())
+    }
+
+    fn position(&self) -> usize {
+        self.chunk.borrow().code.len()
+    }
+
+    /// 进入声明了 `names` 的块作用域；没有声明的块不创建作用域，返回 `false`
+    fn begin_scope(&self, names: Vec<String>) -> Result<bool, CompileError> {
+        if names.is_empty() {
+            return Ok(false);
+        }
+        let names: Rc<[String]> = Rc::from(names);
+        let mut chunk = self.chunk.borrow_mut();
+        chunk.scopes.push(names.clone());
+        let index = Compiler::operand(
+            chunk.scopes.len() - 1,
+            "Too many scopes in one chunk.",
+            self.span.get(),
+        )?;
+        drop(chunk);
+        self.emit_u16(OpCode::PushScope, index, self.span.get());
+        self.scopes.borrow_mut().push(names);
+        self.scope_depth.set(self.scope_depth.get() + 1);
+        Ok(true)
+    }
+
+    fn end_scope(&self, scoped: bool) {
+        if !scoped {
+            return;
+        }
+        self.emit(OpCode::PopScope, self.span.get());
+        self.scopes.borrow_mut().pop();
+        self.scope_depth.set(self.scope_depth.get() - 1);
+    }
+
+    /// 跳出循环前退出循环体内的作用域
+    fn exit_scopes(&self, scope_depth: usize) {
+        for _ in scope_depth..self.scope_depth.get() {
+            self.emit(OpCode::PopScope, self.span.get());
+        }
+    }
+
+    /// 最内层循环的作用域层数与 `continue` 的目标；语法树不一定来自 `Parser`，需要检查
+    fn loop_context(&self, keyword: &Token) -> Result<(usize, Option<usize>), CompileError> {
+        let loops = self.loops.borrow();
+        let context = loops
+            .last()
+            .ok_or_else(|| CompileError::new("Must be inside a loop.", keyword.span))?;
+        Ok((context.scope_depth, context.continue_target))
+    }
+
+    fn closure(&self, declaration: &Rc<FunctionDecl>) -> Result<(), CompileError> {
+        let prototype = self.function(declaration)?;
+        let mut chunk = self.chunk.borrow_mut();
+        chunk.functions.push(prototype);
+        let index = Compiler::operand(
+            chunk.functions.len() - 1,
+            "Too many functions in one chunk.",
+            declaration.span,
+        )?;
+        drop(chunk);
+        self.emit_u16(OpCode::Closure, index, declaration.span);
+        Ok(())
+    }
+}
+
+fn identifier(name: &Token) -> &str {
+    name.lexeme.as_deref().unwrap_or_default()
+}
+
+fn declare(names: &mut Vec<String>, name: &str) {
+    if !names.iter().any(|slot| slot == name) {
+        names.push(name.to_string());
+    }
+}
+
+/// 收集直接在 `statements` 中声明的变量，嵌套的语句只能是块，声明在各自的作用域中
+fn hoist(names: &mut Vec<String>, statements: &[Stmt]) {
+    for statement in statements {
+        match statement {
+            Stmt::Let { name, .. } => declare(names, identifier(name)),
+            Stmt::Function(declaration) => {
+                declare(names, declaration.name.as_ref().map_or("", identifier))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 作用域在进入时已收集其中的全部声明，因此 `name` 一定有槽位
+fn slot(scope: &[String], name: &str) -> usize {
+    scope
+        .iter()
+        .position(|slot| slot == name)
+        .unwrap_or_else(|| unreachable!("`{}` is declared when its scope begins", name))
+}
+
+impl ExprVisitor for Compiler {
+    type ReturnType = Result<(), CompileError>;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.expression(left)?;
+        self.expression(right)?;
+        let op = match operator.token_type {
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            _ => return Err(CompileError::new("Invalid binary operator.", operator.span)),
+        };
+        self.emit(op, operator.span);
+        Ok(())
+    }
+
+    /// 与 `Interpreter` 一样，结果为操作数的真值
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.expression(left)?;
+        let short_circuit = self.emit_jump(OpCode::JumpIfFalse);
+        let end = match operator.token_type {
+            TokenType::Or => {
+                self.emit(OpCode::True, operator.span);
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(short_circuit)?;
+                self.expression(right)?;
+                self.emit(OpCode::Truthy, operator.span);
+                end
+            }
+            TokenType::And => {
+                self.expression(right)?;
+                self.emit(OpCode::Truthy, operator.span);
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(short_circuit)?;
+                self.emit(OpCode::False, operator.span);
+                end
+            }
+            _ => {
+                return Err(CompileError::new(
+                    "Invalid logical operator.",
+                    operator.span,
+                ))
+            }
+        };
+        self.patch_jump(end)
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        self.expression(expr)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.expression(right)?;
+        let op = match operator.token_type {
+            TokenType::Bang => OpCode::Not,
+            TokenType::Minus => OpCode::Negate,
+            _ => return Err(CompileError::new("Invalid unary operator.", operator.span)),
+        };
+        self.emit(op, operator.span);
+        Ok(())
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        let span = self.span.get();
+        match literal {
+            Literal::None => self.emit(OpCode::Nil, span),
+            Literal::Bool(true) => self.emit(OpCode::True, span),
+            Literal::Bool(false) => self.emit(OpCode::False, span),
+            _ => self.emit_constant(OpCode::Constant, literal.clone(), span)?,
+        }
+        Ok(())
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        self.emit_variable(OpCode::GetVar, name)
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        self.expression(value)?;
+        self.emit_variable(OpCode::SetVar, name)
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        let span = callee.span().merge(paren.span);
+        self.expression(callee)?;
+        for argument in arguments {
+            self.expression(argument)?;
+        }
+        let count = u8::try_from(arguments.len())
+            .map_err(|_| CompileError::new("Can't have more than 255 arguments.", span))?;
+        self.emit(OpCode::Call, span);
+        self.chunk.borrow_mut().write(count, span);
+        Ok(())
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.closure(declaration)
+    }
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        let span = self.span.get();
+        for part in parts {
+            match part {
+                InterpolationPart::Text(text) => {
+                    self.emit_constant(OpCode::Constant, Literal::String(text.clone()), span)?
+                }
+                InterpolationPart::Expr(expr) => self.expression(expr)?,
+            }
+        }
+        let count = Compiler::operand(parts.len(), "Too many interpolated parts.", span)?;
+        self.emit_u16(OpCode::Interpolate, count, span);
+        Ok(())
+    }
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        for element in elements {
+            self.expression(element)?;
+        }
+        let span = self.span.get();
+        let count = Compiler::operand(elements.len(), "Too many list elements.", span)?;
+        self.emit_u16(OpCode::BuildList, count, span);
+        Ok(())
+    }
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        for (key, value) in entries {
+            self.expression(key)?;
+            self.expression(value)?;
+        }
+        let span = self.span.get();
+        let count = Compiler::operand(entries.len(), "Too many map entries.", span)?;
+        self.emit_u16(OpCode::BuildMap, count, span);
+        Ok(())
+    }
+
+    fn visit_index(&self, object: &Expr, bracket: &Token, index: &Expr) -> Self::ReturnType {
+        self.expression(object)?;
+        self.expression(index)?;
+        self.emit(OpCode::GetIndex, object.span().merge(bracket.span));
+        Ok(())
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        self.expression(object)?;
+        self.expression(index)?;
+        self.expression(value)?;
+        self.emit(OpCode::SetIndex, object.span().merge(bracket.span));
+        Ok(())
+    }
+}
+
+impl StmtVisitor for Compiler {
+    type ReturnType = Result<(), CompileError>;
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        self.expression(expr)?;
+        self.emit(OpCode::Pop, expr.span());
+        Ok(())
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        self.expression(expr)?;
+        self.emit(OpCode::Print, expr.span());
+        Ok(())
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        match initializer {
+            Some(initializer) => self.expression(initializer)?,
+            None => self.emit(OpCode::Nil, name.span),
+        }
+        self.emit_define(identifier(name), name.span)
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        let mut names = Vec::new();
+        hoist(&mut names, statements);
+        let scoped = self.begin_scope(names)?;
+        for statement in statements {
+            statement.accept(self)?;
+        }
+        self.end_scope(scoped);
+        Ok(())
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        self.expression(condition)?;
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        then_branch.accept(self)?;
+        match else_branch {
+            Some(else_branch) => {
+                let end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump)?;
+                else_branch.accept(self)?;
+                self.patch_jump(end)
+            }
+            None => self.patch_jump(else_jump),
+        }
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        let start = self.position();
+        self.expression(condition)?;
+        let exit = self.emit_jump(OpCode::JumpIfFalse);
+        self.loops.borrow_mut().push(Loop {
+            scope_depth: self.scope_depth.get(),
+            continue_target: Some(start),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        body.accept(self)?;
+        self.emit_loop(start)?;
+        let context = self.loops.borrow_mut().pop().expect("loop context");
+        self.patch_jump(exit)?;
+        for jump in context.breaks {
+            self.patch_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    /// 初始化语句声明的变量只在循环内可见
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        let mut names = Vec::new();
+        hoist(
+            &mut names,
+            initializer.map(slice::from_ref).unwrap_or_default(),
+        );
+        let scoped = self.begin_scope(names)?;
+        if let Some(initializer) = initializer {
+            initializer.accept(self)?;
+        }
+        let start = self.position();
+        let exit = match condition {
+            Some(condition) => {
+                self.expression(condition)?;
+                Some(self.emit_jump(OpCode::JumpIfFalse))
+            }
+            None => None,
+        };
+        self.loops.borrow_mut().push(Loop {
+            scope_depth: self.scope_depth.get(),
+            continue_target: None,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        body.accept(self)?;
+        let context = self.loops.borrow_mut().pop().expect("loop context");
+        for jump in context.continues {
+            self.patch_jump(jump)?;
+        }
+        if let Some(increment) = increment {
+            self.expression(increment)?;
+            self.emit(OpCode::Pop, increment.span());
+        }
+        self.emit_loop(start)?;
+        if let Some(exit) = exit {
+            self.patch_jump(exit)?;
+        }
+        for jump in context.breaks {
+            self.patch_jump(jump)?;
+        }
+        self.end_scope(scoped);
+        Ok(())
+    }
+
+    /// 待遍历的列表与下标留在栈上，循环结束（包括 `break`）后弹出；
+    /// 每次迭代在新的作用域中定义循环变量
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        self.expression(iterable)?;
+        self.emit(OpCode::Iterate, iterable.span());
+        let start = self.position();
+        let exit = self.emit_jump(OpCode::ForNext);
+        self.loops.borrow_mut().push(Loop {
+            scope_depth: self.scope_depth.get(),
+            continue_target: Some(start),
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.begin_scope(vec![identifier(name).to_string()])?;
+        self.emit_define(identifier(name), name.span)?;
+        body.accept(self)?;
+        self.end_scope(true);
+        self.emit_loop(start)?;
+        let context = self.loops.borrow_mut().pop().expect("loop context");
+        self.patch_jump(exit)?;
+        for jump in context.breaks {
+            self.patch_jump(jump)?;
+        }
+        self.emit(OpCode::Pop, self.span.get());
+        self.emit(OpCode::Pop, self.span.get());
+        Ok(())
+    }
+
+    fn visit_break(&self, keyword: &Token) -> Self::ReturnType {
+        self.span.set(keyword.span);
+        let scope_depth = self.loop_context(keyword)?.0;
+        self.exit_scopes(scope_depth);
+        let jump = self.emit_jump(OpCode::Jump);
+        if let Some(context) = self.loops.borrow_mut().last_mut() {
+            context.breaks.push(jump);
+        }
+        Ok(())
+    }
+
+    fn visit_continue(&self, keyword: &Token) -> Self::ReturnType {
+        self.span.set(keyword.span);
+        let (scope_depth, target) = self.loop_context(keyword)?;
+        self.exit_scopes(scope_depth);
+        match target {
+            Some(start) => self.emit_loop(start),
+            None => {
+                let jump = self.emit_jump(OpCode::Jump);
+                if let Some(context) = self.loops.borrow_mut().last_mut() {
+                    context.continues.push(jump);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.closure(declaration)?;
+        match &declaration.name {
+            Some(name) => self.emit_define(identifier(name), name.span),
+            None => self.emit_define("", declaration.span),
+        }
+    }
+
+    fn visit_return(&self, keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        match value {
+            Some(value) => self.expression(value)?,
+            None => self.emit(OpCode::Nil, keyword.span),
+        }
+        self.emit(OpCode::Return, keyword.span);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::testing::{parse_expr, parse_program};
+    use super::super::token::Position;
+    use super::*;
+
+    fn compile(source: &str) -> Chunk {
+        let statements = parse_program(source);
+        Compiler::new().compile(&statements).unwrap()
+    }
+
+    fn ops(chunk: &Chunk) -> Vec<OpCode> {
+        let mut ops = Vec::new();
+        let mut offset = 0;
+        while offset < chunk.code.len() {
+            let op = OpCode::from_byte(chunk.code[offset]).unwrap();
+            ops.push(op);
+            offset += 1 + op.operand_len();
+        }
+        ops
+    }
+
+    #[test]
+    fn compile_expression_statement() {
+        let chunk = compile("print 1 + 2 * -a;");
+        assert_eq!(
+            vec![
+                OpCode::Constant,
+                OpCode::Constant,
+                OpCode::GetVar,
+                OpCode::Negate,
+                OpCode::Multiply,
+                OpCode::Add,
+                OpCode::Print,
+                OpCode::Nil,
+                OpCode::Return
+            ],
+            ops(&chunk)
+        );
+        assert_eq!(Some(Span::new(8, 9)), chunk.span(11));
+    }
+
+    #[test]
+    fn compile_loop_control() {
+        let chunk =
+            compile("for (let i = 0; i < 3; i = i + 1) { let j = i; if (j) continue; break; }");
+        let ops = ops(&chunk);
+        assert_eq!(OpCode::PushScope, ops[0]);
+        assert_eq!(OpCode::PopScope, ops[ops.len() - 3]);
+        // 循环与循环体各一个，`continue` 与 `break` 跳转前各退出一次循环体
+        assert_eq!(4, ops.iter().filter(|&&op| op == OpCode::PopScope).count());
+    }
+
+    #[test]
+    fn compile_local_slots() {
+        let chunk = compile("let g = 1; { let a = g; { a = a + 1; } fn f(x) { return a + x; } }");
+        let ops = ops(&chunk);
+        // 没有声明的块不创建作用域
+        assert_eq!(1, ops.iter().filter(|&&op| op == OpCode::PushScope).count());
+        assert_eq!(
+            vec!["a".to_string(), "f".to_string()],
+            chunk.scopes[0].to_vec()
+        );
+        let function = &chunk.functions[0].chunk;
+        assert_eq!(vec!["x".to_string()], function.locals.to_vec());
+        assert_eq!(vec![0], function.params);
+        let text = chunk.disassemble();
+        assert!(text.contains("PushScope           0 [a, f]"), "{}", text);
+        assert!(text.contains("GetVar              1 'g'"), "{}", text);
+        assert!(text.contains("DefineLocal         0"), "{}", text);
+        assert!(text.contains("SetLocal            0    0"), "{}", text);
+        // 函数体中 `a` 在外一层，`x` 在函数体的作用域中
+        assert!(text.contains("GetLocal            1    0"), "{}", text);
+        assert!(text.contains("GetLocal            0    0"), "{}", text);
+    }
+
+    #[test]
+    fn compile_functions_into_prototypes() {
+        let chunk = compile("fn add(a, b) { return a + b; } print add(1, 2);");
+        assert_eq!(1, chunk.functions.len());
+        assert_eq!("add", chunk.functions[0].chunk.name);
+        let text = chunk.disassemble();
+        assert!(text.starts_with("== <script> ==\n"), "{}", text);
+        assert!(text.contains("Closure             0 <add>"), "{}", text);
+        assert!(text.contains("== add ==\n"), "{}", text);
+        assert!(text.contains("DefineVar           0 'add'"), "{}", text);
+    }
+
+    /// 语法树可以不经过 `Parser` 直接构造，不合法的树报告编译错误而不是 panic
+    #[test]
+    fn compile_invalid_tree() {
+        let mut expr = parse_expr("1 + -a");
+        if let Expr::Binary { right, .. } = &mut expr {
+            if let Expr::Unary { operator, .. } = right.as_mut() {
+                operator.token_type = TokenType::Plus;
+            }
+        }
+        let error = Compiler::new().compile_expr(&expr).unwrap_err();
+        assert_eq!(
+            CompileError::new("Invalid unary operator.", Span::new(4, 5)),
+            error
+        );
+        let mut expr = parse_expr("1 + -a");
+        if let Expr::Binary { operator, .. } = &mut expr {
+            operator.token_type = TokenType::Bang;
+        }
+        let error = Compiler::new().compile_expr(&expr).unwrap_err();
+        assert_eq!(
+            CompileError::new("Invalid binary operator.", Span::new(2, 3)),
+            error
+        );
+
+        let keyword = Token::new(
+            TokenType::Continue,
+            Some("continue".to_string()),
+            Position::new(1, 1),
+            Span::new(0, 8),
+        );
+        let error = Compiler::new()
+            .compile(&[Stmt::Continue(keyword)])
+            .unwrap_err();
+        assert_eq!(
+            CompileError::new("Must be inside a loop.", Span::new(0, 8)),
+            error
+        );
+    }
+
+    #[test]
+    fn compile_too_many_arguments() {
+        let arguments = vec!["1"; 256].join(", ");
+        let statements = parse_program(&format!("f({});", arguments));
+        let error = Compiler::new().compile(&statements).unwrap_err();
+        assert_eq!("Can't have more than 255 arguments.", error.message);
+    }
+}
@@ -0,0 +1,519 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+
+use super::checker::{TypeError, TypeErrorKind};
+use super::interpreter::{RuntimeError, RuntimeErrorKind};
+use super::parser::ParseError;
+use super::scanner::{LexError, LexErrorKind};
+use super::token::Span;
+
+/// 源码区间上的标注，主标注用 `^` 下划线，次要标注用 `-`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: &str) -> Self {
+        Label {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// rustc 风格的错误报告
+///
+/// ```text
+/// error[E0003]: unterminated string literal
+///  --> input:1:5
+///   |
+/// 1 | 1 + "abc
+///   |     ^ unterminated string literal started here
+///   |
+///   = help: add a closing `"` to end the string
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    /// 错误没有位置时为 `None`，报告中不显示源码片段
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: &str, span: Span) -> Self {
+        Diagnostic {
+            code,
+            primary: Some(Label::new(span, "")),
+            ..Diagnostic::unlocated(code, message)
+        }
+    }
+
+    /// 不对应任何源码位置的错误
+    pub fn unlocated(code: &'static str, message: &str) -> Self {
+        Diagnostic {
+            code,
+            message: message.to_string(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            help: None,
+        }
+    }
+
+    /// 主标注下划线后的说明文字，没有主标注时忽略
+    pub fn with_label(mut self, message: &str) -> Self {
+        if let Some(primary) = &mut self.primary {
+            primary.message = message.to_string();
+        }
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// 以 `name` 作为源文件名渲染报告
+    pub fn render(&self, name: &str, source: &str) -> String {
+        let mut labels: Vec<(&Label, char)> =
+            self.primary.iter().map(|label| (label, '^')).collect();
+        labels.extend(self.secondary.iter().map(|label| (label, '-')));
+
+        let mut lines: Vec<(usize, Vec<Underline>)> = vec![];
+        for (label, marker) in labels {
+            let (line, column) = locate(source, label.span.start);
+            let text = source_line(source, line);
+            // 区间可能来自另一段源码，两端都落到字符边界上
+            let start = source.floor_char_boundary(label.span.start);
+            let end = source.floor_char_boundary(label.span.end).max(start);
+            let width = source[start..end]
+                .chars()
+                .take_while(|c| *c != '\n')
+                .count()
+                .min(text.chars().count().saturating_sub(column))
+                .max(1);
+            let underline = Underline {
+                column,
+                width,
+                marker,
+                message: &label.message,
+            };
+            match lines.iter_mut().find(|(number, _)| *number == line) {
+                Some((_, underlines)) => underlines.push(underline),
+                None => lines.push((line, vec![underline])),
+            }
+        }
+        lines.sort_by_key(|(line, _)| *line);
+
+        let gutter = " ".repeat(
+            lines
+                .last()
+                .map_or(0, |(line, _)| line + 1)
+                .to_string()
+                .len(),
+        );
+
+        let mut report = String::new();
+        let _ = writeln!(report, "error[{}]: {}", self.code, self.message);
+        if let Some(primary) = &self.primary {
+            let (line, column) = locate(source, primary.span.start);
+            let _ = writeln!(report, "{}--> {}:{}:{}", gutter, name, line + 1, column + 1);
+        }
+        if !lines.is_empty() {
+            let _ = writeln!(report, "{} |", gutter);
+        }
+        for (line, underlines) in &lines {
+            let _ = writeln!(
+                report,
+                "{:>width$} | {}",
+                line + 1,
+                source_line(source, *line),
+                width = gutter.len()
+            );
+            for underline in underlines {
+                let marks = format!(
+                    "{}{}",
+                    " ".repeat(underline.column),
+                    underline.marker.to_string().repeat(underline.width)
+                );
+                if underline.message.is_empty() {
+                    let _ = writeln!(report, "{} | {}", gutter, marks);
+                } else {
+                    let _ = writeln!(report, "{} | {} {}", gutter, marks, underline.message);
+                }
+            }
+        }
+        if !lines.is_empty() && (!self.notes.is_empty() || self.help.is_some()) {
+            let _ = writeln!(report, "{} |", gutter);
+        }
+        for note in &self.notes {
+            let _ = writeln!(report, "{} = note: {}", gutter, note);
+        }
+        if let Some(help) = &self.help {
+            let _ = writeln!(report, "{} = help: {}", gutter, help);
+        }
+        report
+    }
+}
+
+/// 一次会话中先后执行的多段源码
+///
+/// 每段源码用 `LexerScanner::with_offset` 占据互不重叠的区间。之前定义的函数
+/// 在后续输入中出错时，诊断信息仍然渲染到定义它的那段源码上。
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    /// 起始偏移、名称与源码，按起始偏移排序
+    sources: RefCell<Vec<(usize, String, String)>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// 记录一段源码，返回扫描它时使用的起始偏移
+    pub fn add(&self, name: &str, source: &str) -> usize {
+        let mut sources = self.sources.borrow_mut();
+        let start = sources
+            .last()
+            .map_or(0, |(start, _, text)| start + text.len() + 1);
+        sources.push((start, name.to_string(), source.to_string()));
+        start
+    }
+
+    /// 渲染到主标注所在的那段源码，不在其中的次要标注被省略；
+    /// 没有主标注时使用最后一段源码
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let sources = self.sources.borrow();
+        let source = match &diagnostic.primary {
+            Some(primary) => sources
+                .iter()
+                .rev()
+                .find(|(start, ..)| *start <= primary.span.start),
+            None => sources.last(),
+        };
+        let Some((start, name, text)) = source else {
+            return diagnostic.render("", "");
+        };
+        let end = start + text.len();
+        let shift = |label: &Label| {
+            (*start <= label.span.start && label.span.end <= end).then(|| {
+                Label::new(
+                    Span::new(label.span.start - start, label.span.end - start),
+                    &label.message,
+                )
+            })
+        };
+        Diagnostic {
+            primary: diagnostic.primary.as_ref().and_then(shift),
+            secondary: diagnostic.secondary.iter().filter_map(shift).collect(),
+            ..diagnostic.clone()
+        }
+        .render(name, text)
+    }
+}
+
+struct Underline<'a> {
+    column: usize,
+    width: usize,
+    marker: char,
+    message: &'a str,
+}
+
+/// 字节偏移对应的行号与列号（从 0 开始，列号按字符计数）
+fn locate(source: &str, offset: usize) -> (usize, usize) {
+    let offset = source.floor_char_boundary(offset);
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (line, source[line_start..offset].chars().count())
+}
+
+fn source_line(source: &str, line: usize) -> &str {
+    source
+        .split('\n')
+        .nth(line)
+        .unwrap_or_default()
+        .trim_end_matches('\r')
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(error: &LexError) -> Self {
+        match error.kind {
+            LexErrorKind::UnexpectedCharacter => Diagnostic::error(
+                "E0001",
+                &format!("unexpected character `{}`", error.character),
+                error.span,
+            )
+            .with_label("not valid here"),
+            LexErrorKind::InvalidNumber => {
+                let label = match error.character {
+                    '.' => "expected a digit after the decimal point",
+                    'e' | 'E' | '+' | '-' => "expected a digit in the exponent",
+                    'x' | 'o' | 'b' => "expected a digit after the base prefix",
+                    _ => "not a valid number",
+                };
+                Diagnostic::error("E0002", "invalid number literal", error.span).with_label(label)
+            }
+            LexErrorKind::InvalidDigit => Diagnostic::error(
+                "E0004",
+                &format!("invalid digit `{}` in number literal", error.character),
+                error.span,
+            )
+            .with_label("not a digit of this base"),
+            LexErrorKind::InvalidEscape if error.character == 'u' => {
+                Diagnostic::error("E0005", "invalid unicode escape", error.span)
+                    .with_label("expected 1 to 6 hex digits in `{}`")
+                    .with_help("write unicode escapes like `\\u{1F600}`")
+            }
+            LexErrorKind::InvalidEscape => Diagnostic::error(
+                "E0005",
+                &format!("unknown character escape `\\{}`", error.character),
+                error.span,
+            )
+            .with_label("unknown escape")
+            .with_help(
+                "supported escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\$`, `\\\"`, `\\\\` and `\\u{...}`",
+            ),
+            LexErrorKind::UnterminatedComment => {
+                let opening = Span::new(error.span.start, error.span.start + 2);
+                Diagnostic::error("E0006", "unterminated block comment", opening)
+                    .with_label("unterminated block comment started here")
+                    .with_note("block comments nest, each `/*` needs its own `*/`")
+                    .with_help("add a closing `*/` to end the comment")
+            }
+            LexErrorKind::UnterminatedString => {
+                let quote = Span::new(error.span.start, error.span.start + 1);
+                Diagnostic::error("E0003", "unterminated string literal", quote)
+                    .with_label("unterminated string literal started here")
+                    .with_help("add a closing `\"` to end the string")
+            }
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let label = match &error.token {
+            Some(token) => format!("found {}", token.token_type),
+            None => "input ends here".to_string(),
+        };
+        Diagnostic::error("E0100", &error.message, error.span).with_label(&label)
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        let diagnostic = match error.span {
+            Some(span) => Diagnostic::error("E0200", &error.to_string(), span),
+            None => Diagnostic::unlocated("E0200", &error.to_string()),
+        };
+        match &error.kind {
+            RuntimeErrorKind::BinaryOperands { left, right, .. } => diagnostic.with_label(
+                &format!("this operator does not support {} and {}", left, right),
+            ),
+            RuntimeErrorKind::UnaryOperand { operand, .. } => {
+                diagnostic.with_label(&format!("this operator does not support {}", operand))
+            }
+            RuntimeErrorKind::Overflow { .. } => diagnostic
+                .with_label("result does not fit in a 64-bit integer")
+                .with_help("select a wrapping, saturating, float or exact `NumericMode`"),
+            RuntimeErrorKind::DivisionByZero => diagnostic.with_label("divisor is zero"),
+            RuntimeErrorKind::UndefinedVariable(_) => diagnostic
+                .with_label("not found in this scope")
+                .with_help("declare it first with `let`"),
+            RuntimeErrorKind::Io(_) => diagnostic.with_label("while printing this value"),
+            RuntimeErrorKind::NotCallable(_) => diagnostic.with_label("this is not a function"),
+            RuntimeErrorKind::Arity { expected, .. } => {
+                diagnostic.with_label(&format!("this function takes {} arguments", expected))
+            }
+            RuntimeErrorKind::StackOverflow { .. } => diagnostic
+                .with_label("call nested too deeply")
+                .with_help("check for unbounded recursion"),
+            RuntimeErrorKind::StackExhausted { .. } => diagnostic
+                .with_label("nested too deeply")
+                .with_help("check for unbounded recursion"),
+            RuntimeErrorKind::NotIndexable(_) => diagnostic.with_label("this is not a List or Map"),
+            RuntimeErrorKind::InvalidIndex(_) => diagnostic.with_label("index must be an Integer"),
+            RuntimeErrorKind::IndexOutOfRange { len, .. } => diagnostic
+                .with_label(&format!("the list has {} elements", len))
+                .with_help("indices start at 0; check with `len()` first"),
+            RuntimeErrorKind::MissingKey(_) => diagnostic.with_label("no such key in this map"),
+            RuntimeErrorKind::NotIterable(_) => diagnostic.with_label("this is not a List or Map"),
+            RuntimeErrorKind::ArgumentType { .. } => {
+                diagnostic.with_label("invalid argument in this call")
+            }
+            RuntimeErrorKind::Host(_) => diagnostic.with_label("raised by this native function"),
+            RuntimeErrorKind::InvalidBytecode(_) => diagnostic
+                .with_label("while executing this instruction")
+                .with_help("run bytecode produced by `Compiler`"),
+            RuntimeErrorKind::InvalidSyntaxTree(_) => diagnostic
+                .with_label("in this expression")
+                .with_help("evaluate syntax trees produced by `Parser`"),
+        }
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(error: &TypeError) -> Self {
+        let diagnostic = Diagnostic::error("E0300", &error.to_string(), error.span);
+        match &error.kind {
+            TypeErrorKind::BinaryOperands { left, right, .. } => diagnostic.with_label(&format!(
+                "this operator does not support {} and {}",
+                left, right
+            )),
+            TypeErrorKind::UnaryOperand { operand, .. } => {
+                diagnostic.with_label(&format!("this operator does not support {}", operand))
+            }
+            TypeErrorKind::NotCallable(callee) => {
+                diagnostic.with_label(&format!("this is a {}, not a function", callee))
+            }
+            TypeErrorKind::NotIndexable(object) | TypeErrorKind::NotIterable(object) => {
+                diagnostic.with_label(&format!("this is a {}, not a List or Map", object))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::testing::{parse_expr, parse_program};
+    use super::super::{
+        checker::TypeChecker, interpreter::Interpreter, parser::Parser, scanner::LexerScanner,
+    };
+    use super::*;
+
+    #[test]
+    fn render_unterminated_string() {
+        let source = "1 +\n  \"abc";
+        let errors = LexerScanner::new(source.to_string()).scan().unwrap_err();
+        let report = Diagnostic::from(&errors[0]).render("input", source);
+        assert_eq!(
+            "error[E0003]: unterminated string literal\n \
+             --> input:2:3\n  \
+             |\n\
+             2 |   \"abc\n  \
+             |   ^ unterminated string literal started here\n  \
+             |\n  \
+             = help: add a closing `\"` to end the string\n",
+            report
+        );
+    }
+
+    #[test]
+    fn render_parse_error() {
+        let source = "(1 + 2) * / 3";
+        let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+        let error = Parser::new(tokens).parse().unwrap_err();
+        let report = Diagnostic::from(&error).render("input", source);
+        assert_eq!(
+            "error[E0100]: Expect expression.\n \
+             --> input:1:11\n  \
+             |\n\
+             1 | (1 + 2) * / 3\n  \
+             |           ^ found `/`\n",
+            report
+        );
+    }
+
+    #[test]
+    fn render_runtime_error() {
+        let source = "1 + \"a\" * 2";
+        let expr = parse_expr(source);
+        let error = Interpreter::new().evaluate(&expr).unwrap_err();
+        let report = Diagnostic::from(&error).render("input", source);
+        assert_eq!(
+            "error[E0200]: cannot apply `*` to String and Integer\n \
+             --> input:1:9\n  \
+             |\n\
+             1 | 1 + \"a\" * 2\n  \
+             |         ^ this operator does not support String and Integer\n",
+            report
+        );
+    }
+
+    #[test]
+    fn render_runtime_error_without_span() {
+        let error = RuntimeError::new(RuntimeErrorKind::Overflow { operator: "+" });
+        assert_eq!(
+            "error[E0200]: integer overflow in `+`\n  \
+             = help: select a wrapping, saturating, float or exact `NumericMode`\n",
+            Diagnostic::from(&error).render("input", "let a = 1;")
+        );
+    }
+
+    #[test]
+    fn render_type_error() {
+        let source = "let s = \"a\";\nprint -s;";
+        let statements = parse_program(source);
+        let errors = TypeChecker::new().check(&statements).unwrap_err();
+        let report = Diagnostic::from(&errors[0]).render("input", source);
+        assert_eq!(
+            "error[E0300]: cannot apply unary `-` to String\n \
+             --> input:2:7\n  \
+             |\n\
+             2 | print -s;\n  \
+             |       ^ this operator does not support String\n",
+            report
+        );
+    }
+
+    #[test]
+    fn render_secondary_labels_and_notes() {
+        let source = "let a = 1;\nlet b = a +\n  c;";
+        let report = Diagnostic::error("E9999", "mismatched", Span::new(21, 22))
+            .with_secondary(Span::new(4, 5), "first defined here")
+            .with_secondary(Span::new(25, 26), "used here")
+            .with_label("operator")
+            .with_note("just a test")
+            .render("demo.txt", source);
+        assert_eq!(
+            "error[E9999]: mismatched\n \
+             --> demo.txt:2:11\n  \
+             |\n\
+             1 | let a = 1;\n  \
+             |     - first defined here\n\
+             2 | let b = a +\n  \
+             |           ^ operator\n\
+             3 |   c;\n  \
+             |   - used here\n  \
+             |\n  \
+             = note: just a test\n",
+            report
+        );
+    }
+
+    #[test]
+    fn render_foreign_span() {
+        // 区间来自另一段源码，落在多字节字符的中间
+        let source = "\"é\" + ééé";
+        let report = Diagnostic::error("E9999", "foreign", Span::new(2, 9))
+            .with_secondary(Span::new(11, 40), "past the end")
+            .render("input", source);
+        assert_eq!(
+            "error[E9999]: foreign\n \
+             --> input:1:2\n  \
+             |\n\
+             1 | \"é\" + ééé\n  \
+             |  ^^^^^^\n  \
+             |         - past the end\n",
+            report
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use std::io::Write;
+use std::rc::Rc;
+
+use super::diagnostics::{Diagnostic, SourceMap};
+use super::function::NativeFunction;
+use super::interpreter::{Interpreter, RuntimeError, RuntimeErrorKind};
+use super::literal::{Literal, NumericMode};
+use super::parser::Parser;
+use super::runner::ScriptError;
+use super::scanner::LexerScanner;
+use super::token::Token;
+
+/// 从 `Literal` 转换为宿主函数的参数
+pub trait FromLiteral: Sized {
+    /// 类型不符时错误信息中的期望类型
+    const EXPECTED: &'static str;
+
+    fn from_literal(value: &Literal) -> Option<Self>;
+}
+
+impl FromLiteral for Literal {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromLiteral for i64 {
+    const EXPECTED: &'static str = "Integer";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        match value {
+            Literal::Integer(val) => Some(*val),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_from_literal_integer {
+    ($($t:ty),*) => {$(
+        /// 超出范围时转换失败，报告 `ArgumentType` 错误
+        impl FromLiteral for $t {
+            const EXPECTED: &'static str =
+                concat!("an Integer in the range of `", stringify!($t), "`");
+
+            fn from_literal(value: &Literal) -> Option<Self> {
+                match value {
+                    Literal::Integer(val) => <$t>::try_from(*val).ok(),
+                    Literal::BigInt(val) => val.to_string().parse().ok(),
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_literal_integer!(i8, i16, i32, isize, u8, u16, u32, u64, usize, i128, u128);
+
+/// 接受任意数值，精确值转换为最接近的浮点数
+impl FromLiteral for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        value.as_f64()
+    }
+}
+
+/// 超出 `f32` 范围的有限值转换失败
+impl FromLiteral for f32 {
+    const EXPECTED: &'static str = "a number in the range of `f32`";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        let val = value.as_f64()?;
+        let narrowed = val as f32;
+        (narrowed.is_finite() || !val.is_finite()).then_some(narrowed)
+    }
+}
+
+impl FromLiteral for bool {
+    const EXPECTED: &'static str = "Bool";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        match value {
+            Literal::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+}
+
+impl FromLiteral for String {
+    const EXPECTED: &'static str = "String";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        match value {
+            Literal::String(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// `nil` 转换为 `None`
+impl<T: FromLiteral> FromLiteral for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        match value {
+            Literal::None => Some(None),
+            _ => T::from_literal(value).map(Some),
+        }
+    }
+}
+
+/// 复制列表的元素，任一元素类型不符时转换失败
+impl<T: FromLiteral> FromLiteral for Vec<T> {
+    const EXPECTED: &'static str = "List";
+
+    fn from_literal(value: &Literal) -> Option<Self> {
+        match value {
+            Literal::List(list) => list.values().iter().map(T::from_literal).collect(),
+            _ => None,
+        }
+    }
+}
+
+/// 宿主函数的返回值，返回 `Err` 时脚本以该错误结束
+pub trait IntoReturn {
+    fn into_return(self) -> Result<Literal, RuntimeError>;
+}
+
+impl<T: Into<Literal>> IntoReturn for T {
+    fn into_return(self) -> Result<Literal, RuntimeError> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Literal>> IntoReturn for Result<T, RuntimeError> {
+    fn into_return(self) -> Result<Literal, RuntimeError> {
+        self.map(Into::into)
+    }
+}
+
+/// 第 `position` 个实参（从 1 开始）
+fn argument<T: FromLiteral>(position: usize, value: &Literal) -> Result<T, RuntimeError> {
+    T::from_literal(value).ok_or_else(|| {
+        RuntimeError::new(RuntimeErrorKind::ArgumentType {
+            position,
+            expected: T::EXPECTED,
+            found: value.type_name(),
+        })
+    })
+}
+
+/// 可以注册为脚本函数的闭包，`Args` 为参数类型组成的元组
+///
+/// 参数类型需要在闭包中写明，如 `|sku: String| ...`。
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> NativeFunction;
+}
+
+macro_rules! impl_into_native {
+    ($($arg:ident: $value:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: IntoReturn,
+            $($arg: FromLiteral,)*
+        {
+            fn into_native(self, name: &str) -> NativeFunction {
+                let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                NativeFunction::new(name, arity, move |_arguments: &[Literal]| {
+                    let mut _position = 0;
+                    $(
+                        let $value = argument::<$arg>(_position + 1, &_arguments[_position])?;
+                        _position += 1;
+                    )*
+                    self($($value),*).into_return()
+                })
+            }
+        }
+    };
+}
+
+impl_into_native!();
+impl_into_native!(A: a);
+impl_into_native!(A: a, B: b);
+impl_into_native!(A: a, B: b, C: c);
+impl_into_native!(A: a, B: b, C: c, D: d);
+
+/// 嵌入脚本的入口，在宿主程序中注册函数、预先绑定全局变量并执行脚本
+#[derive(Default)]
+pub struct Engine {
+    interpreter: Interpreter,
+    /// 执行过的脚本，用于渲染之前定义的函数中发生的错误
+    sources: SourceMap,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine::default()
+    }
+
+    /// 指定整数溢出的处理方式，默认为 `NumericMode::Checked`
+    pub fn with_mode(mut self, mode: NumericMode) -> Self {
+        self.interpreter = self.interpreter.with_mode(mode);
+        self
+    }
+
+    /// 将 `print` 的输出写入 `output`
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.interpreter = self.interpreter.with_output(output);
+        self
+    }
+
+    /// 注册宿主函数，同名函数会被覆盖
+    ///
+    /// 与 `len` 一样定义在全局作用域之外，脚本可以用 `let` 遮蔽它。
+    /// 实参会按参数类型转换，类型不符时报告 `ArgumentType` 错误。
+    pub fn register_fn<Args>(&self, name: &str, function: impl IntoNative<Args>) {
+        let function = Literal::Native(Rc::new(function.into_native(name)));
+        let globals = self.interpreter.environment();
+        globals
+            .enclosing()
+            .unwrap_or(globals)
+            .define(name, function);
+    }
+
+    /// 在执行脚本前定义全局变量
+    pub fn set_global(&self, name: &str, value: impl Into<Literal>) {
+        self.interpreter.environment().define(name, value.into());
+    }
+
+    /// 读取全局变量，未定义或类型不符时返回 `None`
+    pub fn get<T: FromLiteral>(&self, name: &str) -> Option<T> {
+        T::from_literal(&self.interpreter.environment().get(name)?)
+    }
+
+    /// 执行脚本，定义的变量在多次调用之间保留
+    ///
+    /// 出错时可以用 `Engine::render` 生成诊断信息。
+    pub fn run(&self, source: &str) -> Result<(), ScriptError> {
+        let statements = Parser::new(self.scan(source)?)
+            .parse_program()
+            .map_err(ScriptError::Parse)?;
+        self.interpreter
+            .execute(&statements)
+            .map_err(ScriptError::Runtime)
+    }
+
+    /// 对单个表达式求值
+    pub fn eval(&self, source: &str) -> Result<Literal, ScriptError> {
+        let expr = Parser::new(self.scan(source)?)
+            .parse()
+            .map_err(ScriptError::Parse)?;
+        self.interpreter
+            .evaluate(&expr)
+            .map_err(ScriptError::Runtime)
+    }
+
+    /// 将 `run` 或 `eval` 返回的错误渲染为诊断信息，源码片段取自出错代码所在的那个脚本
+    pub fn render(&self, error: &ScriptError) -> String {
+        match error {
+            ScriptError::Io(error) => format!("error: {}\n", error),
+            ScriptError::Lex(errors) => errors
+                .iter()
+                .map(|error| self.sources.render(&Diagnostic::from(error)))
+                .collect(),
+            ScriptError::Parse(error) => self.sources.render(&Diagnostic::from(error)),
+            ScriptError::Runtime(error) => self.sources.render(&Diagnostic::from(error)),
+        }
+    }
+
+    /// 每个脚本的区间互不重叠
+    fn scan(&self, source: &str) -> Result<Vec<Token>, ScriptError> {
+        let offset = self.sources.add("<script>", source);
+        LexerScanner::new(source.to_string())
+            .with_offset(offset)
+            .scan()
+            .map_err(ScriptError::Lex)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use super::super::token::Span;
+    use super::*;
+
+    fn runtime_error(result: Result<Literal, ScriptError>) -> RuntimeError {
+        match result {
+            Err(ScriptError::Runtime(error)) => error,
+            other => panic!("Expect runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn convert_primitives() {
+        assert_eq!(Literal::Integer(1), Literal::from(1));
+        assert_eq!(Literal::Float(0.5), Literal::from(0.5));
+        assert_eq!(Literal::String("a".to_string()), Literal::from("a"));
+        assert_eq!(Literal::None, Literal::from(None::<bool>));
+        assert_eq!(
+            "[1, nil, 3]",
+            Literal::from(vec![Some(1), None, Some(3)]).to_string()
+        );
+        assert_eq!(Some(2.0), f64::from_literal(&Literal::Integer(2)));
+        assert_eq!(None, i64::from_literal(&Literal::Float(2.0)));
+        assert_eq!(Some(None), Option::<String>::from_literal(&Literal::None));
+        assert_eq!(
+            Some(vec![1, 2]),
+            Vec::<i64>::from_literal(&Literal::from(vec![1, 2]))
+        );
+        assert_eq!(None, Vec::<i64>::from_literal(&Literal::from(vec!["a"])));
+    }
+
+    #[test]
+    fn convert_integer_widths() {
+        assert_eq!(Literal::Integer(5), Literal::from(5usize));
+        assert_eq!(Literal::Integer(-3), Literal::from(-3i8));
+        assert_eq!("18446744073709551615", Literal::from(u64::MAX).to_string());
+        assert_eq!(Literal::Float(0.5), Literal::from(0.5f32));
+        assert_eq!(Some(200), u8::from_literal(&Literal::Integer(200)));
+        assert_eq!(None, u8::from_literal(&Literal::Integer(256)));
+        assert_eq!(None, u32::from_literal(&Literal::Integer(-1)));
+        assert_eq!(Some(u64::MAX), u64::from_literal(&Literal::from(u64::MAX)));
+        assert_eq!(None, i32::from_literal(&Literal::Float(1.0)));
+        assert_eq!(Some(1.5), f32::from_literal(&Literal::Float(1.5)));
+        assert_eq!(None, f32::from_literal(&Literal::Float(1e300)));
+        assert_eq!(
+            Some(f32::INFINITY),
+            f32::from_literal(&Literal::Float(f64::INFINITY))
+        );
+
+        let engine = Engine::new();
+        engine.register_fn("double", |n: i32| n * 2);
+        engine.register_fn("half", |x: f32| x / 2.0);
+        engine.set_global("n", 5usize);
+        assert_eq!(Literal::Integer(10), engine.eval("double(n)").unwrap());
+        assert_eq!(Literal::Float(0.75), engine.eval("half(1.5)").unwrap());
+        let error = runtime_error(engine.eval("double(4294967296)"));
+        assert_eq!(
+            "argument 1 must be an Integer in the range of `i32`, found Integer",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn call_registered_functions() {
+        let prices = HashMap::from([("apple".to_string(), 3), ("pear".to_string(), 5)]);
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let engine = Engine::new();
+        let log = calls.clone();
+        engine.register_fn("lookup_price", move |sku: String| {
+            log.borrow_mut().push(sku.clone());
+            prices.get(&sku).copied()
+        });
+        engine.register_fn("answer", || 42);
+        engine.register_fn("clamp", |value: f64, low: f64, high: f64| {
+            value.clamp(low, high)
+        });
+        engine.register_fn("describe", |value: Literal, verbose: bool| {
+            if verbose {
+                format!("{} ({})", value, value.type_name())
+            } else {
+                value.to_string()
+            }
+        });
+        engine.register_fn("sum", |values: Vec<i64>| values.iter().sum::<i64>());
+
+        assert_eq!(
+            Literal::Integer(8),
+            engine
+                .eval("lookup_price(\"apple\") + lookup_price(\"pear\")")
+                .unwrap()
+        );
+        assert_eq!(
+            Literal::None,
+            engine.eval("lookup_price(\"kiwi\")").unwrap()
+        );
+        assert_eq!(vec!["apple", "pear", "kiwi"], *calls.borrow());
+        assert_eq!(Literal::Integer(42), engine.eval("answer()").unwrap());
+        assert_eq!(Literal::Float(1.0), engine.eval("clamp(7, 0, 1)").unwrap());
+        assert_eq!(
+            Literal::from("[1] (List)"),
+            engine.eval("describe([1], true)").unwrap()
+        );
+        assert_eq!(Literal::Integer(6), engine.eval("sum([1, 2, 3])").unwrap());
+        assert_eq!(
+            Literal::from("<native fn answer>"),
+            engine.eval("\"${answer}\"").unwrap()
+        );
+    }
+
+    #[test]
+    fn report_invalid_calls() {
+        let engine = Engine::new();
+        engine.register_fn("twice", |value: i64| value * 2);
+        engine.register_fn("fail", |message: String| -> Result<(), RuntimeError> {
+            Err(RuntimeError::host(&message))
+        });
+
+        let error = runtime_error(engine.eval("twice(\"a\")"));
+        assert_eq!(
+            RuntimeErrorKind::ArgumentType {
+                position: 1,
+                expected: "Integer",
+                found: "String",
+            },
+            error.kind
+        );
+        assert_eq!(Some(Span::new(0, 10)), error.span);
+
+        let error = runtime_error(engine.eval("twice(1, 2)"));
+        assert_eq!(
+            RuntimeErrorKind::Arity {
+                expected: 1,
+                found: 2
+            },
+            error.kind
+        );
+
+        let error = engine
+            .run("let x = 1;\nfail(\"out of stock\");")
+            .unwrap_err();
+        assert_eq!(70, error.exit_code());
+        assert_eq!(
+            "error[E0200]: out of stock\n\
+             \x20--> <script>:2:1\n\
+             \x20 |\n\
+             2 | fail(\"out of stock\");\n\
+             \x20 | ^^^^^^^^^^^^^^^^^^^^ raised by this native function\n",
+            engine.render(&error)
+        );
+    }
+
+    #[test]
+    fn exact_arithmetic_opt_in() {
+        let engine = Engine::new().with_mode(NumericMode::Exact);
+        assert_eq!("1/3", engine.eval("1 / 3").unwrap().to_string());
+        assert_eq!(
+            "9223372036854775808",
+            engine.eval("9223372036854775807 + 1").unwrap().to_string()
+        );
+        let checked = Engine::new();
+        assert_eq!(Literal::Integer(0), checked.eval("1 / 3").unwrap());
+        assert!(checked.eval("9223372036854775807 + 1").is_err());
+    }
+
+    #[test]
+    fn bind_globals() {
+        let engine = Engine::new();
+        engine.set_global("name", "engine");
+        engine.set_global("limit", 3);
+        engine.set_global("ratio", Some(0.5));
+        engine.set_global("items", vec!["a", "b"]);
+        engine
+            .run("let count = 0; for (item in items) count = count + 1; let greeting = \"hi ${name}\";")
+            .unwrap();
+        assert_eq!(Some(2), engine.get::<i64>("count"));
+        assert_eq!(Some("hi engine".to_string()), engine.get("greeting"));
+        assert_eq!(
+            Literal::Bool(true),
+            engine.eval("count < limit && ratio == 0.5").unwrap()
+        );
+        assert_eq!(None, engine.get::<String>("count"));
+        assert_eq!(None, engine.get::<Literal>("missing"));
+
+        // 脚本可以遮蔽宿主函数，`len` 仍然可用
+        engine.register_fn("limit", || 0);
+        engine
+            .run("let len2 = len(items); let answer = limit;")
+            .unwrap();
+        assert_eq!(Some(2), engine.get::<i64>("len2"));
+        assert_eq!(Some(3), engine.get::<i64>("answer"));
+    }
+
+    #[test]
+    fn render_error_from_earlier_run() {
+        let engine = Engine::new();
+        engine.run("fn f(a) { return -a; }").unwrap();
+        let error = engine
+            .run("\" éééééééééééééééééé\"; f(\"x\");")
+            .unwrap_err();
+        assert_eq!(
+            "error[E0200]: cannot apply unary `-` to String\n \
+             --> <script>:1:18\n  \
+             |\n\
+             1 | fn f(a) { return -a; }\n  \
+             |                  ^ this operator does not support String\n",
+            engine.render(&error)
+        );
+        let error = engine.eval("1 +").unwrap_err();
+        assert!(engine.render(&error).contains("1 | 1 +\n"));
+    }
+}
@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::literal::Literal;
+
+/// 变量作用域，内层作用域通过 `enclosing` 链接到外层
+///
+/// `Compiler` 在编译期为块内声明的变量分配槽位，虚拟机按下标读写 `slots`；
+/// 按名称的方法同样能访问槽位中的变量。
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: RefCell<HashMap<String, Literal>>,
+    /// 槽位对应的变量名
+    names: Rc<[String]>,
+    /// 尚未定义的槽位为 `None`
+    slots: RefCell<Vec<Option<Literal>>>,
+    enclosing: Option<Rc<Environment>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    pub fn new_enclosed(enclosing: Rc<Environment>) -> Self {
+        Environment {
+            enclosing: Some(enclosing),
+            ..Environment::default()
+        }
+    }
+
+    /// 带有槽位 `names` 的作用域，槽位初始均未定义
+    pub fn with_slots(names: Rc<[String]>, enclosing: Rc<Environment>) -> Self {
+        Environment {
+            slots: RefCell::new(vec![None; names.len()]),
+            names,
+            ..Environment::new_enclosed(enclosing)
+        }
+    }
+
+    pub fn enclosing(&self) -> Option<Rc<Environment>> {
+        self.enclosing.clone()
+    }
+
+    /// 向外第 `depth` 层作用域，`0` 为自身
+    pub fn ancestor(&self, depth: usize) -> Option<&Environment> {
+        let mut environment = self;
+        for _ in 0..depth {
+            environment = environment.enclosing.as_deref()?;
+        }
+        Some(environment)
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|slot| slot == name)
+    }
+
+    /// 槽位的变量名
+    pub fn slot_name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(String::as_str)
+    }
+
+    /// 读取槽位，未定义时返回 `None`
+    pub fn get_slot(&self, index: usize) -> Option<Literal> {
+        self.slots.borrow().get(index)?.clone()
+    }
+
+    /// 给已定义的槽位赋值，未定义时返回 `false`
+    pub fn assign_slot(&self, index: usize, value: Literal) -> bool {
+        match self.slots.borrow_mut().get_mut(index) {
+            Some(Some(slot)) => {
+                *slot = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 定义槽位中的变量，下标越界时返回 `false`
+    pub fn define_slot(&self, index: usize, value: Literal) -> bool {
+        match self.slots.borrow_mut().get_mut(index) {
+            Some(slot) => {
+                *slot = Some(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 在当前作用域定义变量，同名变量会被覆盖
+    pub fn define(&self, name: &str, value: Literal) {
+        match self.slot(name) {
+            Some(index) => {
+                self.define_slot(index, value);
+            }
+            None => {
+                self.values.borrow_mut().insert(name.to_string(), value);
+            }
+        }
+    }
+
+    /// 由内向外查找变量
+    pub fn get(&self, name: &str) -> Option<Literal> {
+        if let Some(value) = self.values.borrow().get(name) {
+            return Some(value.clone());
+        }
+        if let Some(value) = self.slot(name).and_then(|index| self.get_slot(index)) {
+            return Some(value);
+        }
+        self.enclosing.as_ref()?.get(name)
+    }
+
+    /// 当前作用域中的变量，按名称排序
+    pub fn values(&self) -> Vec<(String, Literal)> {
+        let mut values: Vec<(String, Literal)> = self
+            .values
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        for (name, slot) in self.names.iter().zip(self.slots.borrow().iter()) {
+            if let Some(value) = slot {
+                values.push((name.clone(), value.clone()));
+            }
+        }
+        values.sort_by(|(left, _), (right, _)| left.cmp(right));
+        values
+    }
+
+    /// 给最近一层定义了 `name` 的作用域中的变量赋值，未定义时返回 `false`
+    pub fn assign(&self, name: &str, value: Literal) -> bool {
+        if let Some(slot) = self.values.borrow_mut().get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        if let Some(index) = self.slot(name) {
+            if self.assign_slot(index, value.clone()) {
+                return true;
+            }
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.assign(name, value),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lexical_scoping() {
+        let global = Rc::new(Environment::new());
+        global.define("a", Literal::Integer(1));
+        global.define("b", Literal::Integer(2));
+
+        let local = Environment::new_enclosed(global.clone());
+        local.define("a", Literal::Integer(10));
+        assert_eq!(Some(Literal::Integer(10)), local.get("a"));
+        assert_eq!(Some(Literal::Integer(2)), local.get("b"));
+
+        assert!(local.assign("b", Literal::Integer(20)));
+        assert!(local.assign("a", Literal::Integer(30)));
+        assert_eq!(Some(Literal::Integer(20)), global.get("b"));
+        assert_eq!(Some(Literal::Integer(1)), global.get("a"));
+        assert_eq!(Some(Literal::Integer(30)), local.get("a"));
+
+        assert!(!local.assign("c", Literal::None));
+        assert_eq!(None, local.get("c"));
+    }
+
+    #[test]
+    fn slots_share_names() {
+        let global = Rc::new(Environment::new());
+        global.define("a", Literal::Integer(1));
+        let names: Rc<[String]> = Rc::from(vec!["a".to_string(), "b".to_string()]);
+        let local = Rc::new(Environment::with_slots(names, global.clone()));
+        // 槽位定义之前按名称访问外层
+        assert_eq!(None, local.get_slot(0));
+        assert_eq!(Some(Literal::Integer(1)), local.get("a"));
+        assert!(!local.assign_slot(0, Literal::Integer(2)));
+
+        local.define("a", Literal::Integer(10));
+        assert!(local.define_slot(1, Literal::Integer(20)));
+        assert!(!local.define_slot(2, Literal::None));
+        assert_eq!(Some(Literal::Integer(10)), local.get_slot(0));
+        assert_eq!(Some(Literal::Integer(20)), local.get("b"));
+        assert!(local.assign("a", Literal::Integer(30)));
+        assert_eq!(Some(Literal::Integer(30)), local.get_slot(0));
+        assert_eq!(Some(Literal::Integer(1)), global.get("a"));
+        assert_eq!(Some("b"), local.slot_name(1));
+
+        let inner = Environment::new_enclosed(local);
+        assert_eq!(
+            Some(Literal::Integer(20)),
+            inner.ancestor(1).and_then(|scope| scope.get_slot(1))
+        );
+        assert!(inner.ancestor(3).is_none());
+        assert_eq!(
+            vec![
+                ("a".to_string(), Literal::Integer(30)),
+                ("b".to_string(), Literal::Integer(20))
+            ],
+            inner.ancestor(1).unwrap().values()
+        );
+    }
+}
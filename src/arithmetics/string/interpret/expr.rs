@@ -1,4 +1,10 @@
-use super::{literal::Literal, token::Token};
+use std::rc::Rc;
+
+use super::{
+    literal::Literal,
+    stmt::FunctionDecl,
+    token::{Span, Token},
+};
 
 #[derive(Debug, Clone)]
 pub enum Expr {
@@ -7,14 +13,70 @@ pub enum Expr {
         operator: Token,
         right: Box<Expr>,
     },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+    },
     Grouping {
         expr: Box<Expr>,
+        /// 包括括号在内的区间
+        span: Span,
     },
     Unary {
         operator: Token,
         right: Box<Expr>,
     },
-    Literal(Literal),
+    Literal {
+        value: Literal,
+        span: Span,
+    },
+    Variable {
+        name: Token,
+    },
+    Assign {
+        name: Token,
+        value: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        /// 右括号，用于定位调用
+        paren: Token,
+        arguments: Vec<Expr>,
+    },
+    /// 匿名函数 `fn (params) { body }`
+    Function(Rc<FunctionDecl>),
+    /// 插值字符串 `"a ${b}"`
+    Interpolation {
+        parts: Vec<InterpolationPart>,
+        span: Span,
+    },
+    /// `[a, b]`，每次求值创建新的列表
+    List {
+        elements: Vec<Expr>,
+        /// 包括方括号在内的区间
+        span: Span,
+    },
+    /// `{key: value}`，每次求值创建新的映射
+    Map {
+        entries: Vec<(Expr, Expr)>,
+        /// 包括花括号在内的区间
+        span: Span,
+    },
+    /// `object[index]`
+    Index {
+        object: Box<Expr>,
+        /// 右方括号，用于定位下标
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    /// `object[index] = value`
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
 }
 
 impl Expr {
@@ -25,26 +87,108 @@ impl Expr {
                 operator,
                 right,
             } => visitor.visit_binary(left, operator, right),
-            Expr::Grouping { expr } => visitor.visit_grouping(expr),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => visitor.visit_logical(left, operator, right),
+            Expr::Grouping { expr, .. } => visitor.visit_grouping(expr),
             Expr::Unary { operator, right } => visitor.visit_unary(operator, right),
-            Expr::Literal(literal) => visitor.visit_literal(literal),
+            Expr::Literal { value, .. } => visitor.visit_literal(value),
+            Expr::Variable { name } => visitor.visit_variable(name),
+            Expr::Assign { name, value } => visitor.visit_assign(name, value),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => visitor.visit_call(callee, paren, arguments),
+            Expr::Function(declaration) => visitor.visit_function(declaration),
+            Expr::Interpolation { parts, .. } => visitor.visit_interpolation(parts),
+            Expr::List { elements, .. } => visitor.visit_list(elements),
+            Expr::Map { entries, .. } => visitor.visit_map(entries),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => visitor.visit_index(object, bracket, index),
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => visitor.visit_set_index(object, bracket, index, value),
+        }
+    }
+
+    /// 表达式在源码中覆盖的区间
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                left.span().merge(right.span())
+            }
+            Expr::Unary { operator, right } => operator.span.merge(right.span()),
+            Expr::Grouping { span, .. }
+            | Expr::Literal { span, .. }
+            | Expr::Interpolation { span, .. }
+            | Expr::List { span, .. }
+            | Expr::Map { span, .. } => *span,
+            Expr::Variable { name } => name.span,
+            Expr::Assign { name, value } => name.span.merge(value.span()),
+            Expr::Call { callee, paren, .. } => callee.span().merge(paren.span),
+            Expr::Function(declaration) => declaration.span,
+            Expr::Index {
+                object, bracket, ..
+            } => object.span().merge(bracket.span),
+            Expr::SetIndex { object, value, .. } => object.span().merge(value.span()),
         }
     }
 }
 
+/// 插值字符串的片段
+///
+/// `"${"a"}"` 与 `"a"` 求值相同，但只有区分两者才能写回原来的源码。
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    /// 已处理转义的文本
+    Text(String),
+    /// `${...}` 中的表达式
+    Expr(Expr),
+}
+
 pub trait ExprVisitor {
     type ReturnType;
 
-    fn visit_binary(
-        &self,
-        left: &Box<Expr>,
-        operator: &Token,
-        right: &Box<Expr>,
-    ) -> Self::ReturnType;
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType;
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType;
 
-    fn visit_grouping(&self, expr: &Box<Expr>) -> Self::ReturnType;
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType;
 
-    fn visit_unary(&self, operator: &Token, right: &Box<Expr>) -> Self::ReturnType;
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType;
 
     fn visit_literal(&self, literal: &Literal) -> Self::ReturnType;
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType;
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType;
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::ReturnType;
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType;
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType;
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType;
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType;
+
+    fn visit_index(&self, object: &Expr, bracket: &Token, index: &Expr) -> Self::ReturnType;
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType;
 }
@@ -0,0 +1,538 @@
+use std::rc::Rc;
+
+use super::expr::{Expr, ExprVisitor, InterpolationPart};
+use super::interpreter;
+use super::literal::{Literal, NumericMode};
+use super::stmt::{FunctionDecl, Stmt, StmtVisitor};
+use super::token::{Span, Token, TokenType};
+
+/// 常量折叠与代数化简，返回新的语法树
+///
+/// 只做不改变运行结果的变换：运算出错（如除以 0、溢出）的常量子树保持原样，
+/// 留到运行时以相同的位置报告；恒等式只在操作数类型确定时才化简。
+#[derive(Debug, Default)]
+pub struct ConstantFolder {
+    mode: NumericMode,
+}
+
+impl ConstantFolder {
+    pub fn new() -> Self {
+        ConstantFolder::default()
+    }
+
+    /// 与执行时的 `NumericMode` 保持一致，否则折叠结果可能与运行结果不同
+    pub fn with_mode(mut self, mode: NumericMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn fold(&self, expr: &Expr) -> Expr {
+        // `ExprVisitor` 不提供字面量、插值字符串与集合的区间，在这里补上
+        match (expr, expr.accept(self)) {
+            (
+                Expr::Literal { span, .. } | Expr::Interpolation { span, .. },
+                Expr::Literal { value, .. },
+            ) => constant(value, *span),
+            (Expr::Interpolation { span, .. }, Expr::Interpolation { parts, .. }) => {
+                Expr::Interpolation { parts, span: *span }
+            }
+            (Expr::List { span, .. }, Expr::List { elements, .. }) => Expr::List {
+                elements,
+                span: *span,
+            },
+            (Expr::Map { span, .. }, Expr::Map { entries, .. }) => Expr::Map {
+                entries,
+                span: *span,
+            },
+            (_, folded) => folded,
+        }
+    }
+
+    pub fn fold_program(&self, statements: &[Stmt]) -> Vec<Stmt> {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect()
+    }
+
+    /// 保留外层括号的区间，用于调用、下标和 `print` 等以表达式区间报告错误的位置
+    fn fold_at(&self, expr: &Expr) -> Expr {
+        let folded = self.fold(expr);
+        match expr {
+            Expr::Grouping { span, .. } if folded.span() != *span => Expr::Grouping {
+                expr: Box::new(folded),
+                span: *span,
+            },
+            _ => folded,
+        }
+    }
+
+    fn fold_function(&self, declaration: &FunctionDecl) -> Rc<FunctionDecl> {
+        Rc::new(FunctionDecl {
+            name: declaration.name.clone(),
+            params: declaration.params.clone(),
+            body: self.fold_program(&declaration.body),
+            span: declaration.span,
+        })
+    }
+
+    /// `x * 1`、`1 * x` 在 `x` 一定是数字时化简为 `x`（布尔值与 `1` 相乘是运行时错误）；
+    /// `x + 0`、`0 + x` 还要求 `x` 不是浮点数，因为 `-0.0 + 0` 的结果为 `0.0`
+    fn simplify(&self, left: &Expr, operator: &Token, right: &Expr) -> Option<Expr> {
+        let (operand, constant) = match (literal(left), literal(right)) {
+            (_, Some(constant)) => (left, constant),
+            (Some(constant), _) => (right, constant),
+            _ => return None,
+        };
+        let identity = match operator.token_type {
+            TokenType::Star => {
+                is_integer(constant, 1) && matches!(kind(operand), Kind::Exact | Kind::Number)
+            }
+            TokenType::Plus => is_integer(constant, 0) && kind(operand) == Kind::Exact,
+            _ => false,
+        };
+        identity.then(|| operand.clone())
+    }
+}
+
+/// 表达式求值成功时结果的类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// 整数、大整数或有理数
+    Exact,
+    /// 数字，可能是浮点数
+    Number,
+    Bool,
+    Unknown,
+}
+
+fn kind(expr: &Expr) -> Kind {
+    match expr {
+        Expr::Literal { value, .. } => match value {
+            Literal::Integer(_) | Literal::BigInt(_) | Literal::Rational(_) => Kind::Exact,
+            Literal::Float(_) => Kind::Number,
+            Literal::Bool(_) => Kind::Bool,
+            _ => Kind::Unknown,
+        },
+        Expr::Grouping { expr, .. } => kind(expr),
+        Expr::Unary { operator, right } => match operator.token_type {
+            TokenType::Bang => Kind::Bool,
+            _ if kind(right) == Kind::Exact => Kind::Exact,
+            _ => Kind::Number,
+        },
+        Expr::Logical { .. } => Kind::Bool,
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => match operator.token_type {
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash
+                if kind(left) == Kind::Exact && kind(right) == Kind::Exact =>
+            {
+                Kind::Exact
+            }
+            // 字符串只支持 `+`，其余算术运算成功时结果一定是数字
+            TokenType::Minus | TokenType::Star | TokenType::Slash => Kind::Number,
+            TokenType::Plus => Kind::Unknown,
+            _ => Kind::Bool,
+        },
+        _ => Kind::Unknown,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<&Literal> {
+    match expr {
+        Expr::Literal { value, .. } => Some(value),
+        _ => None,
+    }
+}
+
+fn is_integer(value: &Literal, expected: i64) -> bool {
+    matches!(value, Literal::Integer(value) if *value == expected)
+}
+
+fn constant(value: Literal, span: Span) -> Expr {
+    Expr::Literal { value, span }
+}
+
+impl ExprVisitor for ConstantFolder {
+    type ReturnType = Expr;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let left = self.fold(left);
+        let right = self.fold(right);
+        // 两侧都是常量时，出错的运算原样保留到运行时报告
+        let folded = match (literal(&left), literal(&right)) {
+            (Some(l), Some(r)) => {
+                interpreter::binary(operator.token_type, l.clone(), r.clone(), self.mode)
+                    .ok()
+                    .map(|value| constant(value, left.span().merge(right.span())))
+            }
+            _ => self.simplify(&left, operator, &right),
+        };
+        if let Some(folded) = folded {
+            return folded;
+        }
+        Expr::Binary {
+            left: Box::new(left),
+            operator: operator.clone(),
+            right: Box::new(right),
+        }
+    }
+
+    /// 左侧为常量时可以确定是否短路；不短路且右侧为布尔值时结果就是右侧
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let left = self.fold(left);
+        let right = self.fold(right);
+        let span = left.span().merge(right.span());
+        if let Some(value) = literal(&left) {
+            let truthy = value.is_truthy();
+            match operator.token_type {
+                TokenType::Or if truthy => return constant(Literal::Bool(true), span),
+                TokenType::And if !truthy => return constant(Literal::Bool(false), span),
+                _ => {}
+            }
+            if let Some(value) = literal(&right) {
+                return constant(Literal::Bool(value.is_truthy()), span);
+            }
+            if kind(&right) == Kind::Bool {
+                return right;
+            }
+        }
+        Expr::Logical {
+            left: Box::new(left),
+            operator: operator.clone(),
+            right: Box::new(right),
+        }
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        self.fold(expr)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let right = self.fold(right);
+        let span = operator.span.merge(right.span());
+        match (operator.token_type, &right) {
+            (TokenType::Bang, Expr::Literal { value, .. }) => {
+                return constant(!value.clone(), span);
+            }
+            (TokenType::Minus, Expr::Literal { value, .. }) => {
+                if let Ok(value) = value.clone().neg_with(self.mode) {
+                    return constant(value, span);
+                }
+            }
+            // 布尔值的 `!!b` 就是 `b`
+            (
+                TokenType::Bang,
+                Expr::Unary {
+                    operator: inner,
+                    right: operand,
+                },
+            ) if inner.token_type == TokenType::Bang && kind(operand) == Kind::Bool => {
+                return (**operand).clone();
+            }
+            _ => {}
+        }
+        Expr::Unary {
+            operator: operator.clone(),
+            right: Box::new(right),
+        }
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        constant(literal.clone(), Span::default())
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        Expr::Variable { name: name.clone() }
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        Expr::Assign {
+            name: name.clone(),
+            value: Box::new(self.fold(value)),
+        }
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        Expr::Call {
+            callee: Box::new(self.fold_at(callee)),
+            paren: paren.clone(),
+            arguments: arguments
+                .iter()
+                .map(|argument| self.fold(argument))
+                .collect(),
+        }
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        Expr::Function(self.fold_function(declaration))
+    }
+
+    /// 所有片段都是常量时拼接为字符串
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        let mut folded: Vec<InterpolationPart> = Vec::new();
+        for part in parts {
+            // 常量表达式转为文本，相邻的文本合并
+            let part = match part {
+                InterpolationPart::Text(text) => InterpolationPart::Text(text.clone()),
+                InterpolationPart::Expr(expr) => match self.fold(expr) {
+                    Expr::Literal { value, .. } => InterpolationPart::Text(value.to_string()),
+                    expr => InterpolationPart::Expr(expr),
+                },
+            };
+            match (folded.last_mut(), part) {
+                (Some(InterpolationPart::Text(text)), InterpolationPart::Text(value)) => {
+                    text.push_str(&value)
+                }
+                (_, part) => folded.push(part),
+            }
+        }
+        match folded.as_slice() {
+            [] => constant(Literal::String(String::new()), Span::default()),
+            [InterpolationPart::Text(text)] => {
+                constant(Literal::String(text.clone()), Span::default())
+            }
+            _ => Expr::Interpolation {
+                parts: folded,
+                span: Span::default(),
+            },
+        }
+    }
+
+    /// 集合是可变的，每次求值都要创建新的对象，不能折叠为常量
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        Expr::List {
+            elements: elements.iter().map(|element| self.fold(element)).collect(),
+            span: Span::default(),
+        }
+    }
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        Expr::Map {
+            entries: entries
+                .iter()
+                .map(|(key, value)| (self.fold(key), self.fold(value)))
+                .collect(),
+            span: Span::default(),
+        }
+    }
+
+    fn visit_index(&self, object: &Expr, bracket: &Token, index: &Expr) -> Self::ReturnType {
+        Expr::Index {
+            object: Box::new(self.fold_at(object)),
+            bracket: bracket.clone(),
+            index: Box::new(self.fold(index)),
+        }
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        Expr::SetIndex {
+            object: Box::new(self.fold_at(object)),
+            bracket: bracket.clone(),
+            index: Box::new(self.fold(index)),
+            value: Box::new(self.fold(value)),
+        }
+    }
+}
+
+impl StmtVisitor for ConstantFolder {
+    type ReturnType = Stmt;
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        Stmt::Expression(self.fold(expr))
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        Stmt::Print(self.fold_at(expr))
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        Stmt::Let {
+            name: name.clone(),
+            initializer: initializer.map(|initializer| self.fold(initializer)),
+        }
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        Stmt::Block(self.fold_program(statements))
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        Stmt::If {
+            condition: self.fold(condition),
+            then_branch: Box::new(then_branch.accept(self)),
+            else_branch: else_branch.map(|else_branch| Box::new(else_branch.accept(self))),
+        }
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        Stmt::While {
+            condition: self.fold(condition),
+            body: Box::new(body.accept(self)),
+        }
+    }
+
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        Stmt::For {
+            initializer: initializer.map(|initializer| Box::new(initializer.accept(self))),
+            condition: condition.map(|condition| self.fold(condition)),
+            increment: increment.map(|increment| self.fold(increment)),
+            body: Box::new(body.accept(self)),
+        }
+    }
+
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        Stmt::ForIn {
+            name: name.clone(),
+            iterable: self.fold_at(iterable),
+            body: Box::new(body.accept(self)),
+        }
+    }
+
+    fn visit_break(&self, keyword: &Token) -> Self::ReturnType {
+        Stmt::Break(keyword.clone())
+    }
+
+    fn visit_continue(&self, keyword: &Token) -> Self::ReturnType {
+        Stmt::Continue(keyword.clone())
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        Stmt::Function(self.fold_function(declaration))
+    }
+
+    fn visit_return(&self, keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        Stmt::Return {
+            keyword: keyword.clone(),
+            value: value.map(|value| self.fold(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::printer::AstPrinter;
+    use super::super::testing::{parse_expr, parse_program, tree_walk};
+    use super::*;
+
+    fn fold(source: &str) -> String {
+        fold_with(source, NumericMode::default())
+    }
+
+    fn fold_with(source: &str, mode: NumericMode) -> String {
+        let expr = parse_expr(source);
+        AstPrinter::new().print(&ConstantFolder::new().with_mode(mode).fold(&expr))
+    }
+
+    /// 折叠前后的输出与错误（包括出错位置）完全一致
+    fn assert_same(source: &str) {
+        let statements = parse_program(source);
+        for mode in [NumericMode::Checked, NumericMode::Exact] {
+            let folded = ConstantFolder::new()
+                .with_mode(mode)
+                .fold_program(&statements);
+            assert_eq!(
+                tree_walk(&statements, mode),
+                tree_walk(&folded, mode),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn fold_constants() {
+        assert_eq!("6", fold("(1 + 1) * 3"));
+        assert_eq!("(+ x 2.5)", fold("x + (0.5 + 2)"));
+        assert_eq!("\"ab\"", fold("\"a\" + \"b\""));
+        assert_eq!("true", fold("!(1 > 2)"));
+        assert_eq!("-3", fold("-(1 + 2)"));
+        assert_eq!("(interpolate \"n=\" n \" 3\")", fold("\"n=${n} ${1 + 2}\""));
+        assert_eq!("\"1 + 2 = 3\"", fold("\"1 + 2 = ${1 + 2}\""));
+        assert_eq!("(call f 2)", fold("(f)(1 + 1)"));
+        assert_eq!("(fn (x) (return 4))", fold("fn (x) { return 2 * 2; }"));
+    }
+
+    #[test]
+    fn fold_keeps_runtime_errors() {
+        assert_eq!("(/ 2 0)", fold("(1 + 1) / 0"));
+        assert_eq!("(+ 9223372036854775807 1)", fold("9223372036854775807 + 1"));
+        assert_eq!(
+            "9223372036854775808",
+            fold_with("9223372036854775807 + 1", NumericMode::Exact)
+        );
+        assert_eq!("(- \"a\")", fold("-\"a\""));
+        assert_eq!("(+ 1 \"a\")", fold("1 + \"a\""));
+    }
+
+    #[test]
+    fn fold_logical() {
+        assert_eq!("true", fold("1 || x"));
+        assert_eq!("false", fold("nil && x"));
+        assert_eq!("true", fold("true && \"s\""));
+        assert_eq!("(< x 1)", fold("false || x < 1"));
+        assert_eq!("(|| false x)", fold("false || x"));
+        assert_eq!("(&& x true)", fold("x && true"));
+    }
+
+    #[test]
+    fn simplify_identities() {
+        assert_eq!("(- a b)", fold("(a - b) * 1"));
+        assert_eq!("(- a)", fold("1 * -a"));
+        assert_eq!("(* x 1)", fold("x * 1"));
+        assert_eq!("(* (+ a b) 1)", fold("(a + b) * 1"));
+        assert_eq!("(/ 1 0)", fold("(1 / 0) + 0"));
+        assert_eq!("(+ (- 2 a) 0)", fold("(2 - a) + 0"));
+        // 浮点数的 `-0.0 + 0` 为 `0.0`，不能化简
+        assert_eq!("(+ (- a b) 0)", fold("(a - b) + 0"));
+        assert_eq!("(+ x 0)", fold("x + 0"));
+        assert_eq!("(< a b)", fold("!!(a < b)"));
+        assert_eq!("(! (! a))", fold("!!a"));
+    }
+
+    #[test]
+    fn fold_preserves_behavior() {
+        let programs = [
+            "print (1 + 1) * 3 / 2; print 1 / 3 + 1 / 6; print 0.1 + 0.2;",
+            "let a = 2; print a * (3 - 2); print (a - a) * 1; print !!(a > 1) && 1 || 0;",
+            "let f = -0.0; print f * 1; print (f - 0) + 0; print \"${f} ${1 + 1}\";",
+            "print (1 + 1) / 0;",
+            "print 9223372036854775807 + 1;",
+            "print 1 * \"a\";",
+            "let s = \"a\"; print (s - 1) * 1;",
+            "print (undefined);",
+            "(nil)(1 + 2);",
+            "fn f(n) { if (n < 2 * 1) return n; return f(n - 1) + f(n - (1 + 1)); } print f(10);",
+            "for (let i = 0; i < 2 + 1; i = i + 1) { while (false || i > 5) {} print i * 1; }",
+            "let xs = [1 + 1, {2 * 1: 3}]; xs[0 * 1] = xs[1][2] - 0; for (x in xs) print x;",
+            "print [1][1 - 0];",
+            "for (x in 1 + 1) {}",
+            "print (1 < 2) * 1;",
+            "let a = 1; print (a < 2) * 1;",
+            "let a = 1; print !a * 1;",
+            "print 1 * true;",
+        ];
+        for program in programs {
+            assert_same(program);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::ptr;
+use std::rc::Rc;
+
+use super::environment::Environment;
+use super::interpreter::{RuntimeError, RuntimeErrorKind};
+use super::literal::Literal;
+use super::stmt::FunctionDecl;
+
+/// 运行时的函数值，`closure` 为函数定义处的作用域
+pub struct Function {
+    pub declaration: Rc<FunctionDecl>,
+    pub closure: Rc<Environment>,
+}
+
+impl Function {
+    pub fn new(declaration: Rc<FunctionDecl>, closure: Rc<Environment>) -> Self {
+        Function {
+            declaration,
+            closure,
+        }
+    }
+
+    /// 匿名函数返回 `None`
+    pub fn name(&self) -> Option<&str> {
+        self.declaration
+            .name
+            .as_ref()
+            .and_then(|name| name.lexeme.as_deref())
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+/// 函数值只与自身相等
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+// closure 可能反过来引用函数自身，不能派生 Debug
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name())
+            .field("arity", &self.arity())
+            .finish()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => f.write_str("<fn>"),
+        }
+    }
+}
+
+type NativeFn = dyn Fn(&[Literal]) -> Result<Literal, RuntimeError>;
+
+/// 由 Rust 实现的函数，如内置的 `len`
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Literal]) -> Result<Literal, RuntimeError> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// 实参个数不一致时返回 `Arity` 错误，不调用函数
+    pub fn call(&self, arguments: &[Literal]) -> Result<Literal, RuntimeError> {
+        if arguments.len() != self.arity {
+            return Err(RuntimeError::new(RuntimeErrorKind::Arity {
+                expected: self.arity,
+                found: arguments.len(),
+            }));
+        }
+        (self.function)(arguments)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish()
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
@@ -0,0 +1,1206 @@
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use super::builtins;
+use super::collection::{List, Map};
+use super::environment::Environment;
+use super::expr::{Expr, ExprVisitor, InterpolationPart};
+use super::function::Function;
+use super::literal::{Literal, NumericMode};
+use super::stmt::{FunctionDecl, Stmt, StmtVisitor};
+use super::token::{Span, Token, TokenType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+    /// 二元运算符不支持该组操作数类型
+    BinaryOperands {
+        operator: &'static str,
+        left: &'static str,
+        right: &'static str,
+    },
+    /// 一元运算符不支持该操作数类型
+    UnaryOperand {
+        operator: &'static str,
+        operand: &'static str,
+    },
+    /// `NumericMode::Checked` 下的整数溢出
+    Overflow {
+        operator: &'static str,
+    },
+    /// 整数除以 0
+    DivisionByZero,
+    /// 读取或赋值未定义的变量
+    UndefinedVariable(String),
+    /// `print` 写入输出失败
+    Io(String),
+    /// 调用的值不是函数
+    NotCallable(&'static str),
+    /// 实参个数与形参个数不一致
+    Arity {
+        expected: usize,
+        found: usize,
+    },
+    /// 调用嵌套层数超过 `Interpreter` 的上限
+    StackOverflow {
+        depth: usize,
+    },
+    /// 调用或表达式嵌套过深，Rust 栈的用量超过 `budget` 字节
+    StackExhausted {
+        budget: usize,
+    },
+    /// 下标的对象不是列表或映射
+    NotIndexable(&'static str),
+    /// 列表的下标不是 `Integer`
+    InvalidIndex(&'static str),
+    IndexOutOfRange {
+        index: i64,
+        len: usize,
+    },
+    /// 映射中没有该键，键按集合中的显示形式保存
+    MissingKey(String),
+    /// `for (x in value)` 的值不是列表或映射
+    NotIterable(&'static str),
+    /// 内置函数的第 `position` 个实参（从 1 开始）类型不对
+    ArgumentType {
+        position: usize,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// 宿主程序注册的函数报告的错误
+    Host(String),
+    /// 虚拟机遇到不合法的字节码，如未知的操作码或越界的操作数
+    InvalidBytecode(String),
+    /// 手工构造的语法树不合法，如运算符与表达式的种类不符
+    InvalidSyntaxTree(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    /// 出错运算符在源码中的区间，由解释器补充
+    pub span: Option<Span>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind) -> Self {
+        RuntimeError { kind, span: None }
+    }
+
+    pub fn binary_operands(operator: &'static str, left: &Literal, right: &Literal) -> Self {
+        RuntimeError::new(RuntimeErrorKind::BinaryOperands {
+            operator,
+            left: left.type_name(),
+            right: right.type_name(),
+        })
+    }
+
+    pub fn unary_operand(operator: &'static str, operand: &Literal) -> Self {
+        RuntimeError::new(RuntimeErrorKind::UnaryOperand {
+            operator,
+            operand: operand.type_name(),
+        })
+    }
+
+    pub fn host(message: &str) -> Self {
+        RuntimeError::new(RuntimeErrorKind::Host(message.to_string()))
+    }
+
+    /// 补充出错位置，已有位置时保持不变
+    pub fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RuntimeErrorKind::BinaryOperands {
+                operator,
+                left,
+                right,
+            } => write!(f, "cannot apply `{}` to {} and {}", operator, left, right),
+            RuntimeErrorKind::UnaryOperand { operator, operand } => {
+                write!(f, "cannot apply unary `{}` to {}", operator, operand)
+            }
+            RuntimeErrorKind::Overflow { operator } => {
+                write!(f, "integer overflow in `{}`", operator)
+            }
+            RuntimeErrorKind::DivisionByZero => write!(f, "integer division by zero"),
+            RuntimeErrorKind::UndefinedVariable(name) => {
+                write!(f, "undefined variable `{}`", name)
+            }
+            RuntimeErrorKind::Io(message) => write!(f, "failed to write output: {}", message),
+            RuntimeErrorKind::NotCallable(type_name) => {
+                write!(f, "cannot call a value of type {}", type_name)
+            }
+            RuntimeErrorKind::Arity { expected, found } => {
+                write!(f, "expected {} arguments but got {}", expected, found)
+            }
+            RuntimeErrorKind::StackOverflow { depth } => {
+                write!(f, "stack overflow: more than {} nested calls", depth)
+            }
+            RuntimeErrorKind::StackExhausted { budget } => write!(
+                f,
+                "stack overflow: evaluation used more than {} KiB of native stack",
+                budget / 1024
+            ),
+            RuntimeErrorKind::NotIndexable(type_name) => {
+                write!(f, "cannot index into a value of type {}", type_name)
+            }
+            RuntimeErrorKind::InvalidIndex(type_name) => {
+                write!(f, "list index must be an Integer, found {}", type_name)
+            }
+            RuntimeErrorKind::IndexOutOfRange { index, len } => write!(
+                f,
+                "index {} is out of range for a list of length {}",
+                index, len
+            ),
+            RuntimeErrorKind::MissingKey(key) => write!(f, "key {} not found in map", key),
+            RuntimeErrorKind::NotIterable(type_name) => {
+                write!(f, "cannot iterate over a value of type {}", type_name)
+            }
+            RuntimeErrorKind::ArgumentType {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} must be {}, found {}",
+                position, expected, found
+            ),
+            RuntimeErrorKind::Host(message) => write!(f, "{}", message),
+            RuntimeErrorKind::InvalidBytecode(message) => {
+                write!(f, "invalid bytecode: {}", message)
+            }
+            RuntimeErrorKind::InvalidSyntaxTree(message) => {
+                write!(f, "invalid syntax tree: {}", message)
+            }
+        }
+    }
+}
+
+/// 语句执行被中断的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unwind {
+    /// 跳出最内层循环
+    Break,
+    /// 进入最内层循环的下一次迭代
+    Continue,
+    /// 从当前函数返回
+    Return(Literal),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// 树遍历解释器，执行 `Parser` 生成的语句并对 `Expr` 求值
+pub struct Interpreter {
+    mode: NumericMode,
+    /// 当前作用域
+    environment: RefCell<Rc<Environment>>,
+    /// `print` 的输出，默认为标准输出
+    output: RefCell<Box<dyn Write>>,
+    /// 当前函数调用的嵌套层数
+    depth: Cell<usize>,
+    max_depth: usize,
+    /// 最外层的 `execute`/`evaluate` 开始时的栈地址，不在执行中时为 0
+    stack_base: Cell<usize>,
+    stack_budget: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter {
+            mode: NumericMode::default(),
+            environment: RefCell::new(builtins::globals()),
+            output: RefCell::new(Box::new(io::stdout())),
+            depth: Cell::new(0),
+            max_depth: Interpreter::MAX_DEPTH,
+            stack_base: Cell::new(0),
+            stack_budget: Interpreter::STACK_BUDGET,
+        }
+    }
+}
+
+impl Interpreter {
+    /// 默认的调用嵌套上限，与 `Vm::MAX_DEPTH` 相同
+    ///
+    /// 实测每层调用占用 Rust 栈约 1–3KB（release）或 5–10KB（debug），函数体中的表达式
+    /// 每嵌套一层再加约 0.1KB（release）或 0.7–2KB（debug）。达到上限需要的栈远超
+    /// 线程默认的大小，在 `runner::with_large_stack` 中运行并放宽 `stack_budget`。
+    pub const MAX_DEPTH: usize = 10_000;
+
+    /// 默认的 Rust 栈用量上限（字节），为新线程默认的 2MB 栈留出一半余量；
+    /// 不知道所在线程的栈有多大时，只能按最小的情况估计
+    pub const STACK_BUDGET: usize = 1 << 20;
+
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    /// 指定整数溢出的处理方式，默认为 `NumericMode::Checked`
+    pub fn with_mode(mut self, mode: NumericMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// 将 `print` 的输出写入 `output`
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = RefCell::new(output);
+        self
+    }
+
+    /// 限制函数调用的嵌套层数，超过时报告 `StackOverflow` 而不是耗尽 Rust 的栈
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// 限制执行时 Rust 栈的用量（字节），超过时报告 `StackExhausted`；
+    /// 在栈更大的线程中运行时可以放宽
+    pub fn with_stack_budget(mut self, stack_budget: usize) -> Self {
+        self.stack_budget = stack_budget;
+        self
+    }
+
+    pub fn mode(&self) -> NumericMode {
+        self.mode
+    }
+
+    pub fn environment(&self) -> Rc<Environment> {
+        self.environment.borrow().clone()
+    }
+
+    /// 丢弃所有变量，回到空的全局作用域
+    pub fn reset(&self) {
+        self.environment.replace(builtins::globals());
+        self.depth.set(0);
+        self.stack_base.set(0);
+    }
+
+    /// 按顺序执行语句，变量定义在多次调用之间保留
+    ///
+    /// 循环外的 `break`/`continue` 已被 `Parser` 拒绝，若仍出现则结束执行
+    pub fn execute(&self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        let outermost = self.enter_stack()?;
+        let result = self.execute_statements(statements);
+        if outermost {
+            self.stack_base.set(0);
+        }
+        match result {
+            Err(Unwind::Error(error)) => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// 检查 Rust 栈的用量；最外层的调用记录栈的起点并返回 `true`
+    fn enter_stack(&self) -> Result<bool, RuntimeError> {
+        let marker = 0u8;
+        let address = std::hint::black_box(&marker) as *const u8 as usize;
+        let base = self.stack_base.get();
+        if base == 0 {
+            self.stack_base.set(address);
+            return Ok(true);
+        }
+        if base.abs_diff(address) > self.stack_budget {
+            return Err(RuntimeError::new(RuntimeErrorKind::StackExhausted {
+                budget: self.stack_budget,
+            }));
+        }
+        Ok(false)
+    }
+
+    fn execute_statements(&self, statements: &[Stmt]) -> Result<(), Unwind> {
+        for statement in statements {
+            statement.accept(self)?;
+        }
+        Ok(())
+    }
+
+    /// 在 `environment` 作用域中执行语句，结束后（包括出错和跳出循环时）恢复原作用域
+    pub fn execute_block(
+        &self,
+        statements: &[Stmt],
+        environment: Rc<Environment>,
+    ) -> Result<(), Unwind> {
+        let previous = self.environment.replace(environment);
+        let result = self.execute_statements(statements);
+        self.environment.replace(previous);
+        result
+    }
+
+    /// 在以 `closure` 为外层的新作用域中绑定参数并执行函数体
+    fn call(&self, function: &Function, arguments: Vec<Literal>) -> Result<Literal, RuntimeError> {
+        if arguments.len() != function.arity() {
+            return Err(RuntimeError::new(RuntimeErrorKind::Arity {
+                expected: function.arity(),
+                found: arguments.len(),
+            }));
+        }
+        if self.depth.get() >= self.max_depth {
+            return Err(RuntimeError::new(RuntimeErrorKind::StackOverflow {
+                depth: self.max_depth,
+            }));
+        }
+        let environment = Rc::new(Environment::new_enclosed(function.closure.clone()));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            environment.define(identifier(param), argument);
+        }
+        self.depth.set(self.depth.get() + 1);
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.depth.set(self.depth.get() - 1);
+        match result {
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+            // 函数体外的 `break`/`continue` 已被 `Parser` 拒绝
+            _ => Ok(Literal::None),
+        }
+    }
+
+    /// 执行一次循环体，返回是否继续循环
+    fn execute_body(&self, body: &Stmt) -> Result<bool, Unwind> {
+        match body.accept(self) {
+            Ok(()) | Err(Unwind::Continue) => Ok(true),
+            Err(Unwind::Break) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn evaluate(&self, expr: &Expr) -> Result<Literal, RuntimeError> {
+        let outermost = self.enter_stack()?;
+        let result = expr.accept(self);
+        if outermost {
+            self.stack_base.set(0);
+        }
+        result
+    }
+}
+
+impl StmtVisitor for Interpreter {
+    type ReturnType = Result<(), Unwind>;
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        self.evaluate(expr)?;
+        Ok(())
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        let value = self.evaluate(expr)?;
+        writeln!(self.output.borrow_mut(), "{}", value).map_err(|error| {
+            RuntimeError::new(RuntimeErrorKind::Io(error.to_string())).at(expr.span())
+        })?;
+        Ok(())
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Literal::None,
+        };
+        self.environment().define(identifier(name), value);
+        Ok(())
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        let environment = Rc::new(Environment::new_enclosed(self.environment()));
+        self.execute_block(statements, environment)
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        if self.evaluate(condition)?.is_truthy() {
+            then_branch.accept(self)
+        } else if let Some(else_branch) = else_branch {
+            else_branch.accept(self)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        while self.evaluate(condition)?.is_truthy() {
+            if !self.execute_body(body)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        // 初始化语句声明的变量只在循环内可见
+        let environment = Rc::new(Environment::new_enclosed(self.environment()));
+        let previous = self.environment.replace(environment);
+        let result = (|| {
+            if let Some(initializer) = initializer {
+                initializer.accept(self)?;
+            }
+            loop {
+                if let Some(condition) = condition {
+                    if !self.evaluate(condition)?.is_truthy() {
+                        break;
+                    }
+                }
+                if !self.execute_body(body)? {
+                    break;
+                }
+                if let Some(increment) = increment {
+                    self.evaluate(increment)?;
+                }
+            }
+            Ok(())
+        })();
+        self.environment.replace(previous);
+        result
+    }
+
+    /// 每次迭代在新的作用域中绑定循环变量，闭包捕获的是当次的值
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        let values = self
+            .evaluate(iterable)?
+            .iterate()
+            .map_err(|error| error.at(iterable.span()))?;
+        for value in values {
+            let environment = Rc::new(Environment::new_enclosed(self.environment()));
+            environment.define(identifier(name), value);
+            let previous = self.environment.replace(environment);
+            let result = self.execute_body(body);
+            self.environment.replace(previous);
+            if !result? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_break(&self, _keyword: &Token) -> Self::ReturnType {
+        Err(Unwind::Break)
+    }
+
+    fn visit_continue(&self, _keyword: &Token) -> Self::ReturnType {
+        Err(Unwind::Continue)
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        let function = Function::new(declaration.clone(), self.environment());
+        let name = declaration.name.as_ref().map_or("", identifier);
+        self.environment()
+            .define(name, Literal::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        let value = match value {
+            Some(value) => self.evaluate(value)?,
+            None => Literal::None,
+        };
+        Err(Unwind::Return(value))
+    }
+}
+
+/// 对两个操作数应用二元运算符，`&&` 与 `||` 需要短路求值，不经过这里
+pub fn binary(
+    operator: TokenType,
+    left: Literal,
+    right: Literal,
+    mode: NumericMode,
+) -> Result<Literal, RuntimeError> {
+    match operator {
+        TokenType::Plus => left.add_with(right, mode),
+        TokenType::Minus => left.sub_with(right, mode),
+        TokenType::Star => left.mul_with(right, mode),
+        TokenType::Slash => left.div_with(right, mode),
+
+        TokenType::BangEqual => Ok(Literal::Bool(!left.equals(&right))),
+        TokenType::EqualEqual => Ok(Literal::Bool(left.equals(&right))),
+        TokenType::Greater => left
+            .compare(&right, ">")
+            .map(|ordering| Literal::Bool(ordering == Some(Ordering::Greater))),
+        TokenType::GreaterEqual => left.compare(&right, ">=").map(|ordering| {
+            Literal::Bool(matches!(
+                ordering,
+                Some(Ordering::Greater | Ordering::Equal)
+            ))
+        }),
+        TokenType::Less => left
+            .compare(&right, "<")
+            .map(|ordering| Literal::Bool(ordering == Some(Ordering::Less))),
+        TokenType::LessEqual => left.compare(&right, "<=").map(|ordering| {
+            Literal::Bool(matches!(ordering, Some(Ordering::Less | Ordering::Equal)))
+        }),
+        _ => Err(invalid_operator(operator, "binary")),
+    }
+}
+
+#[cold]
+fn invalid_operator(operator: TokenType, kind: &str) -> RuntimeError {
+    RuntimeError::new(RuntimeErrorKind::InvalidSyntaxTree(format!(
+        "{} is not a {} operator",
+        operator, kind
+    )))
+}
+
+fn identifier(name: &Token) -> &str {
+    name.lexeme.as_deref().unwrap_or_default()
+}
+
+impl ExprVisitor for Interpreter {
+    type ReturnType = Result<Literal, RuntimeError>;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+        let result = binary(operator.token_type, left, right, self.mode);
+        result.map_err(|error| error.at(operator.span))
+    }
+
+    /// `&&` 与 `||` 短路求值，右侧只在必要时计算
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let left = self.evaluate(left)?.is_truthy();
+        match operator.token_type {
+            TokenType::Or if left => Ok(Literal::Bool(true)),
+            TokenType::And if !left => Ok(Literal::Bool(false)),
+            TokenType::Or | TokenType::And => Ok(Literal::Bool(self.evaluate(right)?.is_truthy())),
+            _ => Err(invalid_operator(operator.token_type, "logical").at(operator.span)),
+        }
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        self.evaluate(expr)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        let literal = self.evaluate(right)?;
+        let result = match operator.token_type {
+            TokenType::Bang => Ok(!literal),
+            TokenType::Minus => literal.neg_with(self.mode),
+            _ => Err(invalid_operator(operator.token_type, "unary")),
+        };
+        result.map_err(|error| error.at(operator.span))
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        Ok(literal.clone())
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        self.environment().get(identifier(name)).ok_or_else(|| {
+            RuntimeError::new(RuntimeErrorKind::UndefinedVariable(
+                identifier(name).to_string(),
+            ))
+            .at(name.span)
+        })
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        let value = self.evaluate(value)?;
+        if self.environment().assign(identifier(name), value.clone()) {
+            Ok(value)
+        } else {
+            Err(RuntimeError::new(RuntimeErrorKind::UndefinedVariable(
+                identifier(name).to_string(),
+            ))
+            .at(name.span))
+        }
+    }
+
+    fn visit_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        let span = callee.span().merge(paren.span);
+        let function = self.evaluate(callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+        match function {
+            Literal::Function(function) => self
+                .call(&function, arguments)
+                .map_err(|error| error.at(span)),
+            Literal::Native(function) => function.call(&arguments).map_err(|error| error.at(span)),
+            other => {
+                Err(RuntimeError::new(RuntimeErrorKind::NotCallable(other.type_name())).at(span))
+            }
+        }
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        let function = Function::new(declaration.clone(), self.environment());
+        Ok(Literal::Function(Rc::new(function)))
+    }
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        let mut text = String::new();
+        for part in parts {
+            match part {
+                InterpolationPart::Text(value) => text.push_str(value),
+                InterpolationPart::Expr(expr) => text.push_str(&self.evaluate(expr)?.to_string()),
+            }
+        }
+        Ok(Literal::String(text))
+    }
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        let values = elements
+            .iter()
+            .map(|element| self.evaluate(element))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Literal::List(Rc::new(List::new(values))))
+    }
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        let map = Map::new();
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            map.insert(key, self.evaluate(value)?);
+        }
+        Ok(Literal::Map(Rc::new(map)))
+    }
+
+    fn visit_index(&self, object: &Expr, bracket: &Token, index: &Expr) -> Self::ReturnType {
+        let span = object.span().merge(bracket.span);
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        object.index(&index).map_err(|error| error.at(span))
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        let span = object.span().merge(bracket.span);
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        object
+            .set_index(index, value.clone())
+            .map_err(|error| error.at(span))?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::testing::{parse_expr, parse_program, SharedOutput};
+    use super::*;
+
+    /// 执行程序，返回 `print` 的输出
+    fn run(source: &str) -> Result<String, RuntimeError> {
+        let output = SharedOutput::default();
+        let interpreter = Interpreter::new().with_output(Box::new(output.clone()));
+        let statements = parse_program(source);
+        interpreter.execute(&statements)?;
+        Ok(output.contents())
+    }
+
+    fn try_interpret(source: &str) -> Result<Literal, RuntimeError> {
+        let expr = parse_expr(source);
+        Interpreter::new().evaluate(&expr)
+    }
+
+    fn interpret(source: &str) -> Literal {
+        try_interpret(source).unwrap()
+    }
+
+    #[test]
+    fn interpret_math_expression() {
+        assert_eq!(Literal::Integer(3), interpret("(1 + 1) * 3 / 2"));
+        assert_eq!(Literal::Integer(-7), interpret("1 - 2 * 4"));
+        assert_eq!(Literal::Float(3.5), interpret("1.5 + 2"));
+        assert_eq!(Literal::Integer(2), interpret("--2"));
+        assert_eq!(Literal::Integer(1265), interpret("0xFF + 0b1010 + 1_000"));
+        assert_eq!(Literal::Bool(true), interpret("2.5e3 == 2500"));
+    }
+
+    #[test]
+    fn interpret_string_concat() {
+        assert_eq!(
+            Literal::String("foobar".to_string()),
+            interpret("\"foo\" + \"bar\"")
+        );
+    }
+
+    #[test]
+    fn interpret_comparison() {
+        assert_eq!(Literal::Bool(true), interpret("1 + 1 == 2"));
+        assert_eq!(Literal::Bool(true), interpret("3 >= 2 * 2 != 1 < 2"));
+        assert_eq!(Literal::Bool(false), interpret("\"a\" == \"b\""));
+        assert_eq!(Literal::Bool(true), interpret("!0"));
+    }
+
+    #[test]
+    fn interpret_logical_short_circuit() {
+        assert_eq!(Literal::Bool(true), interpret("1 || -\"a\""));
+        assert_eq!(Literal::Bool(false), interpret("0 && -\"a\""));
+        assert_eq!(Literal::Bool(true), interpret("1 && \"a\""));
+        assert_eq!(Literal::Bool(false), interpret("0 || \"\""));
+    }
+
+    #[test]
+    fn interpret_boolean_literals() {
+        assert_eq!(Literal::Bool(true), interpret("!false && true"));
+        assert_eq!(Literal::Bool(false), interpret("true == false || !true"));
+        assert_eq!(Literal::Bool(true), interpret("!nil"));
+        assert_eq!(Literal::Bool(true), interpret("nil == nil"));
+        assert_eq!(Literal::Bool(false), interpret("nil == false"));
+        assert_eq!(Literal::Bool(true), interpret("false < true"));
+        assert_eq!(Literal::None, interpret("nil"));
+        assert_eq!(
+            "yes\nnil\n",
+            run("let flag = 1 < 2; if (flag == true) print \"yes\"; let empty = nil; print empty;")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn interpret_type_errors_with_span() {
+        let error = try_interpret("1 + (\"a\" - 1)").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::BinaryOperands {
+                operator: "-",
+                left: "String",
+                right: "Integer"
+            },
+            error.kind
+        );
+        assert_eq!(Some(Span::new(9, 10)), error.span);
+        assert_eq!("cannot apply `-` to String and Integer", error.to_string());
+
+        let error = try_interpret("2 * -\"b\"").unwrap_err();
+        assert_eq!(Some(Span::new(4, 5)), error.span);
+    }
+
+    #[test]
+    fn interpret_overflow_modes() {
+        let expr = parse_expr("9223372036854775807 + 1");
+        let error = Interpreter::new()
+            .with_mode(NumericMode::Checked)
+            .evaluate(&expr)
+            .unwrap_err();
+        assert_eq!(RuntimeErrorKind::Overflow { operator: "+" }, error.kind);
+        assert_eq!(Some(Span::new(20, 21)), error.span);
+        assert_eq!(
+            Ok(Literal::Integer(i64::MIN)),
+            Interpreter::new()
+                .with_mode(NumericMode::Wrapping)
+                .evaluate(&expr)
+        );
+
+        let error = try_interpret("1 / (2 - 2)").unwrap_err();
+        assert_eq!(RuntimeErrorKind::DivisionByZero, error.kind);
+        assert_eq!(Some(Span::new(2, 3)), error.span);
+    }
+
+    #[test]
+    fn interpret_exact_numbers() {
+        let interpret_exact = |source: &str| {
+            let expr = parse_expr(source);
+            Interpreter::new()
+                .with_mode(NumericMode::Exact)
+                .evaluate(&expr)
+                .unwrap()
+        };
+        assert_eq!("Rational", interpret_exact("1 / 3").type_name());
+        assert_eq!(Literal::Integer(1), interpret_exact("1 / 3 + 2 / 3"));
+        assert_eq!(
+            Literal::Bool(true),
+            interpret_exact("9223372036854775807 + 1 > 9223372036854775807")
+        );
+        assert_eq!(
+            Literal::Integer(1),
+            interpret("100000000000000000000 - 99999999999999999999")
+        );
+        assert_eq!(Literal::Bool(true), interpret("1 == 1.0"));
+        assert_eq!(Literal::Bool(true), interpret("2 > 1.5"));
+
+        let error = try_interpret("\"a\" < 1").unwrap_err();
+        assert_eq!(Some(Span::new(4, 5)), error.span);
+    }
+
+    #[test]
+    fn execute_variables() {
+        let interpreter = Interpreter::new();
+        let run = |source: &str| {
+            let statements = parse_program(source);
+            interpreter.execute(&statements)
+        };
+        run("let a = 1; let b = a + 1; let c; a = b = b * 10;").unwrap();
+        run("c = a + b;").unwrap();
+        let environment = interpreter.environment();
+        assert_eq!(Some(Literal::Integer(20)), environment.get("a"));
+        assert_eq!(Some(Literal::Integer(20)), environment.get("b"));
+        assert_eq!(Some(Literal::Integer(40)), environment.get("c"));
+
+        let error = run("let d = a + missing;").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::UndefinedVariable("missing".to_string()),
+            error.kind
+        );
+        assert_eq!(Some(Span::new(12, 19)), error.span);
+        assert_eq!(None, environment.get("d"));
+
+        let error = run("undefined = 1;").unwrap_err();
+        assert_eq!(Some(Span::new(0, 9)), error.span);
+    }
+
+    #[test]
+    fn execute_print_and_blocks() {
+        let output = run("let a = \"global\"; let b = 1;
+            {
+                let a = \"outer\";
+                {
+                    let a = \"inner\";
+                    print a;
+                    b = b + 1;
+                }
+                print a;
+            }
+            print a;
+            print b;
+            print 1.0 / 4;")
+        .unwrap();
+        assert_eq!("inner\nouter\nglobal\n2\n0.25\n", output);
+    }
+
+    #[test]
+    fn execute_block_restores_scope_on_error() {
+        let interpreter = Interpreter::new().with_output(Box::new(SharedOutput::default()));
+        let statements = parse_program("let a = 1; { let a = 2; -\"x\"; }");
+        assert!(interpreter.execute(&statements).is_err());
+        assert_eq!(
+            Some(Literal::Integer(1)),
+            interpreter.environment().get("a")
+        );
+    }
+
+    #[test]
+    fn execute_control_flow() {
+        let output = run("let n = 0;
+            while (n < 3) n = n + 1;
+            if (n == 3) print \"three\"; else print \"other\";
+            if (\"\") print \"truthy\"; else if (0.0) print \"float\";
+            for (let i = 0; i < 10; i = i + 1) {
+                if (i == 1) continue;
+                if (i > 3) break;
+                print i;
+            }
+            let i = 0;
+            let total = 0;
+            while (1) {
+                i = i + 1;
+                if (i > 5) break;
+                if (i == 2) continue;
+                total = total + i;
+            }
+            print total;")
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!("three\nfloat\n0\n2\n3\n13\n", output);
+    }
+
+    #[test]
+    fn execute_for_scope() {
+        let interpreter = Interpreter::new().with_output(Box::new(SharedOutput::default()));
+        let statements = parse_program("for (let i = 0; i < 3; i = i + 1) {}");
+        interpreter.execute(&statements).unwrap();
+        assert_eq!(None, interpreter.environment().get("i"));
+    }
+
+    #[test]
+    fn execute_functions_and_closures() {
+        let output = run("fn fib(n) {
+                if (n < 2) return n;
+                return fib(n - 1) + fib(n - 2);
+            }
+            print fib(15);
+            fn counter() {
+                let count = 0;
+                return fn () { count = count + 1; return count; };
+            }
+            let a = counter();
+            let b = counter();
+            a(); a();
+            print a();
+            print b();
+            let add = fn (x, y) { return x + y; };
+            print add(1, 2);
+            fn nothing() { return; }
+            print nothing();
+            print fib;
+            print fib == fib;
+            print a == b;
+            for (let i = 0; i < 3; i = i + 1) {
+                fn early() { while (1) { return i; } }
+                print early();
+            }")
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            "610\n3\n1\n3\nnil\n<fn fib>\ntrue\nfalse\n0\n1\n2\n",
+            output
+        );
+    }
+
+    #[test]
+    fn execute_interpolation() {
+        let output = run("let name = \"world\";
+            let n = 3;
+            print \"Hello ${name}!\";
+            print \"${n} * 2 = ${n * 2}, ${n / 2.0} ${n > 2} ${nil} ${\"in${n}\"}\";
+            fn greet(who) { return \"hi ${who}\"; }
+            print \"${greet(\"you\")} \\${name}\";")
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            "Hello world!\n3 * 2 = 6, 1.5 true nil in3\nhi you ${name}\n",
+            output
+        );
+
+        let error = run("print \"a ${-\"b\"}\";").unwrap_err();
+        assert_eq!(Some(Span::new(11, 12)), error.span);
+    }
+
+    #[test]
+    fn execute_collections() {
+        let output = run("let xs = [1, 2, [3]];
+            let ys = xs;
+            ys[0] = \"one\";
+            xs[2][0] = xs[1] * 10;
+            print xs;
+            print len(xs) + len(\"héllo\");
+            let m = {\"a\": 1, 2: [], \"a\": 3};
+            m[\"b\"] = m[\"a\"] + 1;
+            m[2.0] = true;
+            print m;
+            print len(m);
+            print [] || {} || nil;
+            print [1] == [1];
+            print xs == ys;
+            print \"${[\"s\", nil]}\";
+            let total = 0;
+            for (x in [1, 2, 3, 4, 5]) {
+                if (x == 2) continue;
+                if (x == 5) break;
+                total = total + x;
+            }
+            print total;
+            for (key in m) print key;
+            let fns = [];
+            for (x in [\"a\", \"b\"]) {
+                let f = fn () { return x; };
+                fns = [f, fns];
+            }
+            print fns[0]() + fns[1][0]();
+            print len;")
+        .unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(
+            "[\"one\", 2, [20]]\n8\n{\"a\": 3, 2: true, \"b\": 4}\n3\nfalse\nfalse\ntrue\n\
+             [\"s\", nil]\n8\na\n2\nb\nba\n<native fn len>\n",
+            output
+        );
+    }
+
+    #[test]
+    fn execute_collection_errors() {
+        let error = run("let xs = [1, 2]; print xs[2];").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::IndexOutOfRange { index: 2, len: 2 },
+            error.kind
+        );
+        assert_eq!(Some(Span::new(23, 28)), error.span);
+        assert_eq!(
+            "index 2 is out of range for a list of length 2",
+            error.to_string()
+        );
+
+        let error = run("[1][-1] = 0;").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::IndexOutOfRange { index: -1, len: 1 },
+            error.kind
+        );
+
+        let error = run("print [1][\"0\"];").unwrap_err();
+        assert_eq!(RuntimeErrorKind::InvalidIndex("String"), error.kind);
+
+        let error = run("let m = {\"a\": 1}; print m[\"b\"];").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::MissingKey("\"b\"".to_string()),
+            error.kind
+        );
+        assert_eq!(Some(Span::new(24, 30)), error.span);
+
+        let error = run("let n = 1; n[0] = 2;").unwrap_err();
+        assert_eq!(RuntimeErrorKind::NotIndexable("Integer"), error.kind);
+
+        let error = run("for (c in \"abc\") print c;").unwrap_err();
+        assert_eq!(RuntimeErrorKind::NotIterable("String"), error.kind);
+        assert_eq!(Some(Span::new(10, 15)), error.span);
+
+        let error = run("print len(1);").unwrap_err();
+        assert_eq!(
+            "argument 1 must be List, Map or String, found Integer",
+            error.to_string()
+        );
+        assert_eq!(Some(Span::new(6, 12)), error.span);
+
+        let error = run("len(1, 2);").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::Arity {
+                expected: 1,
+                found: 2
+            },
+            error.kind
+        );
+    }
+
+    #[test]
+    fn builtins_are_not_globals() {
+        let interpreter = Interpreter::new();
+        assert!(interpreter.environment().values().is_empty());
+        assert!(interpreter.environment().get("len").is_some());
+        let statements = parse_program("len = 1;");
+        interpreter.execute(&statements).unwrap();
+        interpreter.reset();
+        assert!(matches!(
+            interpreter.environment().get("len"),
+            Some(Literal::Native(_))
+        ));
+    }
+
+    #[test]
+    fn execute_invalid_calls() {
+        let error = run("let f = 1; f(2);").unwrap_err();
+        assert_eq!(RuntimeErrorKind::NotCallable("Integer"), error.kind);
+        assert_eq!(Some(Span::new(11, 15)), error.span);
+
+        let error = run("fn f(a, b) {} f(1);").unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::Arity {
+                expected: 2,
+                found: 1
+            },
+            error.kind
+        );
+        assert_eq!(Some(Span::new(14, 18)), error.span);
+
+        let error = run("fn f(a) { return -a; } f(\"x\");").unwrap_err();
+        assert_eq!(Some(Span::new(17, 18)), error.span);
+
+        let statements = parse_program("fn f(n) { return f(n + 1); } f(0);");
+        let error = Interpreter::new()
+            .with_max_depth(50)
+            .execute(&statements)
+            .unwrap_err();
+        assert_eq!(RuntimeErrorKind::StackOverflow { depth: 50 }, error.kind);
+        assert_eq!(Some(Span::new(17, 25)), error.span);
+    }
+
+    #[test]
+    fn evaluate_invalid_tree() {
+        let error = binary(
+            TokenType::Bang,
+            Literal::Integer(1),
+            Literal::Integer(2),
+            NumericMode::Checked,
+        )
+        .unwrap_err();
+        assert_eq!(
+            "invalid syntax tree: `!` is not a binary operator",
+            error.to_string()
+        );
+
+        // 手工把运算符替换为其他种类的运算符
+        let evaluate = |source: &str, token_type: TokenType| {
+            let mut expr = parse_expr(source);
+            match &mut expr {
+                Expr::Binary { operator, .. }
+                | Expr::Logical { operator, .. }
+                | Expr::Unary { operator, .. } => operator.token_type = token_type,
+                _ => panic!("Expect operator expression, got {:?}", expr),
+            }
+            Interpreter::new().evaluate(&expr).unwrap_err()
+        };
+        let error = evaluate("1 + 2", TokenType::Or);
+        assert_eq!(
+            RuntimeErrorKind::InvalidSyntaxTree("`||` is not a binary operator".to_string()),
+            error.kind
+        );
+        assert_eq!(Some(Span::new(2, 3)), error.span);
+        let error = evaluate("1 || 2", TokenType::Plus);
+        assert_eq!(
+            RuntimeErrorKind::InvalidSyntaxTree("`+` is not a logical operator".to_string()),
+            error.kind
+        );
+        assert_eq!(Some(Span::new(2, 4)), error.span);
+        let error = evaluate("-1", TokenType::Star);
+        assert_eq!(
+            RuntimeErrorKind::InvalidSyntaxTree("`*` is not a unary operator".to_string()),
+            error.kind
+        );
+        assert_eq!(Some(Span::new(0, 1)), error.span);
+    }
+
+    #[test]
+    fn recursion_limits() {
+        let output = run("fn f(n) { if (n == 0) return 0; return 1 + f(n - 1); } print f(100);");
+        assert_eq!(Ok("100\n".to_string()), output);
+
+        // 无论调用上限多高，都在耗尽 2MB 的线程栈之前报错
+        let error = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(|| {
+                let statements =
+                    parse_program("fn f(n) { let m = -(-(-(n + 1))); return f(m) + 1; } f(0);");
+                Interpreter::new()
+                    .with_max_depth(usize::MAX)
+                    .execute(&statements)
+                    .unwrap_err()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(
+            RuntimeErrorKind::StackExhausted {
+                budget: Interpreter::STACK_BUDGET
+            },
+            error.kind
+        );
+
+        // 没有调用时，过长的表达式同样受栈预算的限制
+        let sum = vec!["1"; 200].join(" + ");
+        let expr = parse_expr(&sum);
+        let interpreter = Interpreter::new().with_stack_budget(16 << 10);
+        assert_eq!(
+            Err(RuntimeError::new(RuntimeErrorKind::StackExhausted {
+                budget: 16 << 10
+            })),
+            interpreter.evaluate(&expr).map_err(|mut error| {
+                error.span = None;
+                error
+            })
+        );
+        assert_eq!(
+            "stack overflow: evaluation used more than 16 KiB of native stack",
+            interpreter.evaluate(&expr).unwrap_err().to_string()
+        );
+        assert_eq!(
+            Ok(Literal::Integer(200)),
+            Interpreter::new().evaluate(&expr)
+        );
+    }
+}
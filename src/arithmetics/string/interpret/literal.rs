@@ -1,83 +1,740 @@
+use std::cmp::Ordering;
+use std::fmt;
 use std::ops::{Add, Div, Mul, Neg, Not, Sub};
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+use super::bigint::BigInt;
+use super::collection::{self, List, Map};
+use super::function::{Function, NativeFunction};
+use super::interpreter::{RuntimeError, RuntimeErrorKind};
+use super::rational::Rational;
+
+/// 数值类型按 `Integer` ⊂ `BigInt` ⊂ `Rational` ⊂ `Float` 的顺序提升。
+/// `BigInt` 只保存超出 `i64` 范围的值，`Rational` 只保存非整数，
+/// 运算结果会自动规约回最窄的类型。
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Integer(i64),
+    BigInt(BigInt),
+    Rational(Rational),
     Float(f64),
     String(String),
     Bool(bool),
     None,
+    /// 列表与映射按引用共享，也按引用比较
+    List(Rc<List>),
+    Map(Rc<Map>),
+    /// 函数按引用比较
+    Function(Rc<Function>),
+    Native(Rc<NativeFunction>),
+}
+
+/// 整数运算溢出时的处理方式
+///
+/// 默认为 `Checked`，提升为 `BigInt`、`Rational` 的精确计算需通过 `with_mode` 选择 `Exact`。
+/// 整数除以 0 在任何模式下都是运行时错误；浮点运算遵循 IEEE 754，不受影响。已经是 `BigInt`、`Rational` 的操作数总是精确计算。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumericMode {
+    /// 溢出时返回运行时错误
+    #[default]
+    Checked,
+    /// 按二进制补码回绕，如 `i64::MAX + 1 == i64::MIN`
+    Wrapping,
+    /// 截断到 `i64::MIN` 或 `i64::MAX`
+    Saturating,
+    /// 溢出时改用浮点数计算
+    PromoteFloat,
+    /// 溢出时提升为 `BigInt`，整数除法结果为精确的 `Rational`，如 `1 / 3`
+    Exact,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MathOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl MathOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            MathOp::Add => "+",
+            MathOp::Sub => "-",
+            MathOp::Mul => "*",
+            MathOp::Div => "/",
+        }
+    }
 }
 
-macro_rules! literal_math_op {
-    ($left:expr, $operator:tt, $right:expr) => {
-        match ($left, $right) {
-            (Literal::Integer(left), Literal::Integer(right)) => Literal::Integer(left $operator right),
-            (Literal::Float(left), Literal::Float(right)) => Literal::Float(left $operator right),
-            (Literal::Integer(left), Literal::Float(right)) => Literal::Float(left as f64 $operator right),
-            (Literal::Float(left), Literal::Integer(right)) => Literal::Float(left $operator right as f64),
-            _ => panic!("Unsupported operation for given types."),
+/// 算术运算，类型不匹配、整数溢出或整数除以 0 时返回 `RuntimeError`
+fn math(
+    op: MathOp,
+    left: Literal,
+    right: Literal,
+    mode: NumericMode,
+) -> Result<Literal, RuntimeError> {
+    match (left, right) {
+        (Literal::Integer(left), Literal::Integer(right)) => integer_math(op, left, right, mode),
+        (left, right) if !left.is_number() || !right.is_number() => {
+            Err(RuntimeError::binary_operands(op.symbol(), &left, &right))
+        }
+        (left @ Literal::Float(_), right) | (left, right @ Literal::Float(_)) => Ok(
+            Literal::Float(float_math(op, left.to_f64(), right.to_f64())),
+        ),
+        (left @ Literal::Rational(_), right) | (left, right @ Literal::Rational(_)) => {
+            rational_math(op, &left.to_rational(), &right.to_rational())
         }
+        (left, right) => bigint_math(op, &left.to_bigint(), &right.to_bigint(), mode),
+    }
+}
+
+fn float_math(op: MathOp, left: f64, right: f64) -> f64 {
+    match op {
+        MathOp::Add => left + right,
+        MathOp::Sub => left - right,
+        MathOp::Mul => left * right,
+        MathOp::Div => left / right,
+    }
+}
+
+fn integer_math(
+    op: MathOp,
+    left: i64,
+    right: i64,
+    mode: NumericMode,
+) -> Result<Literal, RuntimeError> {
+    if op == MathOp::Div && right == 0 {
+        return Err(RuntimeError::new(RuntimeErrorKind::DivisionByZero));
+    }
+    if op == MathOp::Div && mode == NumericMode::Exact && left.checked_rem(right) != Some(0) {
+        return bigint_math(op, &BigInt::from(left), &BigInt::from(right), mode);
+    }
+    let checked = match op {
+        MathOp::Add => left.checked_add(right),
+        MathOp::Sub => left.checked_sub(right),
+        MathOp::Mul => left.checked_mul(right),
+        MathOp::Div => left.checked_div(right),
     };
+    if let Some(val) = checked {
+        return Ok(Literal::Integer(val));
+    }
+    match mode {
+        NumericMode::Checked => Err(RuntimeError::new(RuntimeErrorKind::Overflow {
+            operator: op.symbol(),
+        })),
+        NumericMode::Wrapping => Ok(Literal::Integer(match op {
+            MathOp::Add => left.wrapping_add(right),
+            MathOp::Sub => left.wrapping_sub(right),
+            MathOp::Mul => left.wrapping_mul(right),
+            MathOp::Div => left.wrapping_div(right),
+        })),
+        NumericMode::Saturating => Ok(Literal::Integer(match op {
+            MathOp::Add => left.saturating_add(right),
+            MathOp::Sub => left.saturating_sub(right),
+            MathOp::Mul => left.saturating_mul(right),
+            MathOp::Div => left.saturating_div(right),
+        })),
+        NumericMode::PromoteFloat => Ok(Literal::Float(float_math(op, left as f64, right as f64))),
+        NumericMode::Exact => bigint_math(op, &BigInt::from(left), &BigInt::from(right), mode),
+    }
 }
 
-impl Add for Literal {
-    type Output = Self;
-    fn add(self, rhs: Self) -> Self::Output {
+fn bigint_math(
+    op: MathOp,
+    left: &BigInt,
+    right: &BigInt,
+    mode: NumericMode,
+) -> Result<Literal, RuntimeError> {
+    match op {
+        MathOp::Add => Ok(Literal::from(left + right)),
+        MathOp::Sub => Ok(Literal::from(left - right)),
+        MathOp::Mul => Ok(Literal::from(left * right)),
+        MathOp::Div if right.is_zero() => Err(RuntimeError::new(RuntimeErrorKind::DivisionByZero)),
+        MathOp::Div if mode == NumericMode::Exact => {
+            Ok(Literal::from(Rational::new(left.clone(), right.clone())))
+        }
+        MathOp::Div => Ok(Literal::from(left / right)),
+    }
+}
+
+fn rational_math(op: MathOp, left: &Rational, right: &Rational) -> Result<Literal, RuntimeError> {
+    match op {
+        MathOp::Add => Ok(Literal::from(left + right)),
+        MathOp::Sub => Ok(Literal::from(left - right)),
+        MathOp::Mul => Ok(Literal::from(left * right)),
+        MathOp::Div if right.is_zero() => Err(RuntimeError::new(RuntimeErrorKind::DivisionByZero)),
+        MathOp::Div => Ok(Literal::from(left / right)),
+    }
+}
+
+impl From<BigInt> for Literal {
+    /// 能放进 `i64` 时规约为 `Integer`
+    fn from(value: BigInt) -> Self {
+        match value.to_i64() {
+            Some(val) => Literal::Integer(val),
+            None => Literal::BigInt(value),
+        }
+    }
+}
+
+impl From<Rational> for Literal {
+    /// 分母为 1 时规约为整数
+    fn from(value: Rational) -> Self {
+        if value.is_integer() {
+            Literal::from(value.numerator().clone())
+        } else {
+            Literal::Rational(value)
+        }
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Literal::Integer(value)
+    }
+}
+
+macro_rules! impl_from_small_integer {
+    ($($t:ty),*) => {$(
+        impl From<$t> for Literal {
+            fn from(value: $t) -> Self {
+                Literal::Integer(value.into())
+            }
+        }
+    )*};
+}
+
+impl_from_small_integer!(i8, i16, i32, u8, u16, u32);
+
+macro_rules! impl_from_wide_integer {
+    ($($t:ty),*) => {$(
+        /// 超出 `i64` 的范围时为 `BigInt`
+        impl From<$t> for Literal {
+            fn from(value: $t) -> Self {
+                match i64::try_from(value) {
+                    Ok(val) => Literal::Integer(val),
+                    Err(_) => Literal::BigInt(
+                        BigInt::from_str_radix(&value.to_string(), 10)
+                            .expect("integer formats as decimal digits"),
+                    ),
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_wide_integer!(isize, u64, usize, i128, u128);
+
+impl From<f64> for Literal {
+    fn from(value: f64) -> Self {
+        Literal::Float(value)
+    }
+}
+
+impl From<f32> for Literal {
+    fn from(value: f32) -> Self {
+        Literal::Float(value.into())
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Literal::Bool(value)
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Literal::String(value)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Literal::String(value.to_string())
+    }
+}
+
+impl From<()> for Literal {
+    fn from(_: ()) -> Self {
+        Literal::None
+    }
+}
+
+/// `None` 转换为 `nil`
+impl<T: Into<Literal>> From<Option<T>> for Literal {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Literal::None, Into::into)
+    }
+}
+
+/// 转换为新的列表
+impl<T: Into<Literal>> From<Vec<T>> for Literal {
+    fn from(values: Vec<T>) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        Literal::List(Rc::new(List::new(values)))
+    }
+}
+
+impl Literal {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Literal::Integer(_) => "Integer",
+            Literal::BigInt(_) => "BigInt",
+            Literal::Rational(_) => "Rational",
+            Literal::Float(_) => "Float",
+            Literal::String(_) => "String",
+            Literal::Bool(_) => "Bool",
+            Literal::None => "None",
+            Literal::List(_) => "List",
+            Literal::Map(_) => "Map",
+            Literal::Function(_) | Literal::Native(_) => "Function",
+        }
+    }
+
+    /// 真值: `false`、`0`、空字符串、空集合与 `None` 为假，其余为真
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Literal::Bool(val) => *val,
+            Literal::Float(_) => true,
+            Literal::Integer(val) => *val != 0,
+            Literal::BigInt(val) => !val.is_zero(),
+            Literal::Rational(val) => !val.is_zero(),
+            Literal::String(val) => !val.is_empty(),
+            Literal::None => false,
+            Literal::List(list) => !list.is_empty(),
+            Literal::Map(map) => !map.is_empty(),
+            Literal::Function(_) | Literal::Native(_) => true,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Literal::Integer(_) | Literal::BigInt(_) | Literal::Rational(_) | Literal::Float(_)
+        )
+    }
+
+    /// 数值转换为浮点数，非数值返回 `None`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Literal::Integer(val) => Some(*val as f64),
+            Literal::BigInt(val) => Some(val.to_f64()),
+            Literal::Rational(val) => Some(val.to_f64()),
+            Literal::Float(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.as_f64().unwrap_or(f64::NAN)
+    }
+
+    /// 要求为 `Integer`、`BigInt` 或 `Rational`
+    fn to_rational(&self) -> Rational {
+        match self {
+            Literal::Rational(val) => val.clone(),
+            _ => Rational::from_integer(self.to_bigint()),
+        }
+    }
+
+    /// 要求为 `Integer` 或 `BigInt`
+    fn to_bigint(&self) -> BigInt {
+        match self {
+            Literal::Integer(val) => BigInt::from(*val),
+            Literal::BigInt(val) => val.clone(),
+            _ => unreachable!("not an integer: {:?}", self),
+        }
+    }
+
+    pub fn add_with(self, rhs: Self, mode: NumericMode) -> Result<Literal, RuntimeError> {
         match (self, rhs) {
-            (Literal::Integer(left), Literal::Integer(right)) => Literal::Integer(left + right),
-            (Literal::Float(left), Literal::Float(right)) => Literal::Float(left + right),
-            (Literal::Integer(left), Literal::Float(right)) => Literal::Float(left as f64 + right),
-            (Literal::Float(left), Literal::Integer(right)) => Literal::Float(left + right as f64),
             (Literal::String(left), Literal::String(right)) => {
-                Literal::String(format!("{}{}", left, right))
+                Ok(Literal::String(format!("{}{}", left, right)))
             }
-            _ => panic!("Unsupported operation for given types."),
+            (left, right) => math(MathOp::Add, left, right, mode),
         }
     }
+
+    pub fn sub_with(self, rhs: Self, mode: NumericMode) -> Result<Literal, RuntimeError> {
+        math(MathOp::Sub, self, rhs, mode)
+    }
+
+    pub fn mul_with(self, rhs: Self, mode: NumericMode) -> Result<Literal, RuntimeError> {
+        math(MathOp::Mul, self, rhs, mode)
+    }
+
+    pub fn div_with(self, rhs: Self, mode: NumericMode) -> Result<Literal, RuntimeError> {
+        math(MathOp::Div, self, rhs, mode)
+    }
+
+    pub fn neg_with(self, mode: NumericMode) -> Result<Literal, RuntimeError> {
+        match self {
+            Literal::Integer(val) => match val.checked_neg() {
+                Some(val) => Ok(Literal::Integer(val)),
+                // 只有 i64::MIN 会溢出，按 `0 - x` 处理
+                None => integer_math(MathOp::Sub, 0, val, mode),
+            },
+            Literal::BigInt(val) => Ok(Literal::from(-val)),
+            Literal::Rational(val) => Ok(Literal::Rational(-&val)),
+            Literal::Float(val) => Ok(Literal::Float(-val)),
+            operand => Err(RuntimeError::unary_operand("-", &operand)),
+        }
+    }
+
+    /// 语言中的 `==`：数值按大小比较，如 `1 == 1.0`；其余类型需类型与值都相同
+    pub fn equals(&self, rhs: &Self) -> bool {
+        if self.is_number() && rhs.is_number() {
+            return self.numeric_cmp(rhs) == Some(Ordering::Equal);
+        }
+        self == rhs
+    }
+
+    /// 语言中的 `<`、`>` 等比较；数值之间、字符串之间、布尔值之间可比较，
+    /// 其余组合返回运行时错误。与 NaN 比较时返回 `Ok(None)`。
+    pub fn compare(
+        &self,
+        rhs: &Self,
+        operator: &'static str,
+    ) -> Result<Option<Ordering>, RuntimeError> {
+        match (self, rhs) {
+            (Literal::String(left), Literal::String(right)) => Ok(Some(left.cmp(right))),
+            (Literal::Bool(left), Literal::Bool(right)) => Ok(Some(left.cmp(right))),
+            (left, right) if left.is_number() && right.is_number() => Ok(left.numeric_cmp(right)),
+            (left, right) => Err(RuntimeError::binary_operands(operator, left, right)),
+        }
+    }
+
+    /// `value[index]`：列表的下标必须是 `Integer` 且不越界，映射的键必须存在
+    pub fn index(&self, index: &Literal) -> Result<Literal, RuntimeError> {
+        match self {
+            Literal::List(list) => {
+                let position = list_position(list, index)?;
+                Ok(list.get(position).unwrap_or(Literal::None))
+            }
+            Literal::Map(map) => map.get(index).ok_or_else(|| {
+                RuntimeError::new(RuntimeErrorKind::MissingKey(collection::quoted(index)))
+            }),
+            other => Err(RuntimeError::new(RuntimeErrorKind::NotIndexable(
+                other.type_name(),
+            ))),
+        }
+    }
+
+    /// `value[index] = element`：列表只能替换已有的元素，映射可以添加新键
+    pub fn set_index(&self, index: Literal, element: Literal) -> Result<(), RuntimeError> {
+        match self {
+            Literal::List(list) => {
+                let position = list_position(list, &index)?;
+                list.set(position, element);
+                Ok(())
+            }
+            Literal::Map(map) => {
+                map.insert(index, element);
+                Ok(())
+            }
+            other => Err(RuntimeError::new(RuntimeErrorKind::NotIndexable(
+                other.type_name(),
+            ))),
+        }
+    }
+
+    /// `for (x in value)` 依次取出的值：列表的元素或映射的键，取值时的副本
+    pub fn iterate(&self) -> Result<Vec<Literal>, RuntimeError> {
+        match self {
+            Literal::List(list) => Ok(list.values()),
+            Literal::Map(map) => Ok(map.keys()),
+            other => Err(RuntimeError::new(RuntimeErrorKind::NotIterable(
+                other.type_name(),
+            ))),
+        }
+    }
+
+    /// 要求两侧都是数值
+    fn numeric_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        match (self, rhs) {
+            (Literal::Integer(left), Literal::Integer(right)) => Some(left.cmp(right)),
+            (Literal::Float(_), _) | (_, Literal::Float(_)) => {
+                self.to_f64().partial_cmp(&rhs.to_f64())
+            }
+            _ => Some(self.to_rational().cmp(&rhs.to_rational())),
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    /// `print` 的输出格式；浮点数总是带小数点，如 `3.0`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Integer(val) => write!(f, "{}", val),
+            Literal::BigInt(val) => write!(f, "{}", val),
+            Literal::Rational(val) => write!(f, "{}", val),
+            Literal::Float(val) => write!(f, "{:?}", val),
+            Literal::String(val) => f.write_str(val),
+            Literal::Bool(val) => write!(f, "{}", val),
+            Literal::None => f.write_str("nil"),
+            Literal::List(list) => write!(f, "{}", list),
+            Literal::Map(map) => write!(f, "{}", map),
+            Literal::Function(function) => write!(f, "{}", function),
+            Literal::Native(function) => write!(f, "{}", function),
+        }
+    }
+}
+
+fn list_position(list: &List, index: &Literal) -> Result<usize, RuntimeError> {
+    let Literal::Integer(value) = index else {
+        return Err(RuntimeError::new(RuntimeErrorKind::InvalidIndex(
+            index.type_name(),
+        )));
+    };
+    match usize::try_from(*value) {
+        Ok(position) if position < list.len() => Ok(position),
+        _ => Err(RuntimeError::new(RuntimeErrorKind::IndexOutOfRange {
+            index: *value,
+            len: list.len(),
+        })),
+    }
+}
+
+impl Add for Literal {
+    type Output = Result<Literal, RuntimeError>;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.add_with(rhs, NumericMode::default())
+    }
 }
 
 impl Sub for Literal {
-    type Output = Self;
+    type Output = Result<Literal, RuntimeError>;
     fn sub(self, rhs: Self) -> Self::Output {
-        literal_math_op!(self, -, rhs)
+        self.sub_with(rhs, NumericMode::default())
     }
 }
 
 impl Mul for Literal {
-    type Output = Self;
+    type Output = Result<Literal, RuntimeError>;
     fn mul(self, rhs: Self) -> Self::Output {
-        literal_math_op!(self, *, rhs)
+        self.mul_with(rhs, NumericMode::default())
     }
 }
 
 impl Div for Literal {
-    type Output = Self;
+    type Output = Result<Literal, RuntimeError>;
     fn div(self, rhs: Self) -> Self::Output {
-        literal_math_op!(self, /, rhs)
+        self.div_with(rhs, NumericMode::default())
     }
 }
 
 impl Not for Literal {
     type Output = Self;
     fn not(self) -> Self::Output {
-        match self {
-            Literal::Bool(val) => Literal::Bool(!val),
-            Literal::Float(_) => Literal::Bool(false),
-            Literal::Integer(val) => Literal::Bool(val == 0),
-            Literal::String(val) => Literal::Bool(val.is_empty()),
-            Literal::None => Literal::Bool(true),
-        }
+        Literal::Bool(!self.is_truthy())
     }
 }
 
 impl Neg for Literal {
-    type Output = Self;
+    type Output = Result<Literal, RuntimeError>;
     fn neg(self) -> Self::Output {
-        match self {
-            Literal::Integer(val) => Literal::Integer(-val),
-            Literal::Float(val) => Literal::Float(-val),
-            _ => panic!("Negative signs can only be applied before numbers."),
+        self.neg_with(NumericMode::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn math_with_mismatched_types() {
+        let error = (Literal::String("a".to_string()) - Literal::Integer(1)).unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::BinaryOperands {
+                operator: "-",
+                left: "String",
+                right: "Integer"
+            },
+            error.kind
+        );
+        assert_eq!(None, error.span);
+
+        let error = (-Literal::Bool(true)).unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::UnaryOperand {
+                operator: "-",
+                operand: "Bool"
+            },
+            error.kind
+        );
+    }
+
+    #[test]
+    fn math_with_mixed_numbers() {
+        assert_eq!(
+            Ok(Literal::Float(2.5)),
+            Literal::Integer(2) + Literal::Float(0.5)
+        );
+        assert_eq!(
+            Ok(Literal::String("ab".to_string())),
+            Literal::String("a".to_string()) + Literal::String("b".to_string())
+        );
+    }
+
+    #[test]
+    fn integer_overflow_modes() {
+        let max = || Literal::Integer(i64::MAX);
+        let one = || Literal::Integer(1);
+        assert_eq!(
+            RuntimeErrorKind::Overflow { operator: "+" },
+            max()
+                .add_with(one(), NumericMode::Checked)
+                .unwrap_err()
+                .kind
+        );
+        // 运算符默认按 `Checked` 计算
+        assert_eq!(
+            RuntimeErrorKind::Overflow { operator: "+" },
+            (max() + one()).unwrap_err().kind
+        );
+        assert_eq!(
+            "9223372036854775808",
+            max()
+                .add_with(one(), NumericMode::Exact)
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            Ok(Literal::Integer(i64::MIN)),
+            max().add_with(one(), NumericMode::Wrapping)
+        );
+        assert_eq!(Ok(max()), max().add_with(one(), NumericMode::Saturating));
+        assert_eq!(
+            Ok(Literal::Float(i64::MAX as f64 * 2.0)),
+            max().mul_with(Literal::Integer(2), NumericMode::PromoteFloat)
+        );
+
+        let min = || Literal::Integer(i64::MIN);
+        assert_eq!(
+            RuntimeErrorKind::Overflow { operator: "-" },
+            min().neg_with(NumericMode::Checked).unwrap_err().kind
+        );
+        assert_eq!(Ok(min()), min().neg_with(NumericMode::Wrapping));
+        assert_eq!(
+            Ok(max()),
+            min().div_with(Literal::Integer(-1), NumericMode::Saturating)
+        );
+    }
+
+    #[test]
+    fn integer_division_by_zero() {
+        for mode in [
+            NumericMode::Checked,
+            NumericMode::Wrapping,
+            NumericMode::Saturating,
+            NumericMode::PromoteFloat,
+            NumericMode::Exact,
+        ] {
+            assert_eq!(
+                RuntimeErrorKind::DivisionByZero,
+                Literal::Integer(1)
+                    .div_with(Literal::Integer(0), mode)
+                    .unwrap_err()
+                    .kind
+            );
         }
+        assert_eq!(
+            Ok(Literal::Float(f64::INFINITY)),
+            Literal::Integer(1) / Literal::Float(0.0)
+        );
+    }
+
+    #[test]
+    fn exact_mode_promotes_to_bigint() {
+        let max = Literal::Integer(i64::MAX);
+        let sum = max
+            .clone()
+            .add_with(Literal::Integer(1), NumericMode::Exact)
+            .unwrap();
+        assert_eq!(
+            Literal::BigInt(BigInt::from_str_radix("9223372036854775808", 10).unwrap()),
+            sum
+        );
+        // 结果回到 i64 范围内时规约为 Integer
+        assert_eq!(
+            Ok(max),
+            sum.sub_with(Literal::Integer(1), NumericMode::Checked)
+        );
+        assert_eq!(
+            Ok(Literal::BigInt(
+                BigInt::from_str_radix("9223372036854775808", 10).unwrap()
+            )),
+            Literal::Integer(i64::MIN).neg_with(NumericMode::Exact)
+        );
+    }
+
+    #[test]
+    fn exact_mode_division() {
+        let third = Literal::Integer(1)
+            .div_with(Literal::Integer(3), NumericMode::Exact)
+            .unwrap();
+        assert_eq!("Rational", third.type_name());
+        let one = third
+            .clone()
+            .mul_with(Literal::Integer(3), NumericMode::Exact)
+            .unwrap();
+        assert_eq!(Literal::Integer(1), one);
+        assert_eq!(
+            Ok(Literal::Integer(2)),
+            Literal::Integer(6).div_with(Literal::Integer(3), NumericMode::Exact)
+        );
+        assert_eq!(
+            Ok(Literal::Integer(0)),
+            Literal::Integer(1).div_with(Literal::Integer(3), NumericMode::Checked)
+        );
+        let converted = third
+            .add_with(Literal::Float(0.5), NumericMode::Exact)
+            .unwrap();
+        assert!(matches!(converted, Literal::Float(val) if (val - 5.0 / 6.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn compare_mixed_numbers() {
+        let big = Literal::BigInt(BigInt::from_str_radix("100000000000000000000", 10).unwrap());
+        let half = Literal::Integer(1)
+            .div_with(Literal::Integer(2), NumericMode::Exact)
+            .unwrap();
+        assert_eq!(
+            Ok(Some(Ordering::Less)),
+            Literal::Integer(2).compare(&big, "<")
+        );
+        assert_eq!(
+            Ok(Some(Ordering::Greater)),
+            Literal::Integer(2).compare(&Literal::Float(1.5), ">")
+        );
+        assert_eq!(
+            Ok(Some(Ordering::Less)),
+            half.compare(&Literal::Integer(1), "<")
+        );
+        assert!(half.equals(&Literal::Float(0.5)));
+        assert!(Literal::Integer(1).equals(&Literal::Float(1.0)));
+        assert!(!Literal::Integer(1).equals(&Literal::String("1".to_string())));
+        assert_eq!(
+            Ok(None),
+            Literal::Float(f64::NAN).compare(&Literal::Integer(1), "<")
+        );
+        assert!(Literal::String("a".to_string())
+            .compare(&Literal::Integer(1), "<")
+            .is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("3.0", Literal::Float(3.0).to_string());
+        assert_eq!("0.1", Literal::Float(0.1).to_string());
+        assert_eq!("-7", Literal::Integer(-7).to_string());
+        assert_eq!("a b", Literal::String("a b".to_string()).to_string());
+        assert_eq!("nil", Literal::None.to_string());
+        let third = Literal::Integer(1)
+            .div_with(Literal::Integer(3), NumericMode::Exact)
+            .unwrap();
+        assert_eq!("1/3", third.to_string());
     }
 }
@@ -1,5 +1,25 @@
+pub mod bigint;
+pub mod builtins;
+pub mod bytecode;
+pub mod checker;
+pub mod collection;
+pub mod compiler;
+pub mod diagnostics;
+pub mod engine;
+pub mod environment;
 pub mod expr;
+pub mod folder;
+pub mod function;
+pub mod interpreter;
 pub mod literal;
 pub mod parser;
+pub mod printer;
+pub mod rational;
+pub mod repl;
+pub mod runner;
 pub mod scanner;
+pub mod stmt;
+#[cfg(test)]
+mod testing;
 pub mod token;
+pub mod vm;
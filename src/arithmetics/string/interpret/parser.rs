@@ -1,91 +1,1195 @@
 use std::cell::Cell;
+use std::fmt;
 
 /// 递归下降解析器
-use super::expr::{Expr, ExprVisitor};
+///
+/// program    -> declaration*
+/// declaration-> "let" IDENTIFIER ( "=" expression )? ";"
+///             | "fn" IDENTIFIER function | statement
+/// function   -> "(" ( IDENTIFIER ( "," IDENTIFIER )* )? ")" block
+/// statement  -> "print" expression ";" | block | if | while | for
+///             | "break" ";" | "continue" ";" | "return" expression? ";"
+///             | expression ";"
+/// if         -> "if" "(" expression ")" statement ( "else" statement )?
+/// while      -> "while" "(" expression ")" statement
+/// for        -> "for" "(" ( let | expression ";" | ";" ) expression? ";" expression? ")" statement
+///             | "for" "(" IDENTIFIER "in" expression ")" statement
+/// block      -> "{" declaration* "}"
+/// expression -> assignment
+/// assignment -> ( IDENTIFIER | call "[" expression "]" ) "=" assignment | or
+/// or         -> and ( "||" and )*
+/// and        -> equality ( "&&" equality )*
+/// equality   -> comparison ( ( "!=" | "==" ) comparison )*
+/// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )*
+/// term       -> factor ( ( "-" | "+" ) factor )*
+/// factor     -> unary ( ( "/" | "*" ) unary )*
+/// unary      -> ( "!" | "-" ) unary | call
+/// call       -> primary ( "(" ( expression ( "," expression )* )? ")" | "[" expression "]" )*
+/// primary    -> NUMBER | STRING | INTERPOLATION | "true" | "false" | "nil" | IDENTIFIER
+///             | "(" expression ")" | "fn" function
+///             | "[" ( expression ( "," expression )* )? "]"
+///             | "{" ( expression ":" expression ( "," expression ":" expression )* )? "}"
+///
+/// 语句开头的 `{` 总是块，映射只出现在表达式中
+use std::rc::Rc;
+
+use super::expr::{Expr, InterpolationPart};
+use super::stmt::{FunctionDecl, Stmt};
 
 use super::literal::Literal;
-use super::token::{Token, TokenType};
+use super::token::{Span, StringPart, Token, TokenType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The token where the error was detected, `None` at the end of input.
+    /// Boxed to keep `Result<_, ParseError>` small.
+    pub token: Option<Box<Token>>,
+    /// 出错 token 的区间；输入结束时为最后一个 token 之后的空区间
+    pub span: Span,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Option<Token>, span: Span, message: &str) -> Self {
+        ParseError {
+            token: token.map(Box::new),
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.token {
+            Some(token) => write!(
+                f,
+                "[line {}:{}] Error at {}: {}",
+                token.position.line + 1,
+                token.position.column + 1,
+                token.token_type,
+                self.message
+            ),
+            None => write!(f, "Error at end: {}", self.message),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: Cell<usize>,
+    /// 当前所在循环的嵌套层数，用于检查 `break`/`continue`
+    loop_depth: Cell<usize>,
+    /// 当前所在函数的嵌套层数，用于检查 `return`
+    function_depth: Cell<usize>,
+    /// 正在解析的括号、块等递归结构的层数
+    nesting: Cell<usize>,
+    /// 正在构造的语法树节点的深度，左结合的运算符链中每个运算符也算一层
+    height: Cell<usize>,
+    max_nesting: usize,
+    max_height: usize,
 }
 
 impl Parser {
+    /// 括号、块等递归结构的默认嵌套上限
+    ///
+    /// 实测每层括号占用 Rust 栈约 29KB（debug）或 7KB（release），每层块约 6KB（debug）。
+    /// 达到上限的输入需要的栈超过新线程默认的 2MB，`runner::STACK_SIZE` 足够；
+    /// 在较小的栈上解析不受信任的输入时用 `with_max_nesting` 收紧。
+    pub const MAX_NESTING: usize = 256;
+
+    /// 语法树的默认深度上限。各个访问者与语法树的释放都会递归遍历，
+    /// 每层在 debug 构建中约占 2KB 栈。
+    pub const MAX_HEIGHT: usize = 4096;
+
     pub fn new(tokens: Vec<Token>) -> Self {
         Parser {
             tokens,
             current: Cell::new(0),
+            loop_depth: Cell::new(0),
+            function_depth: Cell::new(0),
+            nesting: Cell::new(0),
+            height: Cell::new(0),
+            max_nesting: Parser::MAX_NESTING,
+            max_height: Parser::MAX_HEIGHT,
+        }
+    }
+
+    /// 限制括号、块等的嵌套层数，超过时报告 "Too much nesting."
+    pub fn with_max_nesting(mut self, max_nesting: usize) -> Self {
+        self.max_nesting = max_nesting;
+        self
+    }
+
+    /// 限制语法树的深度，超过时报告 "Expression too complex."
+    pub fn with_max_height(mut self, max_height: usize) -> Self {
+        self.max_height = max_height;
+        self
+    }
+
+    /// Parses the whole token stream as a single expression.
+    pub fn parse(&self) -> Result<Expr, ParseError> {
+        self.current.set(0);
+        let expr = self.expression()?;
+        match self.peek() {
+            Some(token) => Err(self.error(Some(token), "Expect end of expression.")),
+            None => Ok(expr),
+        }
+    }
+
+    /// Parses the whole token stream as a sequence of statements.
+    pub fn parse_program(&self) -> Result<Vec<Stmt>, ParseError> {
+        self.current.set(0);
+        let mut statements = vec![];
+        while self.peek().is_some() {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    /// 在嵌套一层的递归结构中解析，超过 `max_nesting` 时报错；出错时同样恢复层数
+    fn nest<T>(&self, parse: impl FnOnce() -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.nesting.get() >= self.max_nesting {
+            return Err(self.error(self.peek(), "Too much nesting."));
+        }
+        self.nesting.set(self.nesting.get() + 1);
+        let result = self.deepen(1, parse);
+        self.nesting.set(self.nesting.get() - 1);
+        result
+    }
+
+    /// 解析深 `levels` 层的子树，语法树超过 `max_height` 时报错
+    fn deepen<T>(
+        &self,
+        levels: usize,
+        parse: impl FnOnce() -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let height = self.height.get() + levels;
+        if height > self.max_height {
+            return Err(self.error(self.peek(), "Expression too complex."));
+        }
+        self.height.set(height);
+        let result = parse();
+        self.height.set(height - levels);
+        result
+    }
+
+    fn declaration(&self) -> Result<Stmt, ParseError> {
+        if self.match_token(&[TokenType::Let]).is_some() {
+            return self.let_declaration();
+        }
+        // `fn (` 开头的是匿名函数表达式，交给 `statement`
+        if self.check_next(TokenType::Identifier) {
+            if let Some(keyword) = self.match_token(&[TokenType::Fn]) {
+                let name = self.advance().cloned();
+                return Ok(Stmt::Function(self.function(keyword, name)?));
+            }
+        }
+        self.statement()
+    }
+
+    /// `fn` 与函数名（如有）已被消费
+    fn function(
+        &self,
+        keyword: Token,
+        name: Option<Token>,
+    ) -> Result<Rc<FunctionDecl>, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'fn'.")?;
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name.")?
+                        .clone(),
+                );
+                if self.match_token(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.")?;
+
+        // 函数体内的 `break` 不能跳出函数外的循环
+        let loop_depth = self.loop_depth.replace(0);
+        self.function_depth.set(self.function_depth.get() + 1);
+        let body = self.block();
+        self.function_depth.set(self.function_depth.get() - 1);
+        self.loop_depth.set(loop_depth);
+        let body = body?;
+
+        let end = self.tokens[self.current.get() - 1].span;
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body,
+            span: keyword.span.merge(end),
+        }))
+    }
+
+    fn let_declaration(&self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect variable name.")?
+            .clone();
+        let initializer = match self.match_token(&[TokenType::Equal]) {
+            Some(_) => Some(self.expression()?),
+            None => None,
+        };
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Let { name, initializer })
+    }
+
+    fn statement(&self) -> Result<Stmt, ParseError> {
+        if self.match_token(&[TokenType::Print]).is_some() {
+            let expr = self.expression()?;
+            self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+            return Ok(Stmt::Print(expr));
+        }
+        if self.match_token(&[TokenType::LeftBrace]).is_some() {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if self.match_token(&[TokenType::If]).is_some() {
+            return self.if_statement();
+        }
+        if self.match_token(&[TokenType::While]).is_some() {
+            return self.while_statement();
+        }
+        if self.match_token(&[TokenType::For]).is_some() {
+            return self.for_statement();
+        }
+        if let Some(keyword) = self.match_token(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_control(keyword);
+        }
+        if let Some(keyword) = self.match_token(&[TokenType::Return]) {
+            return self.return_statement(keyword);
+        }
+        self.expression_statement()
+    }
+
+    fn expression_statement(&self) -> Result<Stmt, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(expr))
+    }
+
+    /// `else if` 链逐个分支解析，递归不随分支数加深，每个分支使语法树加深一层
+    fn if_statement(&self) -> Result<Stmt, ParseError> {
+        let (condition, then_branch) = self.if_arm()?;
+        let mut arms = vec![];
+        let mut else_branch = None;
+        while self.match_token(&[TokenType::Else]).is_some() {
+            let levels = arms.len() + 1;
+            if self.match_token(&[TokenType::If]).is_none() {
+                else_branch = Some(self.deepen(levels, || self.nest(|| self.statement()))?);
+                break;
+            }
+            arms.push(self.deepen(levels, || self.if_arm())?);
+        }
+        let else_branch =
+            arms.into_iter()
+                .rev()
+                .fold(else_branch, |else_branch, (condition, then_branch)| {
+                    Some(Stmt::If {
+                        condition,
+                        then_branch: Box::new(then_branch),
+                        else_branch: else_branch.map(Box::new),
+                    })
+                });
+        Ok(Stmt::If {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+        })
+    }
+
+    /// `if` 之后的 `(condition) then_branch`
+    fn if_arm(&self) -> Result<(Expr, Stmt), ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+        let then_branch = self.nest(|| self.statement())?;
+        Ok((condition, then_branch))
+    }
+
+    fn while_statement(&self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::While { condition, body })
+    }
+
+    fn for_statement(&self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            return self.for_in_statement();
         }
+        let initializer = if self.match_token(&[TokenType::Semicolon]).is_some() {
+            None
+        } else if self.match_token(&[TokenType::Let]).is_some() {
+            Some(Box::new(self.let_declaration()?))
+        } else {
+            Some(Box::new(self.expression_statement()?))
+        };
+        let condition = match self.check(TokenType::Semicolon) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+        let increment = match self.check(TokenType::RightParen) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::For {
+            initializer,
+            condition,
+            increment,
+            body,
+        })
     }
 
-    fn evaluate(&self, expr: &Box<Expr>) -> Literal {
-        expr.accept(self)
+    /// `for (` 已被消费
+    fn for_in_statement(&self) -> Result<Stmt, ParseError> {
+        let name = self.advance().cloned().expect("loop variable");
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+        let body = Box::new(self.loop_body()?);
+        Ok(Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        })
     }
+
+    /// `return` 关键字已被消费
+    fn return_statement(&self, keyword: Token) -> Result<Stmt, ParseError> {
+        if self.function_depth.get() == 0 {
+            return Err(self.error(Some(&keyword), "Can't return from top-level code."));
+        }
+        let value = match self.check(TokenType::Semicolon) {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn loop_body(&self) -> Result<Stmt, ParseError> {
+        self.loop_depth.set(self.loop_depth.get() + 1);
+        let body = self.nest(|| self.statement());
+        self.loop_depth.set(self.loop_depth.get() - 1);
+        body
+    }
+
+    /// `break`/`continue` 关键字已被消费
+    fn loop_control(&self, keyword: Token) -> Result<Stmt, ParseError> {
+        if self.loop_depth.get() == 0 {
+            return Err(self.error(Some(&keyword), "Must be inside a loop."));
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after loop control.")?;
+        Ok(match keyword.token_type {
+            TokenType::Break => Stmt::Break(keyword),
+            _ => Stmt::Continue(keyword),
+        })
+    }
+
+    /// 左花括号已被消费
+    fn block(&self) -> Result<Vec<Stmt>, ParseError> {
+        self.nest(|| {
+            let mut statements = vec![];
+            while !matches!(
+                self.peek(),
+                None | Some(Token {
+                    token_type: TokenType::RightBrace,
+                    ..
+                })
+            ) {
+                statements.push(self.declaration()?);
+            }
+            self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+            Ok(statements)
+        })
+    }
+
     // 表达式
-    fn expression() {
-        
+    fn expression(&self) -> Result<Expr, ParseError> {
+        self.nest(|| self.assignment())
     }
 
-    fn advance(&self) -> &Token {
-        self.current.set(self.current.get() + 1);
-        &self.tokens[self.current.get() - 1]
+    /// 赋值是右结合的，左侧必须是变量或下标
+    fn assignment(&self) -> Result<Expr, ParseError> {
+        let expr = self.or()?;
+        if let Some(equals) = self.match_token(&[TokenType::Equal]) {
+            let value = self.nest(|| self.assignment())?;
+            return match expr {
+                Expr::Variable { name } => Ok(Expr::Assign {
+                    name,
+                    value: Box::new(value),
+                }),
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                } => Ok(Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value: Box::new(value),
+                }),
+                _ => Err(self.error(Some(&equals), "Invalid assignment target.")),
+            };
+        }
+        Ok(expr)
     }
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current.get()]
+    fn or(&self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        let mut levels = 0;
+        while let Some(operator) = self.match_token(&[TokenType::Or]) {
+            levels += 1;
+            let right = self.deepen(levels, || self.and())?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
     }
 
-    fn is_at_end(&self) -> bool {
-        self.tokens.len() == self.current.get() + 1
+    fn and(&self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality()?;
+        let mut levels = 0;
+        while let Some(operator) = self.match_token(&[TokenType::And]) {
+            levels += 1;
+            let right = self.deepen(levels, || self.equality())?;
+            expr = Expr::Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn equality(&self) -> Result<Expr, ParseError> {
+        self.binary(
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&self) -> Result<Expr, ParseError> {
+        self.binary(
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::term,
+        )
+    }
+
+    fn term(&self) -> Result<Expr, ParseError> {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&self) -> Result<Expr, ParseError> {
+        self.binary(&[TokenType::Slash, TokenType::Star], Self::unary)
+    }
+
+    /// 左结合的二元运算: operand ( operator operand )*
+    ///
+    /// 运算符链在循环中解析，但每个运算符都让语法树加深一层
+    fn binary(
+        &self,
+        operators: &[TokenType],
+        operand: fn(&Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let mut expr = operand(self)?;
+        let mut levels = 0;
+        while let Some(operator) = self.match_token(operators) {
+            levels += 1;
+            let right = self.deepen(levels, || operand(self))?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
     }
-}
 
-impl ExprVisitor for Parser {
-    type ReturnType = Literal;
-    fn visit_binary(
+    /// 连续的前缀运算符逐个收集，递归不随运算符个数加深
+    fn unary(&self) -> Result<Expr, ParseError> {
+        let mut operators = vec![];
+        while let Some(operator) = self.match_token(&[TokenType::Bang, TokenType::Minus]) {
+            operators.push(operator);
+        }
+        let right = self.deepen(operators.len(), || self.call())?;
+        Ok(operators
+            .into_iter()
+            .rev()
+            .fold(right, |right, operator| Expr::Unary {
+                operator,
+                right: Box::new(right),
+            }))
+    }
+
+    fn call(&self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        let mut levels = 0;
+        while let Some(token) = self.match_token(&[TokenType::LeftParen, TokenType::LeftBracket]) {
+            levels += 1;
+            if token.token_type == TokenType::LeftBracket {
+                let index = self.deepen(levels, || self.expression())?;
+                let bracket = self
+                    .consume(TokenType::RightBracket, "Expect ']' after index.")?
+                    .clone();
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
+                continue;
+            }
+            let arguments = self.deepen(levels, || {
+                self.list(TokenType::RightParen, Self::expression)
+            })?;
+            let paren = self
+                .consume(TokenType::RightParen, "Expect ')' after arguments.")?
+                .clone();
+            expr = Expr::Call {
+                callee: Box::new(expr),
+                paren,
+                arguments,
+            };
+        }
+        Ok(expr)
+    }
+
+    /// 以逗号分隔的元素，直到 `end`（不消费 `end`）
+    fn list<T>(
         &self,
-        left: &Box<Expr>,
-        operator: &Token,
-        right: &Box<Expr>,
-    ) -> Self::ReturnType {
-        let token_type = operator.token_type;
-        let left = self.evaluate(left);
-        let right = self.evaluate(right);
-        match token_type {
-            TokenType::Plus => left + right,
-            TokenType::Minus => left - right,
-            TokenType::Star => left * right,
-            TokenType::Slash => left / right,
+        end: TokenType,
+        element: fn(&Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut elements = vec![];
+        if !self.check(end) {
+            loop {
+                elements.push(element(self)?);
+                if self.match_token(&[TokenType::Comma]).is_none() {
+                    break;
+                }
+            }
+        }
+        Ok(elements)
+    }
+
+    fn map_entry(&self) -> Result<(Expr, Expr), ParseError> {
+        let key = self.expression()?;
+        self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+        Ok((key, self.expression()?))
+    }
+
+    fn primary(&self) -> Result<Expr, ParseError> {
+        let token = match self.advance() {
+            Some(token) => token,
+            None => return Err(self.error(None, "Expect expression.")),
+        };
+        match token.token_type {
+            TokenType::Integer | TokenType::Float => Ok(Expr::Literal {
+                value: self.number(token)?,
+                span: token.span,
+            }),
+            TokenType::Identifier => Ok(Expr::Variable {
+                name: token.clone(),
+            }),
+            TokenType::String => Ok(Expr::Literal {
+                value: Literal::String(token.lexeme.clone().unwrap_or_default()),
+                span: token.span,
+            }),
+            TokenType::Interpolation => self.interpolation(token),
+            TokenType::True => Ok(Expr::Literal {
+                value: Literal::Bool(true),
+                span: token.span,
+            }),
+            TokenType::False => Ok(Expr::Literal {
+                value: Literal::Bool(false),
+                span: token.span,
+            }),
+            TokenType::Nil => Ok(Expr::Literal {
+                value: Literal::None,
+                span: token.span,
+            }),
+            TokenType::Fn => Ok(Expr::Function(self.function(token.clone(), None)?)),
+            TokenType::LeftParen => {
+                let start = token.span;
+                let expr = self.expression()?;
+                let end = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                Ok(Expr::Grouping {
+                    expr: Box::new(expr),
+                    span: start.merge(end.span),
+                })
+            }
+            TokenType::LeftBracket => {
+                let start = token.span;
+                let elements = self.list(TokenType::RightBracket, Self::expression)?;
+                let end =
+                    self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+                Ok(Expr::List {
+                    elements,
+                    span: start.merge(end.span),
+                })
+            }
+            TokenType::LeftBrace => {
+                let start = token.span;
+                let entries = self.list(TokenType::RightBrace, Self::map_entry)?;
+                let end = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+                Ok(Expr::Map {
+                    entries,
+                    span: start.merge(end.span),
+                })
+            }
+            _ => Err(self.error(Some(token), "Expect expression.")),
+        }
+    }
+
+    /// 每个 `${}` 片段的 token 单独解析为一个表达式
+    fn interpolation(&self, token: &Token) -> Result<Expr, ParseError> {
+        let parts = token
+            .parts
+            .iter()
+            .map(|part| match part {
+                StringPart::Text(text) => Ok(InterpolationPart::Text(text.clone())),
+                StringPart::Expr { tokens, span } if tokens.is_empty() => Err(ParseError::new(
+                    Some(token.clone()),
+                    *span,
+                    "Expect expression inside '${}'.",
+                )),
+                StringPart::Expr { tokens, .. } => Ok(InterpolationPart::Expr(
+                    self.nested(tokens.clone()).parse()?,
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Expr::Interpolation {
+            parts,
+            span: token.span,
+        })
+    }
+
+    /// 解析 `${...}` 中的 token，继承当前所在的函数、循环与嵌套层数
+    fn nested(&self, tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            current: Cell::new(0),
+            loop_depth: Cell::new(self.loop_depth.get()),
+            function_depth: Cell::new(self.function_depth.get()),
+            nesting: Cell::new(self.nesting.get()),
+            height: Cell::new(self.height.get()),
+            max_nesting: self.max_nesting,
+            max_height: self.max_height,
+        }
+    }
+
+    /// 数字的值已由 `LexerScanner` 计算
+    fn number(&self, token: &Token) -> Result<Literal, ParseError> {
+        token
+            .literal
+            .as_deref()
+            .cloned()
+            .ok_or_else(|| self.error(Some(token), "Invalid number literal."))
+    }
+
+    fn error(&self, token: Option<&Token>, message: &str) -> ParseError {
+        let span = match token {
+            Some(token) => token.span,
+            None => self.tokens.last().map_or(Span::default(), |last| {
+                Span::new(last.span.end, last.span.end)
+            }),
+        };
+        ParseError::new(token.cloned(), span, message)
+    }
+
+    fn match_token(&self, token_types: &[TokenType]) -> Option<Token> {
+        match self.peek() {
+            Some(token) if token_types.contains(&token.token_type) => self.advance().cloned(),
+            _ => None,
+        }
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        matches!(self.peek(), Some(token) if token.token_type == token_type)
+    }
+
+    fn check_next(&self, token_type: TokenType) -> bool {
+        matches!(self.tokens.get(self.current.get() + 1), Some(token) if token.token_type == token_type)
+    }
+
+    fn consume(&self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
+        match self.peek() {
+            Some(token) if token.token_type == token_type => Ok(self.advance().unwrap()),
+            token => Err(self.error(token, message)),
+        }
+    }
+
+    fn advance(&self) -> Option<&Token> {
+        let token = self.tokens.get(self.current.get());
+        if token.is_some() {
+            self.current.set(self.current.get() + 1);
+        }
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.current.get())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::scanner::LexerScanner;
+    use super::*;
+
+    fn parse(source: &str) -> Result<Expr, ParseError> {
+        let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+        Parser::new(tokens).parse()
+    }
+
+    fn operator(expr: &Expr) -> TokenType {
+        match expr {
+            Expr::Binary { operator, .. }
+            | Expr::Logical { operator, .. }
+            | Expr::Unary { operator, .. } => operator.token_type,
+            _ => panic!("Expect operator expression, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn parse_spans() {
+        let expr = parse("1 + -(2 * 3)").unwrap();
+        assert_eq!(Span::new(0, 12), expr.span());
+        let Expr::Binary { right, .. } = expr else {
+            panic!("Expect binary expression.");
+        };
+        assert_eq!(Span::new(4, 12), right.span());
+    }
+
+    #[test]
+    fn parse_precedence() {
+        let expr = parse("1 + 2 * 3").unwrap();
+        assert_eq!(TokenType::Plus, operator(&expr));
+        let Expr::Binary { left, right, .. } = expr else {
+            panic!("Expect binary expression.");
+        };
+        assert!(matches!(
+            *left,
+            Expr::Literal {
+                value: Literal::Integer(1),
+                ..
+            }
+        ));
+        assert_eq!(TokenType::Star, operator(&right));
+    }
+
+    #[test]
+    fn parse_logical_precedence() {
+        let expr = parse("1 < 2 || 3 == 4 && !\"a\"").unwrap();
+        assert_eq!(TokenType::Or, operator(&expr));
+        let Expr::Logical { left, right, .. } = expr else {
+            panic!("Expect logical expression.");
+        };
+        assert_eq!(TokenType::Less, operator(&left));
+        assert_eq!(TokenType::And, operator(&right));
+    }
+
+    #[test]
+    fn parse_left_associative() {
+        let expr = parse("8 - 4 - 2").unwrap();
+        let Expr::Binary { left, right, .. } = expr else {
+            panic!("Expect binary expression.");
+        };
+        assert_eq!(TokenType::Minus, operator(&left));
+        assert!(matches!(
+            *right,
+            Expr::Literal {
+                value: Literal::Integer(2),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_keyword_literals() {
+        let literal = |source: &str| match parse(source).unwrap() {
+            Expr::Literal { value, .. } => value,
+            _ => panic!("Expect literal."),
+        };
+        assert_eq!(Literal::Bool(true), literal("true"));
+        assert_eq!(Literal::Bool(false), literal("false"));
+        assert_eq!(Literal::None, literal("nil"));
+    }
 
-            TokenType::BangEqual => Literal::Bool(left != right),
-            TokenType::EqualEqual => Literal::Bool(left == right),
-            TokenType::Greater => Literal::Bool(left > right),
-            TokenType::GreaterEqual => Literal::Bool(left >= right),
-            TokenType::Less => Literal::Bool(left < right),
-            TokenType::LessEqual => Literal::Bool(left <= right),
+    #[test]
+    fn parse_grouping_and_unary() {
+        let expr = parse("-(1.5 + 2)").unwrap();
+        assert_eq!(TokenType::Minus, operator(&expr));
+        let Expr::Unary { right, .. } = expr else {
+            panic!("Expect unary expression.");
+        };
+        let Expr::Grouping { expr, span } = *right else {
+            panic!("Expect grouping expression.");
+        };
+        let Expr::Binary { left, .. } = *expr else {
+            panic!("Expect binary expression.");
+        };
+        assert_eq!(Span::new(1, 10), span);
+        assert!(matches!(
+            *left,
+            Expr::Literal {
+                value: Literal::Float(val),
+                ..
+            } if val == 1.5
+        ));
+    }
 
-            _ => panic!("Expression error."),
+    #[test]
+    fn parse_program() {
+        let tokens = LexerScanner::new("let a = 1; let b; a = b = a + 1;".to_string())
+            .scan()
+            .unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        assert_eq!(3, statements.len());
+        assert!(matches!(
+            &statements[0],
+            Stmt::Let { name, initializer: Some(_) } if name.lexeme.as_deref() == Some("a")
+        ));
+        assert!(matches!(
+            &statements[1],
+            Stmt::Let {
+                initializer: None,
+                ..
+            }
+        ));
+        let Stmt::Expression(Expr::Assign { name, value }) = &statements[2] else {
+            panic!("Expect assignment.");
         };
-        Literal::None
+        assert_eq!(Some("a"), name.lexeme.as_deref());
+        assert!(matches!(**value, Expr::Assign { .. }));
     }
 
-    fn visit_grouping(&self, expr: &Box<Expr>) -> Self::ReturnType {
-        self.evaluate(expr)
+    #[test]
+    fn parse_print_and_block() {
+        let tokens = LexerScanner::new("print 1; { let a = 2; { print a; } }".to_string())
+            .scan()
+            .unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        assert_eq!(2, statements.len());
+        assert!(matches!(&statements[0], Stmt::Print(_)));
+        let Stmt::Block(block) = &statements[1] else {
+            panic!("Expect block.");
+        };
+        assert!(matches!(&block[0], Stmt::Let { .. }));
+        assert!(matches!(&block[1], Stmt::Block(inner) if inner.len() == 1));
     }
 
-    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
-        literal.clone()
+    #[test]
+    fn parse_control_flow() {
+        let tokens = LexerScanner::new(
+            "if (a) print 1; else if (b) print 2; for (;;) { while (c) break; continue; }"
+                .to_string(),
+        )
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::If {
+            else_branch: Some(else_branch),
+            ..
+        } = &statements[0]
+        else {
+            panic!("Expect if statement.");
+        };
+        assert!(matches!(
+            **else_branch,
+            Stmt::If {
+                else_branch: None,
+                ..
+            }
+        ));
+        let Stmt::For {
+            initializer: None,
+            condition: None,
+            increment: None,
+            body,
+        } = &statements[1]
+        else {
+            panic!("Expect for statement.");
+        };
+        let Stmt::Block(body) = &**body else {
+            panic!("Expect block.");
+        };
+        assert!(matches!(&body[0], Stmt::While { body, .. } if matches!(**body, Stmt::Break(_))));
+        assert!(matches!(&body[1], Stmt::Continue(_)));
     }
 
-    fn visit_unary(&self, operator: &Token, right: &Box<Expr>) -> Self::ReturnType {
-        let token_type = operator.token_type;
-        let literal = self.evaluate(right);
-        match token_type {
-            TokenType::Bang => !literal,
-            TokenType::Minus => -literal,
-            _ => panic!("Does not conform to unary operations"),
+    #[test]
+    fn parse_functions() {
+        let tokens =
+            LexerScanner::new("fn add(a, b) { return a + b; } add(1, 2)(3); fn () {};".to_string())
+                .scan()
+                .unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Function(declaration) = &statements[0] else {
+            panic!("Expect function declaration.");
+        };
+        assert_eq!(
+            Some("add"),
+            declaration
+                .name
+                .as_ref()
+                .and_then(|name| name.lexeme.as_deref())
+        );
+        assert_eq!(2, declaration.params.len());
+        assert!(matches!(
+            declaration.body[..],
+            [Stmt::Return { value: Some(_), .. }]
+        ));
+        assert_eq!(Span::new(0, 30), declaration.span);
+
+        let Stmt::Expression(call) = &statements[1] else {
+            panic!("Expect expression statement.");
+        };
+        assert_eq!(Span::new(31, 43), call.span());
+        let Expr::Call {
+            callee, arguments, ..
+        } = call
+        else {
+            panic!("Expect call.");
+        };
+        assert_eq!(1, arguments.len());
+        assert!(matches!(&**callee, Expr::Call { arguments, .. } if arguments.len() == 2));
+
+        assert!(
+            matches!(&statements[2], Stmt::Expression(Expr::Function(declaration)) if declaration.name.is_none())
+        );
+    }
+
+    #[test]
+    fn parse_collections() {
+        let tokens = LexerScanner::new(
+            "let m = {\"a\": [1, 2], 3: {}}; m[\"a\"][0] = 1; for (k in m) { print k; }"
+                .to_string(),
+        )
+        .scan()
+        .unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        let Stmt::Let {
+            initializer: Some(Expr::Map { entries, span }),
+            ..
+        } = &statements[0]
+        else {
+            panic!("Expect map.");
+        };
+        assert_eq!(Span::new(8, 28), *span);
+        assert_eq!(2, entries.len());
+        assert!(matches!(&entries[0].1, Expr::List { elements, .. } if elements.len() == 2));
+        assert!(matches!(&entries[1].1, Expr::Map { entries, .. } if entries.is_empty()));
+
+        let Stmt::Expression(assignment) = &statements[1] else {
+            panic!("Expect expression statement.");
+        };
+        let Expr::SetIndex { object, index, .. } = assignment else {
+            panic!("Expect index assignment.");
+        };
+        assert!(matches!(&**object, Expr::Index { .. }));
+        assert!(matches!(
+            &**index,
+            Expr::Literal {
+                value: Literal::Integer(0),
+                ..
+            }
+        ));
+        assert_eq!(Span::new(30, 43), assignment.span());
+
+        let Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        } = &statements[2]
+        else {
+            panic!("Expect for-in.");
+        };
+        assert_eq!(Some("k"), name.lexeme.as_deref());
+        assert!(matches!(iterable, Expr::Variable { .. }));
+        assert!(matches!(&**body, Stmt::Block(_)));
+    }
+
+    #[test]
+    fn parse_interpolation_context() {
+        let parse_program = |source: &str| {
+            let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+            Parser::new(tokens).parse_program()
+        };
+        assert!(parse_program(
+            "fn f() { while (true) { print \"${fn () { return 1; }()}\"; } }\
+             print \"${fn (x) { for (;;) { break; } return x; }(1)}\";"
+        )
+        .is_ok());
+        let error = parse_program("while (true) print \"${fn () { break; }}\";").unwrap_err();
+        assert_eq!("Must be inside a loop.", error.message);
+    }
+
+    #[test]
+    fn parse_invalid_program() {
+        let parse_program = |source: &str| {
+            let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+            Parser::new(tokens).parse_program().unwrap_err()
+        };
+        let error = parse_program("1 + 2 = 3;");
+        assert_eq!("Invalid assignment target.", error.message);
+        assert_eq!(Span::new(6, 7), error.span);
+
+        let error = parse_program("let = 1;");
+        assert_eq!("Expect variable name.", error.message);
+
+        let error = parse_program("let a = 1");
+        assert_eq!("Expect ';' after variable declaration.", error.message);
+
+        let error = parse_program("while (1) {} break;");
+        assert_eq!("Must be inside a loop.", error.message);
+        assert_eq!(Span::new(13, 18), error.span);
+
+        let error = parse_program("fn f(a b) {}");
+        assert_eq!("Expect ')' after parameters.", error.message);
+
+        let error = parse_program("return 1;");
+        assert_eq!("Can't return from top-level code.", error.message);
+
+        let error = parse_program("while (1) { fn f() { break; } }");
+        assert_eq!("Must be inside a loop.", error.message);
+
+        let error = parse_program("print \"a ${ } b\";");
+        assert_eq!("Expect expression inside '${}'.", error.message);
+        assert_eq!(Span::new(11, 12), error.span);
+
+        let error = parse_program("print \"${1 +}\";");
+        assert_eq!("Expect expression.", error.message);
+        assert_eq!(Span::new(12, 12), error.span);
+
+        let error = parse_program("let m = {1 2};");
+        assert_eq!("Expect ':' after map key.", error.message);
+
+        let error = parse_program("print [1, 2;");
+        assert_eq!("Expect ']' after list elements.", error.message);
+
+        let error = parse_program("xs[0;");
+        assert_eq!("Expect ']' after index.", error.message);
+
+        let error = parse_program("[1][0] + 1 = 2;");
+        assert_eq!("Invalid assignment target.", error.message);
+
+        let error = parse_program("{ print 1;");
+        assert_eq!("Expect '}' after block.", error.message);
+        assert_eq!(None, error.token);
+    }
+
+    #[test]
+    fn parse_nesting_limits() {
+        // 测试线程的栈只有 2MB，收紧上限
+        let parse = |source: &str| {
+            let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+            Parser::new(tokens)
+                .with_max_nesting(32)
+                .with_max_height(256)
+                .parse_program()
+        };
+        let parens = |depth: usize| format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+        // 最外层的表达式本身算一层
+        assert!(parse(&parens(31)).is_ok());
+        let error = parse(&parens(32)).unwrap_err();
+        assert_eq!("Too much nesting.", error.message);
+        assert_eq!(
+            "Too much nesting.",
+            parse(&parens(100_000)).unwrap_err().message
+        );
+        let blocks = format!("{}{}", "{".repeat(100), "}".repeat(100));
+        assert_eq!("Too much nesting.", parse(&blocks).unwrap_err().message);
+
+        // 运算符链不占用解析器的栈，但同样让语法树加深
+        let sum = |terms: usize| format!("{};", vec!["1"; terms].join(" + "));
+        assert!(parse(&sum(255)).is_ok());
+        let error = parse(&sum(100_000)).unwrap_err();
+        assert_eq!("Expression too complex.", error.message);
+        let calls = format!("f{};", "()".repeat(1_000));
+        assert_eq!(
+            "Expression too complex.",
+            parse(&calls).unwrap_err().message
+        );
+        let negations = format!("{}1;", "-".repeat(100_000));
+        assert_eq!(
+            "Expression too complex.",
+            parse(&negations).unwrap_err().message
+        );
+
+        // 插值中的表达式继承外层的嵌套层数
+        let interpolated = format!("\"${{{}}}\";", parens(31).trim_end_matches(';'));
+        assert_eq!(
+            "Too much nesting.",
+            parse(&interpolated).unwrap_err().message
+        );
+    }
+
+    #[test]
+    fn parse_long_chains() {
+        let source = |arms: usize| {
+            let mut source = "let x = 0; if (x == 0) print 0;".to_string();
+            for arm in 1..arms {
+                source.push_str(&format!(" else if (x == {}) print {};", arm, arm));
+            }
+            source + " else { print -1; }"
+        };
+        let tokens = LexerScanner::new(source(1_000)).scan().unwrap();
+        let statements = Parser::new(tokens).parse_program().unwrap();
+        // `else if` 不增加嵌套层数，链的长度只受语法树深度的限制
+        let mut branch = &statements[1];
+        let mut arms = 0;
+        while let Stmt::If { else_branch, .. } = branch {
+            arms += 1;
+            branch = else_branch.as_deref().unwrap();
         }
+        assert_eq!(1_000, arms);
+        assert!(matches!(branch, Stmt::Block(_)));
+        let tokens = LexerScanner::new(source(Parser::MAX_HEIGHT + 1))
+            .scan()
+            .unwrap();
+        let error = Parser::new(tokens).parse_program().unwrap_err();
+        assert_eq!("Expression too complex.", error.message);
+
+        assert!(parse(&vec!["1"; 300].join(" + ")).is_ok());
+        assert!(parse(&format!("{}1", "-".repeat(100))).is_ok());
+        assert!(parse(&format!("{}true", "!".repeat(100))).is_ok());
+    }
+
+    #[test]
+    fn parse_invalid_expression() {
+        let error = parse("(1 + 2").unwrap_err();
+        assert_eq!(None, error.token);
+        assert_eq!(Span::new(6, 6), error.span);
+        assert_eq!("Expect ')' after expression.", error.message);
+
+        let error = parse("1 + * 2").unwrap_err();
+        assert_eq!(TokenType::Star, error.token.unwrap().token_type);
+        assert_eq!(Span::new(4, 5), error.span);
+
+        let error = parse("1 2").unwrap_err();
+        assert_eq!("Expect end of expression.", error.message);
     }
 }
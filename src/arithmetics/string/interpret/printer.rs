@@ -0,0 +1,1014 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::expr::{Expr, ExprVisitor, InterpolationPart};
+use super::literal::Literal;
+use super::stmt::{FunctionDecl, Stmt, StmtVisitor};
+use super::token::{Comment, StringPart, Token, TokenType};
+
+/// 将语法树打印为完全加括号的 S 表达式，用于检查解析器的优先级，
+/// 如 `1 + 2 * 3` 打印为 `(+ 1 (* 2 3))`。源码中的括号不会单独出现。
+#[derive(Debug, Default)]
+pub struct AstPrinter;
+
+impl AstPrinter {
+    pub fn new() -> Self {
+        AstPrinter
+    }
+
+    pub fn print(&self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    /// 每条语句一行
+    pub fn print_program(&self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|statement| statement.accept(self) + "\n")
+            .collect()
+    }
+
+    fn parenthesize(&self, name: &str, parts: &[String]) -> String {
+        let mut text = format!("({}", name);
+        for part in parts {
+            text.push(' ');
+            text.push_str(part);
+        }
+        text.push(')');
+        text
+    }
+
+    fn function(&self, declaration: &FunctionDecl) -> String {
+        let mut parts = vec![format!("({})", names(&declaration.params).join(" "))];
+        parts.extend(
+            declaration
+                .body
+                .iter()
+                .map(|statement| statement.accept(self)),
+        );
+        match &declaration.name {
+            Some(name) => self.parenthesize(&format!("fn {}", lexeme(name)), &parts),
+            None => self.parenthesize("fn", &parts),
+        }
+    }
+}
+
+impl ExprVisitor for AstPrinter {
+    type ReturnType = String;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.parenthesize(
+            operator_symbol(operator),
+            &[left.accept(self), right.accept(self)],
+        )
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.visit_binary(left, operator, right)
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        expr.accept(self)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.parenthesize(operator_symbol(operator), &[right.accept(self)])
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        literal_source(literal)
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        lexeme(name).to_string()
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        self.parenthesize("=", &[lexeme(name).to_string(), value.accept(self)])
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        let mut parts = vec![callee.accept(self)];
+        parts.extend(arguments.iter().map(|argument| argument.accept(self)));
+        self.parenthesize("call", &parts)
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.function(declaration)
+    }
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        let parts: Vec<String> = parts
+            .iter()
+            .map(|part| match part {
+                InterpolationPart::Text(text) => format!("\"{}\"", escape(text)),
+                InterpolationPart::Expr(expr) => expr.accept(self),
+            })
+            .collect();
+        self.parenthesize("interpolate", &parts)
+    }
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        let elements: Vec<String> = elements
+            .iter()
+            .map(|element| element.accept(self))
+            .collect();
+        self.parenthesize("list", &elements)
+    }
+
+    /// 每个键值对打印为 `(key value)`
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        let entries: Vec<String> = entries
+            .iter()
+            .map(|(key, value)| format!("({} {})", key.accept(self), value.accept(self)))
+            .collect();
+        self.parenthesize("map", &entries)
+    }
+
+    fn visit_index(&self, object: &Expr, _bracket: &Token, index: &Expr) -> Self::ReturnType {
+        self.parenthesize("index", &[object.accept(self), index.accept(self)])
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        self.parenthesize(
+            "=",
+            &[self.visit_index(object, bracket, index), value.accept(self)],
+        )
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    type ReturnType = String;
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        self.parenthesize("expr", &[expr.accept(self)])
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        self.parenthesize("print", &[expr.accept(self)])
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        let mut parts = vec![lexeme(name).to_string()];
+        parts.extend(initializer.map(|initializer| initializer.accept(self)));
+        self.parenthesize("let", &parts)
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        let parts: Vec<String> = statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect();
+        self.parenthesize("block", &parts)
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        let mut parts = vec![condition.accept(self), then_branch.accept(self)];
+        parts.extend(else_branch.map(|else_branch| else_branch.accept(self)));
+        self.parenthesize("if", &parts)
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        self.parenthesize("while", &[condition.accept(self), body.accept(self)])
+    }
+
+    /// 省略的部分打印为 `_`
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        let omitted = || "_".to_string();
+        self.parenthesize(
+            "for",
+            &[
+                initializer.map_or_else(omitted, |initializer| initializer.accept(self)),
+                condition.map_or_else(omitted, |condition| condition.accept(self)),
+                increment.map_or_else(omitted, |increment| increment.accept(self)),
+                body.accept(self),
+            ],
+        )
+    }
+
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        self.parenthesize(
+            "for-in",
+            &[
+                lexeme(name).to_string(),
+                iterable.accept(self),
+                body.accept(self),
+            ],
+        )
+    }
+
+    fn visit_break(&self, _keyword: &Token) -> Self::ReturnType {
+        "(break)".to_string()
+    }
+
+    fn visit_continue(&self, _keyword: &Token) -> Self::ReturnType {
+        "(continue)".to_string()
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.function(declaration)
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        let parts: Vec<String> = value.into_iter().map(|value| value.accept(self)).collect();
+        self.parenthesize("return", &parts)
+    }
+}
+
+/// 规范格式化：只保留必要的括号，运算符两侧各一个空格，每条语句一行，缩进四个空格。
+///
+/// 格式化结果重新解析后得到与原语法树相同的 `AstPrinter` 输出。
+/// 注释由 `with_comments` 提供，否则不会保留。
+#[derive(Debug, Default)]
+pub struct Formatter {
+    /// 当前的缩进层数
+    indent: Cell<usize>,
+    /// 尚未写出的注释，按位置排序
+    comments: RefCell<VecDeque<Comment>>,
+}
+
+const INDENT: &str = "    ";
+
+impl Formatter {
+    pub fn new() -> Self {
+        Formatter::default()
+    }
+
+    /// 写回 token 上（包括插值片段中）的注释与扫描结束时剩余的 `trailing`
+    ///
+    /// 语法树不记录语句的位置，注释各占一行，写在其后的第一条语句之前；
+    /// 语句中间的注释移到语句之前或之后，块末尾的注释移到块之后（函数体除外）。
+    pub fn with_comments(self, tokens: &[Token], trailing: &[Comment]) -> Self {
+        let mut comments = Vec::new();
+        collect_comments(tokens, &mut comments);
+        comments.extend_from_slice(trailing);
+        comments.sort_by_key(|comment| comment.span.start);
+        *self.comments.borrow_mut() = comments.into();
+        self
+    }
+
+    pub fn format_expr(&self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    /// 每条语句一行，以换行结尾
+    pub fn format(&self, statements: &[Stmt]) -> String {
+        let mut text = String::new();
+        for statement in statements {
+            text.push_str(&self.comments_before(statement_start(statement), 0));
+            text.push_str(&statement.accept(self));
+            text.push('\n');
+        }
+        text.push_str(&self.comments_before(Some(usize::MAX), 0));
+        text
+    }
+
+    /// 起始位置在 `offset` 之前的注释，每条一行
+    fn comments_before(&self, offset: Option<usize>, indent: usize) -> String {
+        let mut text = String::new();
+        let Some(offset) = offset else {
+            return text;
+        };
+        let mut comments = self.comments.borrow_mut();
+        while let Some(comment) = comments.front() {
+            if comment.span.start >= offset {
+                break;
+            }
+            text.push_str(&INDENT.repeat(indent));
+            text.push_str(&comment.text);
+            text.push('\n');
+            comments.pop_front();
+        }
+        text
+    }
+
+    /// 优先级低于 `min` 的子表达式需要加括号
+    fn operand(&self, expr: &Expr, min: Precedence) -> String {
+        let text = expr.accept(self);
+        if precedence(expr) < min {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+
+    fn binary(
+        &self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+        precedence: Precedence,
+    ) -> String {
+        // 二元运算符都是左结合的
+        format!(
+            "{} {} {}",
+            self.operand(left, precedence),
+            operator_symbol(operator),
+            self.operand(right, precedence.next())
+        )
+    }
+
+    /// `{`、缩进后的语句与 `}`，空块为 `{}`；已知右花括号的位置 `end` 时，
+    /// 块末尾的注释留在块内
+    fn block(&self, statements: &[Stmt], end: Option<usize>) -> String {
+        let indent = self.indent.get();
+        self.indent.set(indent + 1);
+        let mut body = String::new();
+        for statement in statements {
+            body.push_str(&self.comments_before(statement_start(statement), indent + 1));
+            body.push_str(&INDENT.repeat(indent + 1));
+            body.push_str(&statement.accept(self));
+            body.push('\n');
+        }
+        body.push_str(&self.comments_before(end, indent + 1));
+        self.indent.set(indent);
+        if body.is_empty() {
+            return "{}".to_string();
+        }
+        format!("{{\n{}{}}}", body, INDENT.repeat(indent))
+    }
+
+    fn function(&self, declaration: &FunctionDecl) -> String {
+        // 匿名函数写作 `fn (params)`
+        format!(
+            "fn {}({}) {}",
+            declaration.name.as_ref().map_or("", lexeme),
+            names(&declaration.params).join(", "),
+            self.block(&declaration.body, Some(declaration.span.end))
+        )
+    }
+}
+
+impl ExprVisitor for Formatter {
+    type ReturnType = String;
+
+    fn visit_binary(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.binary(
+            left,
+            operator,
+            right,
+            binary_precedence(operator.token_type),
+        )
+    }
+
+    fn visit_logical(&self, left: &Expr, operator: &Token, right: &Expr) -> Self::ReturnType {
+        self.binary(
+            left,
+            operator,
+            right,
+            binary_precedence(operator.token_type),
+        )
+    }
+
+    fn visit_grouping(&self, expr: &Expr) -> Self::ReturnType {
+        expr.accept(self)
+    }
+
+    fn visit_unary(&self, operator: &Token, right: &Expr) -> Self::ReturnType {
+        format!(
+            "{}{}",
+            operator_symbol(operator),
+            self.operand(right, Precedence::Unary)
+        )
+    }
+
+    fn visit_literal(&self, literal: &Literal) -> Self::ReturnType {
+        literal_source(literal)
+    }
+
+    fn visit_variable(&self, name: &Token) -> Self::ReturnType {
+        lexeme(name).to_string()
+    }
+
+    fn visit_assign(&self, name: &Token, value: &Expr) -> Self::ReturnType {
+        format!(
+            "{} = {}",
+            lexeme(name),
+            self.operand(value, Precedence::Assignment)
+        )
+    }
+
+    fn visit_call(&self, callee: &Expr, _paren: &Token, arguments: &[Expr]) -> Self::ReturnType {
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|argument| self.operand(argument, Precedence::Assignment))
+            .collect();
+        format!(
+            "{}({})",
+            self.operand(callee, Precedence::Call),
+            arguments.join(", ")
+        )
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.function(declaration)
+    }
+
+    fn visit_interpolation(&self, parts: &[InterpolationPart]) -> Self::ReturnType {
+        let mut text = "\"".to_string();
+        for part in parts {
+            match part {
+                InterpolationPart::Text(value) => text.push_str(&escape(value)),
+                InterpolationPart::Expr(expr) => {
+                    text.push_str("${");
+                    text.push_str(&expr.accept(self));
+                    text.push('}');
+                }
+            }
+        }
+        text.push('"');
+        text
+    }
+
+    fn visit_list(&self, elements: &[Expr]) -> Self::ReturnType {
+        let elements: Vec<String> = elements
+            .iter()
+            .map(|element| self.operand(element, Precedence::Assignment))
+            .collect();
+        format!("[{}]", elements.join(", "))
+    }
+
+    fn visit_map(&self, entries: &[(Expr, Expr)]) -> Self::ReturnType {
+        let entries: Vec<String> = entries
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "{}: {}",
+                    self.operand(key, Precedence::Assignment),
+                    self.operand(value, Precedence::Assignment)
+                )
+            })
+            .collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn visit_index(&self, object: &Expr, _bracket: &Token, index: &Expr) -> Self::ReturnType {
+        format!(
+            "{}[{}]",
+            self.operand(object, Precedence::Call),
+            index.accept(self)
+        )
+    }
+
+    fn visit_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Self::ReturnType {
+        format!(
+            "{} = {}",
+            self.visit_index(object, bracket, index),
+            self.operand(value, Precedence::Assignment)
+        )
+    }
+}
+
+impl StmtVisitor for Formatter {
+    type ReturnType = String;
+
+    /// 语句开头的 `{` 会被解析为块，以映射开头的表达式需要加括号
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType {
+        if starts_with_map(expr) {
+            format!("({});", expr.accept(self))
+        } else {
+            format!("{};", expr.accept(self))
+        }
+    }
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType {
+        format!("print {};", expr.accept(self))
+    }
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType {
+        match initializer {
+            Some(initializer) => format!("let {} = {};", lexeme(name), initializer.accept(self)),
+            None => format!("let {};", lexeme(name)),
+        }
+    }
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType {
+        self.block(statements, None)
+    }
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType {
+        let mut text = format!(
+            "if ({}) {}",
+            condition.accept(self),
+            then_branch.accept(self)
+        );
+        if let Some(else_branch) = else_branch {
+            text.push_str(" else ");
+            text.push_str(&else_branch.accept(self));
+        }
+        text
+    }
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType {
+        format!("while ({}) {}", condition.accept(self), body.accept(self))
+    }
+
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType {
+        let mut text = "for (".to_string();
+        text.push_str(&initializer.map_or(";".to_string(), |initializer| initializer.accept(self)));
+        if let Some(condition) = condition {
+            text.push(' ');
+            text.push_str(&condition.accept(self));
+        }
+        text.push(';');
+        if let Some(increment) = increment {
+            text.push(' ');
+            text.push_str(&increment.accept(self));
+        }
+        text.push_str(") ");
+        text.push_str(&body.accept(self));
+        text
+    }
+
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType {
+        format!(
+            "for ({} in {}) {}",
+            lexeme(name),
+            iterable.accept(self),
+            body.accept(self)
+        )
+    }
+
+    fn visit_break(&self, _keyword: &Token) -> Self::ReturnType {
+        "break;".to_string()
+    }
+
+    fn visit_continue(&self, _keyword: &Token) -> Self::ReturnType {
+        "continue;".to_string()
+    }
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType {
+        self.function(declaration)
+    }
+
+    fn visit_return(&self, _keyword: &Token, value: Option<&Expr>) -> Self::ReturnType {
+        match value {
+            Some(value) => format!("return {};", value.accept(self)),
+            None => "return;".to_string(),
+        }
+    }
+}
+
+/// 由低到高的优先级，与 `Parser` 的语法规则一一对应
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+fn binary_precedence(token_type: TokenType) -> Precedence {
+    match token_type {
+        TokenType::Or => Precedence::Or,
+        TokenType::And => Precedence::And,
+        TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            Precedence::Comparison
+        }
+        TokenType::Plus | TokenType::Minus => Precedence::Term,
+        _ => Precedence::Factor,
+    }
+}
+
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Assign { .. } | Expr::SetIndex { .. } => Precedence::Assignment,
+        Expr::Binary { operator, .. } | Expr::Logical { operator, .. } => {
+            binary_precedence(operator.token_type)
+        }
+        Expr::Unary { .. } => Precedence::Unary,
+        Expr::Call { .. } | Expr::Index { .. } => Precedence::Call,
+        Expr::Grouping { expr, .. } => precedence(expr),
+        // 常量折叠产生的有理数写作除法，负数写作取负
+        Expr::Literal {
+            value: Literal::Rational(value),
+            ..
+        } if !value.is_integer() => Precedence::Factor,
+        Expr::Literal { value, .. } if is_negative(value) => Precedence::Unary,
+        Expr::Literal { .. }
+        | Expr::Variable { .. }
+        | Expr::Function(_)
+        | Expr::Interpolation { .. }
+        | Expr::List { .. }
+        | Expr::Map { .. } => Precedence::Primary,
+    }
+}
+
+/// 语句开头已知的位置；语法树不记录 `print`、`{` 等的位置，
+/// 块返回 `None`，让其中的注释留在块内
+fn statement_start(statement: &Stmt) -> Option<usize> {
+    match statement {
+        Stmt::Expression(expr) | Stmt::Print(expr) => Some(expr.span().start),
+        Stmt::If { condition, .. } | Stmt::While { condition, .. } => Some(condition.span().start),
+        Stmt::For {
+            initializer,
+            condition,
+            increment,
+            ..
+        } => initializer
+            .as_deref()
+            .and_then(statement_start)
+            .or_else(|| condition.as_ref().map(|condition| condition.span().start))
+            .or_else(|| increment.as_ref().map(|increment| increment.span().start)),
+        Stmt::Let { name, .. } | Stmt::ForIn { name, .. } => Some(name.span.start),
+        Stmt::Break(keyword) | Stmt::Continue(keyword) | Stmt::Return { keyword, .. } => {
+            Some(keyword.span.start)
+        }
+        Stmt::Function(declaration) => Some(declaration.span.start),
+        Stmt::Block(_) => None,
+    }
+}
+
+/// 收集 token 与插值片段上的注释
+fn collect_comments(tokens: &[Token], comments: &mut Vec<Comment>) {
+    for token in tokens {
+        comments.extend_from_slice(&token.comments);
+        for part in &token.parts {
+            if let StringPart::Expr { tokens, .. } = part {
+                collect_comments(tokens, comments);
+            }
+        }
+    }
+}
+
+/// 格式化结果的第一个字符来自映射字面量
+fn starts_with_map(expr: &Expr) -> bool {
+    match expr {
+        Expr::Map { .. } => true,
+        Expr::Binary { left, .. } | Expr::Logical { left, .. } => starts_with_map(left),
+        Expr::Call { callee: first, .. }
+        | Expr::Index { object: first, .. }
+        | Expr::SetIndex { object: first, .. } => starts_with_map(first),
+        // 加括号的子表达式可能被去掉括号
+        Expr::Grouping { expr, .. } => starts_with_map(expr),
+        _ => false,
+    }
+}
+
+fn lexeme(token: &Token) -> &str {
+    token.lexeme.as_deref().unwrap_or_default()
+}
+
+fn operator_symbol(operator: &Token) -> &'static str {
+    operator.token_type.symbol().unwrap_or_default()
+}
+
+fn names(tokens: &[Token]) -> Vec<&str> {
+    tokens.iter().map(lexeme).collect()
+}
+
+/// 字面量的源码形式，字符串加引号并转义
+///
+/// 溢出的浮点数字面量（如 `1e400`）解析为无穷大，写作 `1e999`；
+/// NaN 与有理数只能由常量折叠产生，写作结果相同的表达式。
+fn literal_source(literal: &Literal) -> String {
+    match literal {
+        Literal::String(value) => format!("\"{}\"", escape(value)),
+        Literal::Rational(value) if !value.is_integer() => {
+            format!("{} / {}", value.numerator(), value.denominator())
+        }
+        Literal::Float(value) if value.is_nan() => "(1e999 - 1e999)".to_string(),
+        Literal::Float(value) if value.is_infinite() => {
+            format!("{}1e999", if *value < 0.0 { "-" } else { "" })
+        }
+        _ => literal.to_string(),
+    }
+}
+
+fn is_negative(literal: &Literal) -> bool {
+    match literal {
+        Literal::Integer(value) => *value < 0,
+        Literal::BigInt(value) => value.is_negative(),
+        Literal::Rational(value) => value.numerator().is_negative(),
+        Literal::Float(value) => value.is_sign_negative() && !value.is_nan(),
+        _ => false,
+    }
+}
+
+/// 转义后可以原样放回字符串字面量中
+fn escape(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            '\0' => text.push_str("\\0"),
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '$' if chars.peek() == Some(&'{') => text.push_str("\\$"),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::folder::ConstantFolder;
+    use super::super::interpreter::Interpreter;
+    use super::super::literal::NumericMode;
+    use super::super::parser::Parser;
+    use super::super::scanner::LexerScanner;
+    use super::super::testing::{parse_expr, parse_program, tree_walk};
+    use super::super::token::Span;
+    use super::*;
+
+    fn sexpr(source: &str) -> String {
+        AstPrinter::new().print(&parse_expr(source))
+    }
+
+    fn format(source: &str) -> String {
+        Formatter::new().format(&parse_program(source))
+    }
+
+    fn format_with_comments(source: &str) -> String {
+        let scanner = LexerScanner::new(source.to_string()).with_comments();
+        let tokens = scanner.scan().unwrap();
+        let formatter = Formatter::new().with_comments(&tokens, &scanner.trailing_comments());
+        formatter.format(&Parser::new(tokens).parse_program().unwrap())
+    }
+
+    /// 格式化后重新解析得到相同的语法树，且格式化是幂等的
+    fn assert_round_trip(source: &str) {
+        let printer = AstPrinter::new();
+        let formatted = format(source);
+        assert_eq!(
+            printer.print_program(&parse_program(source)),
+            printer.print_program(&parse_program(&formatted)),
+            "{}",
+            formatted
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn print_sexpr() {
+        assert_eq!(sexpr("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(sexpr("(1 + 2) * -3"), "(* (+ 1 2) (- 3))");
+        assert_eq!(
+            sexpr("a = b = c || d && !e"),
+            "(= a (= b (|| c (&& d (! e)))))"
+        );
+        assert_eq!(sexpr("f(1, \"x\")(2)"), "(call (call f 1 \"x\") 2)");
+        assert_eq!(sexpr("\"a ${b} c\""), "(interpolate \"a \" b \" c\")");
+        assert_eq!(sexpr("fn (x) { return x; }"), "(fn (x) (return x))");
+        assert_eq!(sexpr("1.5 + nil"), "(+ 1.5 nil)");
+        assert_eq!(
+            sexpr("[1, {\"a\": []}][0] = m[k]"),
+            "(= (index (list 1 (map (\"a\" (list)))) 0) (index m k))"
+        );
+    }
+
+    #[test]
+    fn print_program_sexpr() {
+        let statements =
+            parse_program("let a = 1; for (;a < 3;) { a = a + 1; } if (a) print a; else a = 0;");
+        assert_eq!(
+            AstPrinter::new().print_program(&statements),
+            "(let a 1)\n\
+             (for _ (< a 3) _ (block (expr (= a (+ a 1)))))\n\
+             (if a (print a) (expr (= a 0)))\n"
+        );
+    }
+
+    #[test]
+    fn format_minimal_parentheses() {
+        let cases = [
+            ("(1 + 2) * 3;", "(1 + 2) * 3;\n"),
+            ("1 + (2 * 3);", "1 + 2 * 3;\n"),
+            ("(1 - 2) - 3;", "1 - 2 - 3;\n"),
+            ("1 - (2 - 3);", "1 - (2 - 3);\n"),
+            ("-(-1);", "--1;\n"),
+            ("-(a + b);", "-(a + b);\n"),
+            ("a = (b = c);", "a = b = c;\n"),
+            ("(a || b) && c;", "(a || b) && c;\n"),
+            ("a || (b && c);", "a || b && c;\n"),
+            ("1 + (a = 2);", "1 + (a = 2);\n"),
+            ("(f)((1));", "f(1);\n"),
+            ("(a < b) == (c < d);", "a < b == c < d;\n"),
+            ("a < (b < c);", "a < (b < c);\n"),
+            ("(xs)[(1 + 2)];", "xs[1 + 2];\n"),
+            ("(a = b)[0];", "(a = b)[0];\n"),
+            ("({\"a\": 1})[\"a\"];", "({\"a\": 1}[\"a\"]);\n"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(format(source), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn format_statements() {
+        let source = "fn fib(n){if(n<2)return n;else{return fib(n-1)+fib(n-2);}}\
+            let f=fn(a,b){};for(let i=0;i<3;i=i+1){while(true){break;}continue;}\
+            { let x; print \"v=${ x }\\n\\\"q\\\"\"; } for(;;){}";
+        assert_eq!(
+            format(source),
+            "fn fib(n) {\n\
+             \x20   if (n < 2) return n; else {\n\
+             \x20       return fib(n - 1) + fib(n - 2);\n\
+             \x20   }\n\
+             }\n\
+             let f = fn (a, b) {};\n\
+             for (let i = 0; i < 3; i = i + 1) {\n\
+             \x20   while (true) {\n\
+             \x20       break;\n\
+             \x20   }\n\
+             \x20   continue;\n\
+             }\n\
+             {\n\
+             \x20   let x;\n\
+             \x20   print \"v=${x}\\n\\\"q\\\"\";\n\
+             }\n\
+             for (;;) {}\n"
+        );
+    }
+
+    #[test]
+    fn format_round_trip() {
+        assert_round_trip(
+            "let a = 1_000; let b = 0x1f * (2.5e3 - -a) / ((a));\n\
+             fn counter() { let n = 0; return fn () { n = n + 1; return n; }; }\n\
+             let next = counter(); print next() + next();\n\
+             if (a > b || !(a == b) && nil) { print \"${a + b} \\${x} \\\\ $\"; }\n\
+             else if (false) print 1; else { }\n\
+             for (a = 0; a < 10; a = a + 1) if (a == 5) break; else continue;\n\
+             while (a) { a = a - 1; { let c = fn (x) { return \"${\"${x}\"}\"; }(a); } }\n\
+             fn (x) { return x; }(1)(2);\n\
+             fn nothing() { return; }",
+        );
+        assert_round_trip("print 1e20 + 0.1 + 1.0 + \"\\t\\r\\0\";");
+        assert_round_trip(
+            "let m = {\"k\": [1, [2, 3]], 1.5: {}}; m[\"k\"][1][0] = m;\n\
+             for (x in m[\"k\"]) for (y in [x, []]) print y;",
+        );
+        assert_round_trip("let nested = \"outer ${\"inner ${1 + 2}\"} done\";");
+        // 插值中的字符串字面量不能当作文本写回
+        assert_eq!("print \"${\"a\"}\";\n", format("print \"${\"a\"}\";"));
+        assert_eq!(
+            "print \"${b}${\"$\" + \"{\"}\";\n",
+            format("print \"${b}${\"$\" + \"{\"}\";")
+        );
+        assert_round_trip("print \"${b}${\"a\"}c\";");
+    }
+
+    #[test]
+    fn format_folded_constants() {
+        // 折叠后的语法树格式化再解析，运行结果不变
+        let programs = [
+            (
+                "let x = 3; print x / (1 / 3);",
+                "let x = 3;\nprint x / (1 / 3);\n",
+            ),
+            (
+                "let x = 3; print -(1 / 3) * x;",
+                "let x = 3;\nprint -1 / 3 * x;\n",
+            ),
+            ("print (1 / 3)(1);", "print (1 / 3)(1);\n"),
+            ("print (-3)[0];", "print (-3)[0];\n"),
+            (
+                "let x = 2; print -(0 - 3)[x];",
+                "let x = 2;\nprint -(-3)[x];\n",
+            ),
+            (
+                "let x = 1; print x - -2.5 * x;",
+                "let x = 1;\nprint x - -2.5 * x;\n",
+            ),
+        ];
+        for (source, expected) in programs {
+            let statements = parse_program(source);
+            let folded = ConstantFolder::new()
+                .with_mode(NumericMode::Exact)
+                .fold_program(&statements);
+            let formatted = Formatter::new().format(&folded);
+            assert_eq!(expected, formatted, "{}", source);
+            let (output, result) = tree_walk(&statements, NumericMode::Exact);
+            let (reparsed, reparsed_result) =
+                tree_walk(&parse_program(&formatted), NumericMode::Exact);
+            assert_eq!(output, reparsed, "{}", source);
+            assert_eq!(
+                result.map_err(|error| error.kind),
+                reparsed_result.map_err(|error| error.kind)
+            );
+        }
+    }
+
+    #[test]
+    fn format_non_finite_floats() {
+        // `inf` 会被当作变量名
+        assert_eq!("print 1e999;\n", format("print 1e400;"));
+        assert_round_trip("print 1e400 + \"${-1e400}\";");
+
+        let nan = Expr::Literal {
+            value: Literal::Float(f64::NAN),
+            span: Span::default(),
+        };
+        let formatted = Formatter::new().format_expr(&nan);
+        let value = Interpreter::new()
+            .evaluate(&parse_expr(&formatted))
+            .unwrap();
+        assert!(matches!(value, Literal::Float(value) if value.is_nan()));
+        let negative = Expr::Literal {
+            value: Literal::Float(f64::NEG_INFINITY),
+            span: Span::default(),
+        };
+        let formatted = Formatter::new().format_expr(&negative);
+        assert_eq!(
+            Literal::Float(f64::NEG_INFINITY),
+            Interpreter::new()
+                .evaluate(&parse_expr(&formatted))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn format_comments() {
+        assert_eq!(
+            "// c\nprint 1;\n/* k */\n",
+            format_with_comments("// c\nprint 1; /* k */")
+        );
+        // 不提供注释时不保留
+        assert_eq!("print 1;\n", format("// c\nprint 1; /* k */"));
+
+        let source = "/* head */ fn f(a /* p */) { // body\n\
+                      let x = a; if (x) { /* then */ print \"${x /* in */}\"; } /* before brace */ }\n\
+                      // tail";
+        // 语句中间的注释移到下一条语句之前
+        let expected = "/* head */\n\
+                        fn f(a) {\n\
+                        \x20   /* p */\n\
+                        \x20   // body\n\
+                        \x20   let x = a;\n\
+                        \x20   if (x) {\n\
+                        \x20       /* then */\n\
+                        \x20       print \"${x}\";\n\
+                        \x20   }\n\
+                        \x20   /* in */\n\
+                        \x20   /* before brace */\n\
+                        }\n\
+                        // tail\n";
+        let formatted = format_with_comments(source);
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format_with_comments(&formatted));
+        assert_eq!(format(source), format(&formatted));
+    }
+}
@@ -0,0 +1,159 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use super::bigint::BigInt;
+
+/// 精确有理数，始终保持最简形式且分母为正
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Rational {
+    /// 分母为 0 时 panic
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Self {
+        assert!(!denominator.is_zero(), "attempt to divide by zero");
+        let (numerator, denominator) = if denominator.is_negative() {
+            (-numerator, -denominator)
+        } else {
+            (numerator, denominator)
+        };
+        let gcd = numerator.gcd(&denominator);
+        if gcd == BigInt::from(1) {
+            return Rational {
+                numerator,
+                denominator,
+            };
+        }
+        Rational {
+            numerator: &numerator / &gcd,
+            denominator: &denominator / &gcd,
+        }
+    }
+
+    pub fn from_integer(value: BigInt) -> Self {
+        Rational {
+            numerator: value,
+            denominator: BigInt::from(1),
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.numerator * &other.denominator).cmp(&(&other.numerator * &self.denominator))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+    fn add(self, rhs: Self) -> Rational {
+        Rational::new(
+            &(&self.numerator * &rhs.denominator) + &(&rhs.numerator * &self.denominator),
+            &self.denominator * &rhs.denominator,
+        )
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+    fn sub(self, rhs: Self) -> Rational {
+        self + &-rhs
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+    fn mul(self, rhs: Self) -> Rational {
+        Rational::new(
+            &self.numerator * &rhs.numerator,
+            &self.denominator * &rhs.denominator,
+        )
+    }
+}
+
+impl Div for &Rational {
+    type Output = Rational;
+    fn div(self, rhs: Self) -> Rational {
+        Rational::new(
+            &self.numerator * &rhs.denominator,
+            &self.denominator * &rhs.numerator,
+        )
+    }
+}
+
+impl Neg for &Rational {
+    type Output = Rational;
+    fn neg(self) -> Rational {
+        Rational {
+            numerator: -&self.numerator,
+            denominator: self.denominator.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ratio(numerator: i64, denominator: i64) -> Rational {
+        Rational::new(BigInt::from(numerator), BigInt::from(denominator))
+    }
+
+    #[test]
+    fn normalized() {
+        assert_eq!("-2/3", ratio(4, -6).to_string());
+        assert_eq!("5", ratio(10, 2).to_string());
+        assert!(ratio(10, 2).is_integer());
+        assert_eq!(ratio(0, 5), ratio(0, -7));
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(ratio(1, 2), &ratio(1, 3) + &ratio(1, 6));
+        assert_eq!(ratio(1, 6), &ratio(1, 2) - &ratio(1, 3));
+        assert_eq!(ratio(1, 3), &ratio(2, 3) * &ratio(1, 2));
+        assert_eq!(ratio(-4, 3), &ratio(2, 3) / &ratio(-1, 2));
+        assert!(ratio(1, 3) < ratio(1, 2));
+        assert!(ratio(-1, 2) < ratio(-1, 3));
+        assert_eq!(0.25, ratio(1, 4).to_f64());
+    }
+}
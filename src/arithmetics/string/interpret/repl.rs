@@ -0,0 +1,384 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use super::checker::{Type, TypeChecker};
+use super::compiler::Compiler;
+use super::diagnostics::{Diagnostic, SourceMap};
+use super::expr::Expr;
+use super::interpreter::Interpreter;
+use super::literal::Literal;
+use super::parser::Parser;
+use super::printer::AstPrinter;
+use super::scanner::{LexErrorKind, LexerScanner};
+use super::stmt::Stmt;
+use super::token::TokenType;
+
+const HELP: &str = "\
+:tokens <code>    show the tokens of <code>
+:ast <code>       show the syntax tree of <code>
+:bytecode <code>  disassemble the compiled <code>
+:type <code>      check <code> and show the type of an expression
+:env              list the global variables
+:reset            clear all variables
+:help             show this message
+:quit             exit
+";
+
+/// 诊断信息中使用的源文件名
+const SOURCE_NAME: &str = "repl";
+
+/// 一次输入既可以是单个表达式，也可以是若干语句
+enum Input {
+    Expr(Expr),
+    Program(Vec<Stmt>),
+}
+
+/// 交互式解释器，变量在多次输入之间保留。
+///
+/// 括号、方括号或花括号未闭合、字符串或块注释未结束时继续读取下一行，空行强制结束输入。
+pub struct Repl {
+    interpreter: Interpreter,
+    /// 尚未完成的多行输入
+    pending: RefCell<String>,
+    /// 执行过的输入，之前定义的函数出错时渲染到定义它的那次输入上
+    sources: SourceMap,
+}
+
+impl Repl {
+    pub fn new(interpreter: Interpreter) -> Self {
+        Repl {
+            interpreter,
+            pending: RefCell::new(String::new()),
+            sources: SourceMap::new(),
+        }
+    }
+
+    /// 逐行读取 `input` 直到 `:quit` 或输入结束，提示符与结果写入 `output`
+    pub fn run(&self, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            if self.pending.borrow().is_empty() && line.trim() == ":quit" {
+                return Ok(());
+            }
+            if let Some(response) = self.feed(&line) {
+                output.write_all(response.as_bytes())?;
+            }
+            let prompt = match self.pending.borrow().is_empty() {
+                true => "> ",
+                false => "... ",
+            };
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    /// 处理一行输入并返回要显示的内容，输入尚不完整时返回 `None`
+    pub fn feed(&self, line: &str) -> Option<String> {
+        let mut pending = self.pending.borrow_mut();
+        if pending.is_empty() {
+            if let Some(command) = line.trim_start().strip_prefix(':') {
+                return Some(self.command(command.trim()));
+            }
+        } else {
+            pending.push('\n');
+        }
+        pending.push_str(line);
+        if !line.trim().is_empty() && !is_complete(&pending) {
+            return None;
+        }
+        let source = mem::take(&mut *pending);
+        drop(pending);
+        Some(self.evaluate(&source))
+    }
+
+    fn command(&self, command: &str) -> String {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        match name {
+            "tokens" => self.tokens(argument),
+            "ast" => match self.parse(argument, 0) {
+                Ok(Input::Expr(expr)) => format!("{}\n", AstPrinter::new().print(&expr)),
+                Ok(Input::Program(statements)) => AstPrinter::new().print_program(&statements),
+                Err(diagnostics) => render(&diagnostics, argument),
+            },
+            "bytecode" => {
+                let chunk = match self.parse(argument, 0) {
+                    Ok(Input::Expr(expr)) => Compiler::new().compile_expr(&expr),
+                    Ok(Input::Program(statements)) => Compiler::new().compile(&statements),
+                    Err(diagnostics) => return render(&diagnostics, argument),
+                };
+                match chunk {
+                    Ok(chunk) => chunk.disassemble(),
+                    Err(error) => format!("error: {}\n", error),
+                }
+            }
+            "type" => self.check(argument),
+            "env" => self.env(),
+            "reset" => {
+                self.interpreter.reset();
+                "environment cleared\n".to_string()
+            }
+            "help" => HELP.to_string(),
+            _ => format!("unknown command `:{}`, try `:help`\n", name),
+        }
+    }
+
+    fn tokens(&self, source: &str) -> String {
+        let tokens = match LexerScanner::new(source.to_string()).scan() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .map(|error| Diagnostic::from(error).render(SOURCE_NAME, source))
+                    .collect()
+            }
+        };
+        tokens
+            .iter()
+            .map(|token| {
+                let span = format!("{}..{}", token.span.start, token.span.end);
+                match &token.lexeme {
+                    Some(lexeme) => format!("{:<8} {} {:?}\n", span, token.token_type, lexeme),
+                    None => format!("{:<8} {}\n", span, token.token_type),
+                }
+            })
+            .collect()
+    }
+
+    /// 已有的全局变量按当前值的类型参与检查
+    fn check(&self, source: &str) -> String {
+        let input = match self.parse(source, 0) {
+            Ok(input) => input,
+            Err(diagnostics) => return render(&diagnostics, source),
+        };
+        let checker = TypeChecker::new().with_mode(self.interpreter.mode());
+        for (name, value) in self.interpreter.environment().values() {
+            checker.define(&name, Type::of(&value));
+        }
+        let result = match input {
+            Input::Expr(expr) => checker.infer(&expr).map(|value| format!("{}\n", value)),
+            Input::Program(statements) => checker
+                .check(&statements)
+                .map(|()| "no type errors\n".to_string()),
+        };
+        result.unwrap_or_else(|errors| {
+            errors
+                .iter()
+                .map(|error| Diagnostic::from(error).render(SOURCE_NAME, source))
+                .collect()
+        })
+    }
+
+    fn env(&self) -> String {
+        let values = self.interpreter.environment().values();
+        if values.is_empty() {
+            return "no variables\n".to_string();
+        }
+        values
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, describe(value)))
+            .collect()
+    }
+
+    /// 能作为单个表达式解析时按表达式处理，这样 `1 + 2` 不需要分号；
+    /// 区间从 `offset` 开始计数
+    fn parse(&self, source: &str, offset: usize) -> Result<Input, Vec<Diagnostic>> {
+        let tokens = LexerScanner::new(source.to_string())
+            .with_offset(offset)
+            .scan()
+            .map_err(|errors| errors.iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+        if let Ok(expr) = Parser::new(tokens.clone()).parse() {
+            return Ok(Input::Expr(expr));
+        }
+        Parser::new(tokens)
+            .parse_program()
+            .map(Input::Program)
+            .map_err(|error| vec![Diagnostic::from(&error)])
+    }
+
+    /// 执行输入，显示表达式或最后一条表达式语句的值
+    fn evaluate(&self, source: &str) -> String {
+        let offset = self.sources.add(SOURCE_NAME, source);
+        let result = match self.parse(source, offset) {
+            Ok(Input::Expr(expr)) => self.interpreter.evaluate(&expr).map(Some),
+            Ok(Input::Program(statements)) => match statements.split_last() {
+                Some((Stmt::Expression(expr), statements)) => self
+                    .interpreter
+                    .execute(statements)
+                    .and_then(|()| self.interpreter.evaluate(expr))
+                    .map(Some),
+                _ => self.interpreter.execute(&statements).map(|()| None),
+            },
+            Err(diagnostics) => {
+                return diagnostics
+                    .iter()
+                    .map(|diagnostic| self.sources.render(diagnostic))
+                    .collect()
+            }
+        };
+        match result {
+            Ok(Some(value)) => describe(&value),
+            Ok(None) => String::new(),
+            Err(error) => self.sources.render(&Diagnostic::from(&error)),
+        }
+    }
+}
+
+fn render(diagnostics: &[Diagnostic], source: &str) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(SOURCE_NAME, source))
+        .collect()
+}
+
+/// `value: Type`，字符串带引号以便与其他类型区分
+fn describe(value: &Literal) -> String {
+    match value {
+        Literal::String(text) => format!("{:?}: {}\n", text, value.type_name()),
+        _ => format!("{}: {}\n", value, value.type_name()),
+    }
+}
+
+/// 括号、方括号与花括号都已闭合，且字符串与块注释都已结束
+fn is_complete(source: &str) -> bool {
+    match LexerScanner::new(source.to_string()).scan() {
+        Ok(tokens) => {
+            let depth = tokens
+                .iter()
+                .fold(0, |depth, token| match token.token_type {
+                    TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                        depth + 1
+                    }
+                    TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                        depth - 1
+                    }
+                    _ => depth,
+                });
+            depth <= 0
+        }
+        Err(errors) => !errors.iter().any(|error| {
+            matches!(
+                error.kind,
+                LexErrorKind::UnterminatedString | LexErrorKind::UnterminatedComment
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn repl() -> Repl {
+        Repl::new(Interpreter::new().with_output(Box::new(io::sink())))
+    }
+
+    #[test]
+    fn evaluate_lines() {
+        let repl = repl();
+        assert_eq!(Some("3: Integer\n".to_string()), repl.feed("1 + 2"));
+        assert_eq!(Some(String::new()), repl.feed("let a = \"x\";"));
+        assert_eq!(
+            Some("\"xy\": String\n".to_string()),
+            repl.feed("a = a + \"y\";")
+        );
+        repl.feed("fn f() {}");
+        assert_eq!(Some("<fn f>: Function\n".to_string()), repl.feed("f"));
+        assert_eq!(Some("nil: None\n".to_string()), repl.feed("f();"));
+        assert!(repl
+            .feed("-a")
+            .unwrap()
+            .starts_with("error[E0200]: cannot apply unary `-` to String"));
+    }
+
+    #[test]
+    fn multi_line_input() {
+        let repl = repl();
+        assert_eq!(None, repl.feed("fn add(a, b) {"));
+        assert_eq!(None, repl.feed("  return a + b;"));
+        assert_eq!(Some(String::new()), repl.feed("}"));
+        assert_eq!(None, repl.feed("add(1,"));
+        assert_eq!(Some("3: Integer\n".to_string()), repl.feed("2)"));
+        assert_eq!(None, repl.feed("\"multi"));
+        assert_eq!(
+            Some("\"multi\\nline\": String\n".to_string()),
+            repl.feed("line\"")
+        );
+        assert_eq!(None, repl.feed("/* note"));
+        assert_eq!(Some("1: Integer\n".to_string()), repl.feed("*/ 1"));
+
+        // 空行强制结束不完整的输入
+        assert_eq!(None, repl.feed("(1 +"));
+        let report = repl.feed("").unwrap();
+        assert!(report.starts_with("error[E0100]: Expect expression."));
+    }
+
+    #[test]
+    fn meta_commands() {
+        let repl = repl();
+        assert_eq!(
+            "0..1     integer \"1\"\n2..3     `+`\n4..5     identifier \"x\"\n",
+            repl.feed(":tokens 1 + x").unwrap()
+        );
+        assert_eq!(repl.feed(":ast 1 + 2 * 3").unwrap(), "(+ 1 (* 2 3))\n");
+        assert_eq!(
+            repl.feed(":bytecode -x").unwrap(),
+            "== <script> ==\n\
+             0000      1..2 GetVar              0 'x'\n\
+             0003      0..1 Negate\n\
+             0004      0..2 Return\n"
+        );
+        assert_eq!("no variables\n", repl.feed(":env").unwrap());
+        repl.feed("let s = \"a\";");
+        assert_eq!("String\n", repl.feed(":type s + \"b\"").unwrap());
+        assert_eq!("no type errors\n", repl.feed(":type let t = s;").unwrap());
+        assert!(repl
+            .feed(":type s * 2")
+            .unwrap()
+            .starts_with("error[E0300]: cannot apply `*` to String and Integer"));
+        repl.feed(":reset");
+        repl.feed("let b = 2.5; let a = true;");
+        assert_eq!(
+            "a = true: Bool\nb = 2.5: Float\n",
+            repl.feed(":env").unwrap()
+        );
+        assert_eq!("environment cleared\n", repl.feed(":reset").unwrap());
+        assert_eq!("no variables\n", repl.feed(":env").unwrap());
+        assert_eq!(
+            "unknown command `:nope`, try `:help`\n",
+            repl.feed(":nope").unwrap()
+        );
+    }
+
+    #[test]
+    fn run_session() {
+        let input = "let x = 2;\nx * (\n3)\n:quit\nx\n";
+        let mut output = vec![];
+        repl().run(input.as_bytes(), &mut output).unwrap();
+        assert_eq!("> > ... 6: Integer\n> ", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn error_in_earlier_input() {
+        let repl = repl();
+        repl.feed("fn f(a) {\n  return -a;\n}");
+        // 错误渲染到定义 `f` 的输入上，而不是当前输入
+        assert_eq!(
+            Some(
+                "error[E0200]: cannot apply unary `-` to String\n \
+                 --> repl:2:10\n  \
+                 |\n\
+                 2 |   return -a;\n  \
+                 |          ^ this operator does not support String\n"
+                    .to_string()
+            ),
+            repl.feed("\" éééééééééééééééééé\"; f(\"x\");")
+        );
+        assert!(repl.feed("-\"é\"").unwrap().contains(" --> repl:1:1\n"));
+    }
+}
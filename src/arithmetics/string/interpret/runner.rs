@@ -0,0 +1,234 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::panic;
+use std::thread;
+
+use super::diagnostics::Diagnostic;
+use super::interpreter::{Interpreter, RuntimeError};
+use super::parser::{ParseError, Parser};
+use super::scanner::{LexError, LexerScanner};
+
+/// 执行脚本的线程的栈大小（字节）
+///
+/// 实测 `Interpreter::MAX_DEPTH` 层简单的递归在 debug 构建中约需 80MB（release 约 16MB），
+/// `Parser` 默认上限内最深的语法树在各个阶段也都用不完。线程栈按需占用物理内存。
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// 在 `STACK_SIZE` 的线程中 `Interpreter` 可用的栈，为线程中调用解释器之前的栈帧留出 1MB
+pub const STACK_BUDGET: usize = STACK_SIZE - (1 << 20);
+
+/// 在栈大小为 `STACK_SIZE` 的新线程中运行 `f` 并返回其结果，`f` 中的 panic 原样传出
+///
+/// 其中创建的 `Interpreter` 用 `with_stack_budget(STACK_BUDGET)` 放宽栈预算。
+pub fn with_large_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn the interpreter thread")
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+}
+
+/// 脚本执行失败的阶段
+///
+/// | 阶段     | 退出码 |
+/// |----------|--------|
+/// | 读取脚本 | 74     |
+/// | 词法分析 | 65     |
+/// | 语法分析 | 66     |
+/// | 运行时   | 70     |
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Lex(Vec<LexError>),
+    Parse(ParseError),
+    Runtime(RuntimeError),
+}
+
+impl ScriptError {
+    /// 命令行参数错误时的退出码
+    pub const USAGE: i32 = 64;
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ScriptError::Lex(_) => 65,
+            ScriptError::Parse(_) => 66,
+            ScriptError::Runtime(_) => 70,
+            ScriptError::Io(_) => 74,
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(error) => write!(f, "{}", error),
+            ScriptError::Lex(errors) => match errors.first() {
+                Some(error) => write!(f, "{}", error),
+                None => write!(f, "lexical error"),
+            },
+            ScriptError::Parse(error) => write!(f, "{}", error),
+            ScriptError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// 待执行的脚本，`name` 用于诊断信息中的文件名
+pub struct Script {
+    name: String,
+    source: String,
+}
+
+impl Script {
+    pub fn new(name: &str, source: &str) -> Self {
+        Script {
+            name: name.to_string(),
+            source: source.to_string(),
+        }
+    }
+
+    /// 读取 `path` 处的脚本，`-` 表示标准输入
+    pub fn load(path: &str) -> Result<Self, ScriptError> {
+        if path == "-" {
+            let mut source = String::new();
+            io::stdin()
+                .read_to_string(&mut source)
+                .map_err(ScriptError::Io)?;
+            return Ok(Script::new("<stdin>", &source));
+        }
+        let source = fs::read_to_string(path).map_err(ScriptError::Io)?;
+        Ok(Script::new(path, &source))
+    }
+
+    /// 依次扫描、解析并执行，任一阶段出错即停止
+    pub fn run(&self, interpreter: &Interpreter) -> Result<(), ScriptError> {
+        let tokens = LexerScanner::new(self.source.clone())
+            .scan()
+            .map_err(ScriptError::Lex)?;
+        let statements = Parser::new(tokens)
+            .parse_program()
+            .map_err(ScriptError::Parse)?;
+        interpreter
+            .execute(&statements)
+            .map_err(ScriptError::Runtime)
+    }
+
+    /// 将错误渲染为带源码片段的诊断信息
+    pub fn render(&self, error: &ScriptError) -> String {
+        match error {
+            ScriptError::Io(error) => format!("error: cannot read `{}`: {}\n", self.name, error),
+            ScriptError::Lex(errors) => errors
+                .iter()
+                .map(|error| Diagnostic::from(error).render(&self.name, &self.source))
+                .collect(),
+            ScriptError::Parse(error) => Diagnostic::from(error).render(&self.name, &self.source),
+            ScriptError::Runtime(error) => Diagnostic::from(error).render(&self.name, &self.source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(source: &str) -> Result<(), ScriptError> {
+        let interpreter = Interpreter::new().with_output(Box::new(io::sink()));
+        Script::new("test.txt", source).run(&interpreter)
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert!(run("let a = 1; print a;").is_ok());
+        assert_eq!(65, run("let a = 1 # 2;").unwrap_err().exit_code());
+        assert_eq!(66, run("let a = ;").unwrap_err().exit_code());
+        assert_eq!(70, run("print -\"a\";").unwrap_err().exit_code());
+        assert_eq!(
+            74,
+            Script::load("/nonexistent/script.txt")
+                .err()
+                .unwrap()
+                .exit_code()
+        );
+    }
+
+    #[test]
+    fn render_errors() {
+        let script = Script::new("test.txt", "let a = 1 # 2 @;");
+        let error = script.run(&Interpreter::new()).unwrap_err();
+        let report = script.render(&error);
+        assert_eq!(2, report.matches("error[E0001]").count());
+        assert!(report.contains(" --> test.txt:1:11\n"));
+
+        let script = Script::new("test.txt", "let a = 1;\nprint a + nil;");
+        let error = script.run(&Interpreter::new()).unwrap_err();
+        assert_eq!(
+            "error[E0200]: cannot apply `+` to Integer and None\n \
+             --> test.txt:2:9\n  \
+             |\n\
+             2 | print a + nil;\n  \
+             |         ^ this operator does not support Integer and None\n",
+            script.render(&error)
+        );
+    }
+
+    #[test]
+    fn default_limits_fit_in_large_stack() {
+        use std::rc::Rc;
+
+        use super::super::checker::TypeChecker;
+        use super::super::compiler::Compiler;
+        use super::super::folder::ConstantFolder;
+        use super::super::printer::Formatter;
+        use super::super::testing::{parse_program, SharedOutput};
+        use super::super::vm::Vm;
+
+        // 解析器默认上限内最深的语法树经过每一个阶段，以及上限以内最深的递归
+        let parens = Parser::MAX_NESTING - 3;
+        let deepest = format!(
+            "{{{{print {}1{} + {};}}}}",
+            "(".repeat(parens),
+            ")".repeat(parens),
+            vec!["1"; Parser::MAX_HEIGHT - Parser::MAX_NESTING].join(" + ")
+        );
+        let recursion = format!(
+            "fn s(n) {{ if (n == 0) return 0; return n + s(n - 1); }} print s({});",
+            Interpreter::MAX_DEPTH - 1
+        );
+        let sum = (Interpreter::MAX_DEPTH - 1) * Interpreter::MAX_DEPTH / 2;
+        let cases = [
+            (deepest, Parser::MAX_HEIGHT - Parser::MAX_NESTING + 1),
+            (recursion, sum),
+        ];
+        with_large_stack(|| {
+            for (source, expected) in cases {
+                let statements = parse_program(&source);
+                let formatted = Formatter::new().format(&statements);
+                assert_eq!(
+                    formatted,
+                    Formatter::new().format(&parse_program(&formatted))
+                );
+                let folded = ConstantFolder::new().fold_program(&statements);
+                TypeChecker::new().check(&folded).unwrap();
+
+                let output = SharedOutput::default();
+                Interpreter::new()
+                    .with_stack_budget(STACK_BUDGET)
+                    .with_output(Box::new(output.clone()))
+                    .execute(&statements)
+                    .unwrap();
+                assert_eq!(format!("{}\n", expected), output.contents());
+
+                let output = SharedOutput::default();
+                let chunk = Compiler::new().compile(&statements).unwrap();
+                Vm::new()
+                    .with_output(Box::new(output.clone()))
+                    .run(Rc::new(chunk))
+                    .unwrap();
+                assert_eq!(format!("{}\n", expected), output.contents());
+            }
+        });
+    }
+}
@@ -1,6 +1,9 @@
 use std::cell::{Cell, RefCell};
+use std::fmt;
 
-use super::token::{Position, Token, TokenType};
+use super::bigint::BigInt;
+use super::literal::Literal;
+use super::token::{keyword, Comment, CommentKind, Position, Span, StringPart, Token, TokenType};
 
 /// Nondeterministic finite Automation
 /// lexer scanner using NFA
@@ -11,9 +14,52 @@ use super::token::{Position, Token, TokenType};
 pub enum ScanState {
     Initial,
     InNumber,
+    /// 小数点之后，至少需要一位数字
     InNumberFloat,
+    InNumberFraction,
+    /// `e`/`E` 之后，可以跟符号
+    InNumberExponent,
+    InNumberExponentSign,
+    InNumberExponentDigits,
+    /// `0x`/`0o`/`0b` 之后
+    InNumberRadix(u32),
     InString,
+    /// 字符串中的 `$` 之后，`{` 开始插值
+    InStringDollar,
+    /// `${` 之后，收集表达式的源码直到匹配的 `}`；
+    /// `depth` 为嵌套花括号层数，`quoted` 表示位于表达式中的字符串内
+    InStringInterpolation {
+        depth: usize,
+        quoted: bool,
+        escaped: bool,
+    },
+    /// 字符串中的 `\` 之后
+    InStringEscape,
+    /// `\u` 之后，需要 `{`
+    InStringUnicode,
+    /// `\u{` 之后，累计十六进制数字
+    InStringUnicodeDigits {
+        value: u32,
+        digits: u8,
+    },
+    /// `r` 之后的 `#`，记录已读到的个数
+    InRawStringStart(usize),
+    /// `r#"` 之后，`usize` 为开头 `#` 的个数
+    InRawString(usize),
+    /// 原始字符串中 `"` 之后已读到 `seen` 个 `#`
+    InRawStringEnd {
+        hashes: usize,
+        seen: usize,
+    },
     InIdentifier,
+    InSlash,
+    InLineComment,
+    /// `usize` 为块注释的嵌套层数
+    InBlockComment(usize),
+    /// 块注释中 `*` 之后，可能结束一层注释
+    InBlockCommentStar(usize),
+    /// 块注释中 `/` 之后，可能开始一层嵌套注释
+    InBlockCommentSlash(usize),
     InBang,
     InGreater,
     InLess,
@@ -22,12 +68,87 @@ pub enum ScanState {
     InOr,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LexErrorKind {
+    UnexpectedCharacter,
+    /// 数字不完整，`character` 为缺少后续数字的字符，如 `.`、`e`、`x`
+    InvalidNumber,
+    /// 数字不属于字面量的进制，如 `0b102` 中的 `2`
+    InvalidDigit,
+    UnterminatedString,
+    /// 未知的转义字符或无效的 `\u{...}`，`character` 为 `\` 之后的字符
+    InvalidEscape,
+    /// 块注释缺少 `*/`
+    UnterminatedComment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    /// The offending character.
+    pub character: char,
+    pub position: Position,
+    pub span: Span,
+}
+
+impl LexError {
+    pub fn new(kind: LexErrorKind, character: char, position: Position, span: Span) -> Self {
+        LexError {
+            kind,
+            character,
+            position,
+            span,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            LexErrorKind::UnexpectedCharacter => "Unexpected character",
+            LexErrorKind::InvalidNumber => "Invalid number literal at",
+            LexErrorKind::InvalidDigit => "Invalid digit in number literal",
+            LexErrorKind::UnterminatedString => "Unterminated string starting with",
+            LexErrorKind::InvalidEscape => "Invalid escape sequence",
+            LexErrorKind::UnterminatedComment => "Unterminated block comment starting with",
+        };
+        write!(
+            f,
+            "[line {}:{}] {} {:?}.",
+            self.position.line + 1,
+            self.position.column + 1,
+            message,
+            self.character
+        )
+    }
+}
+
 pub struct LexerScanner {
     tokens: RefCell<Vec<Token>>,
+    errors: RefCell<Vec<LexError>>,
     source: String,
     current_state: Cell<ScanState>,
     current_buffer: RefCell<String>,
+    /// 当前字符的位置
     pub position: Cell<Position>,
+    /// 当前字符的字节偏移
+    offset: Cell<usize>,
+    /// 下一个字符的字节偏移
+    next_offset: Cell<usize>,
+    /// 当前 token 的起始字节偏移与位置
+    token_start: Cell<(usize, Position)>,
+    /// 字符串中当前转义序列 `\` 的字节偏移与位置
+    escape_start: Cell<(usize, Position)>,
+    /// 源码在外层源码中的字节偏移，插值表达式由嵌套的扫描器扫描
+    base: usize,
+    /// 当前字符串中已完成的插值片段
+    parts: RefCell<Vec<StringPart>>,
+    /// 当前插值表达式的起始字节偏移与位置
+    segment_start: Cell<(usize, Position)>,
+    /// 是否保留注释
+    keep_comments: bool,
+    /// 尚未附加到 token 上的注释
+    comments: RefCell<Vec<Comment>>,
 }
 
 impl LexerScanner {
@@ -36,79 +157,241 @@ impl LexerScanner {
         new_source.push('\0');
         LexerScanner {
             tokens: RefCell::new(vec![]),
+            errors: RefCell::new(vec![]),
             source: new_source,
             current_state: Cell::new(ScanState::Initial),
             current_buffer: RefCell::new(String::new()),
-            position: Cell::new(Position::new(0)),
+            position: Cell::new(Position::default()),
+            offset: Cell::new(0),
+            next_offset: Cell::new(0),
+            token_start: Cell::new((0, Position::default())),
+            escape_start: Cell::new((0, Position::default())),
+            base: 0,
+            parts: RefCell::new(vec![]),
+            segment_start: Cell::new((0, Position::default())),
+            keep_comments: false,
+            comments: RefCell::new(vec![]),
         }
     }
 
-    pub fn scan(&self) -> Vec<Token> {
-        for c in self.source.chars() {
+    /// token 与错误的区间从 `offset` 开始计数，用于让多段源码的区间互不重叠
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.base = offset;
+        self
+    }
+
+    /// 扫描外层源码中从 `offset`、`position` 开始的片段，token 与错误的位置都相对于外层源码
+    fn nested(source: String, offset: usize, position: Position, keep_comments: bool) -> Self {
+        let mut scanner = LexerScanner::new(source);
+        scanner.base = offset;
+        scanner.position = Cell::new(position);
+        scanner.keep_comments = keep_comments;
+        scanner
+    }
+
+    /// 将注释作为 trivia 附加到其后的 token 上，供格式化与文档工具使用
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// 最后一个 token 之后的注释
+    pub fn trailing_comments(&self) -> Vec<Comment> {
+        self.comments.borrow().clone()
+    }
+
+    /// 扫描整个输入；出错时跳过出错字符并回到初始状态继续扫描，
+    /// 因此一次扫描即可报告所有词法错误
+    pub fn scan(&self) -> Result<Vec<Token>, Vec<LexError>> {
+        for (offset, c) in self.source.char_indices() {
+            let offset = self.base + offset;
+            self.offset.set(offset);
+            self.next_offset.set(offset + c.len_utf8());
             self.process_input(c);
+            self.position_advance(c);
         }
 
-        self.tokens.borrow().to_vec()
+        let errors = self.errors.borrow();
+        if errors.is_empty() {
+            Ok(self.tokens.borrow().to_vec())
+        } else {
+            Err(errors.to_vec())
+        }
     }
 
     pub fn process_input(&self, c: char) {
         match self.current_state.get() {
-            ScanState::Initial => match c {
-                '(' => self.add_token(TokenType::LeftParen),
-                ')' => self.add_token(TokenType::RightParen),
-                '+' => self.add_token(TokenType::Plus),
-                '-' => self.add_token(TokenType::Minus),
-                '*' => self.add_token(TokenType::Star),
-                '/' => self.add_token(TokenType::Slash),
-                ';' => self.add_token(TokenType::Semicolon),
-                '!' => self.set_state(ScanState::InBang),
-                '>' => self.set_state(ScanState::InGreater),
-                '<' => self.set_state(ScanState::InLess),
-                '=' => self.set_state(ScanState::InEqual),
-                '"' => self.set_state(ScanState::InString),
-                '&' => self.set_state(ScanState::InAnd),
-                '|' => self.set_state(ScanState::InOr),
-                '0'..='9' => {
-                    self.set_state(ScanState::InNumber);
+            ScanState::Initial => {
+                self.mark_token_start();
+                match c {
+                    '(' => self.add_token(TokenType::LeftParen),
+                    ')' => self.add_token(TokenType::RightParen),
+                    '{' => self.add_token(TokenType::LeftBrace),
+                    '}' => self.add_token(TokenType::RightBrace),
+                    '[' => self.add_token(TokenType::LeftBracket),
+                    ']' => self.add_token(TokenType::RightBracket),
+                    '+' => self.add_token(TokenType::Plus),
+                    '-' => self.add_token(TokenType::Minus),
+                    '*' => self.add_token(TokenType::Star),
+                    '/' => self.set_state(ScanState::InSlash),
+                    ';' => self.add_token(TokenType::Semicolon),
+                    ',' => self.add_token(TokenType::Comma),
+                    ':' => self.add_token(TokenType::Colon),
+                    '!' => self.set_state(ScanState::InBang),
+                    '>' => self.set_state(ScanState::InGreater),
+                    '<' => self.set_state(ScanState::InLess),
+                    '=' => self.set_state(ScanState::InEqual),
+                    '"' => self.set_state(ScanState::InString),
+                    '&' => self.set_state(ScanState::InAnd),
+                    '|' => self.set_state(ScanState::InOr),
+                    '0'..='9' => {
+                        self.set_state(ScanState::InNumber);
+                        self.process_input(c);
+                    }
+                    c if c.is_alphabetic() || c == '_' => {
+                        self.set_state(ScanState::InIdentifier);
+                        self.process_input(c);
+                    }
+                    '\0' | ' ' | '\t' | '\r' | '\n' => {
+                        self.clear_buffer();
+                        self.state_initial()
+                    }
+                    _ => self.add_error(LexErrorKind::UnexpectedCharacter, c),
+                }
+            }
+            ScanState::InNumber => match c {
+                '0'..='9' | '_' => self.push_buffer(c),
+                'x' | 'o' | 'b' if *self.current_buffer.borrow() == "0" => {
+                    self.push_buffer(c);
+                    self.set_state(ScanState::InNumberRadix(match c {
+                        'x' => 16,
+                        'o' => 8,
+                        _ => 2,
+                    }));
+                }
+                '.' => {
+                    self.push_buffer(c);
+                    self.set_state(ScanState::InNumberFloat);
+                }
+                'e' | 'E' => {
+                    self.push_buffer(c);
+                    self.set_state(ScanState::InNumberExponent);
+                }
+                _ => {
+                    self.add_previous_number(TokenType::Integer);
                     self.process_input(c);
                 }
-                c if c.is_alphabetic() => {
-                    self.set_state(ScanState::InIdentifier);
+            },
+            ScanState::InNumberFloat => {
+                if c.is_ascii_digit() {
+                    self.push_buffer(c);
+                    self.set_state(ScanState::InNumberFraction);
+                } else {
+                    self.add_previous_error(LexErrorKind::InvalidNumber, '.');
                     self.process_input(c);
                 }
-                '\n' => {
-                    self.line_advance();
+            }
+            ScanState::InNumberFraction => match c {
+                '0'..='9' | '_' => self.push_buffer(c),
+                'e' | 'E' => {
+                    self.push_buffer(c);
+                    self.set_state(ScanState::InNumberExponent);
                 }
-                '\0' | ' ' => {
-                    self.clear_buffer();
-                    self.state_initial()
+                _ => {
+                    self.add_previous_number(TokenType::Float);
+                    self.process_input(c);
                 }
-                _ => panic!("Expression invalid."),
             },
-            ScanState::InNumber => {
-                if c.is_digit(10) {
+            ScanState::InNumberExponent | ScanState::InNumberExponentSign => match c {
+                '0'..='9' => {
                     self.push_buffer(c);
-                } else if c == '.' {
+                    self.set_state(ScanState::InNumberExponentDigits);
+                }
+                '+' | '-' if matches!(self.current_state.get(), ScanState::InNumberExponent) => {
                     self.push_buffer(c);
-                    self.set_state(ScanState::InNumberFloat);
-                } else {
-                    self.add_token_with_value(TokenType::Number, Some(self.get_buffer()));
+                    self.set_state(ScanState::InNumberExponentSign);
+                }
+                _ => {
+                    let last = self.current_buffer.borrow().chars().last();
+                    self.add_previous_error(LexErrorKind::InvalidNumber, last.unwrap_or('e'));
                     self.process_input(c);
                 }
-            }
-            ScanState::InNumberFloat => {
-                if c.is_digit(10) {
-                    self.set_state(ScanState::InNumber);
+            },
+            ScanState::InNumberExponentDigits => match c {
+                '0'..='9' | '_' => self.push_buffer(c),
+                _ => {
+                    self.add_previous_number(TokenType::Float);
+                    self.process_input(c);
+                }
+            },
+            ScanState::InNumberRadix(radix) => {
+                if c.is_digit(radix) || c == '_' {
+                    self.push_buffer(c);
+                } else if c.is_ascii_alphanumeric() {
+                    self.add_error(LexErrorKind::InvalidDigit, c);
+                } else if !self.current_buffer.borrow()[2..]
+                    .chars()
+                    .any(|c| c.is_digit(radix))
+                {
+                    let prefix = self.current_buffer.borrow().chars().nth(1);
+                    self.add_previous_error(LexErrorKind::InvalidNumber, prefix.unwrap_or('x'));
                     self.process_input(c);
                 } else {
-                    panic!("Invalid float number.");
+                    self.add_previous_number(TokenType::Integer);
+                    self.process_input(c);
                 }
             }
             ScanState::InIdentifier => {
-                if c.is_alphabetic() {
+                if *self.current_buffer.borrow() == "r" && (c == '"' || c == '#') {
+                    // 原始字符串 r"..." 与 r#"..."#
+                    self.clear_buffer();
+                    self.set_state(ScanState::InRawStringStart(0));
+                    self.process_input(c);
+                } else if c.is_alphanumeric() || c == '_' {
                     self.push_buffer(c);
                 } else {
-                    self.add_token_with_value(TokenType::Identifier, Some(self.get_buffer()));
+                    let identifier = self.get_buffer();
+                    match keyword(&identifier) {
+                        Some(token_type) => self.add_previous_token(token_type, None),
+                        None => self.add_previous_token(TokenType::Identifier, Some(identifier)),
+                    }
+                    self.process_input(c);
+                }
+            }
+            ScanState::InSlash => match c {
+                '/' => self.set_state(ScanState::InLineComment),
+                '*' => self.set_state(ScanState::InBlockComment(1)),
+                _ => {
+                    self.add_previous_token(TokenType::Slash, None);
+                    self.process_input(c);
+                }
+            },
+            ScanState::InLineComment => {
+                if c == '\n' || c == '\0' {
+                    self.add_comment(CommentKind::Line, self.offset.get());
+                    self.process_input(c);
+                }
+            }
+            ScanState::InBlockComment(depth) => match c {
+                '*' => self.set_state(ScanState::InBlockCommentStar(depth)),
+                '/' => self.set_state(ScanState::InBlockCommentSlash(depth)),
+                '\0' => self.add_previous_error(LexErrorKind::UnterminatedComment, '/'),
+                _ => {}
+            },
+            ScanState::InBlockCommentStar(depth) => match c {
+                '/' if depth == 1 => self.add_comment(CommentKind::Block, self.next_offset.get()),
+                '/' => self.set_state(ScanState::InBlockComment(depth - 1)),
+                '*' => {}
+                _ => {
+                    self.set_state(ScanState::InBlockComment(depth));
+                    self.process_input(c);
+                }
+            },
+            ScanState::InBlockCommentSlash(depth) => {
+                if c == '*' {
+                    self.set_state(ScanState::InBlockComment(depth + 1));
+                } else {
+                    self.set_state(ScanState::InBlockComment(depth));
                     self.process_input(c);
                 }
             }
@@ -116,7 +399,7 @@ impl LexerScanner {
                 if c == '=' {
                     self.add_token(TokenType::BangEqual);
                 } else {
-                    self.add_token(TokenType::Bang);
+                    self.add_previous_token(TokenType::Bang, None);
                     self.process_input(c);
                 }
             }
@@ -124,7 +407,7 @@ impl LexerScanner {
                 if c == '=' {
                     self.add_token(TokenType::LessEqual);
                 } else {
-                    self.add_token(TokenType::Less);
+                    self.add_previous_token(TokenType::Less, None);
                     self.process_input(c);
                 }
             }
@@ -132,7 +415,7 @@ impl LexerScanner {
                 if c == '=' {
                     self.add_token(TokenType::GreaterEqual);
                 } else {
-                    self.add_token(TokenType::Greater);
+                    self.add_previous_token(TokenType::Greater, None);
                     self.process_input(c);
                 }
             }
@@ -140,39 +423,183 @@ impl LexerScanner {
                 if c == '=' {
                     self.add_token(TokenType::EqualEqual);
                 } else {
-                    self.add_token(TokenType::Equal);
+                    self.add_previous_token(TokenType::Equal, None);
                     self.process_input(c);
                 }
             }
-            ScanState::InString => {
-                if c != '"' {
-                    self.push_buffer(c);
-                } else if c == ';' {
-                    panic!("String must end with '\"'");
+            ScanState::InString => match c {
+                '\0' => self.add_previous_error(LexErrorKind::UnterminatedString, '"'),
+                '"' => self.add_string(),
+                '$' => self.set_state(ScanState::InStringDollar),
+                '\\' => {
+                    self.escape_start
+                        .set((self.offset.get(), self.position.get()));
+                    self.set_state(ScanState::InStringEscape);
+                }
+                _ => self.push_buffer(c),
+            },
+            ScanState::InStringDollar => {
+                if c == '{' {
+                    let text = self.get_buffer();
+                    if !text.is_empty() {
+                        self.parts.borrow_mut().push(StringPart::Text(text));
+                    }
+                    self.current_buffer.borrow_mut().clear();
+                    let position = self.position.get();
+                    self.segment_start.set((
+                        self.next_offset.get(),
+                        Position::new(position.line, position.column + 1),
+                    ));
+                    self.set_state(ScanState::InStringInterpolation {
+                        depth: 0,
+                        quoted: false,
+                        escaped: false,
+                    });
+                } else {
+                    self.push_buffer('$');
+                    self.set_state(ScanState::InString);
+                    self.process_input(c);
+                }
+            }
+            ScanState::InStringInterpolation {
+                depth,
+                quoted,
+                escaped,
+            } => {
+                if c == '\0' {
+                    return self.add_previous_error(LexErrorKind::UnterminatedString, '"');
+                }
+                if c == '}' && depth == 0 && !quoted {
+                    return self.add_interpolation();
+                }
+                self.push_buffer(c);
+                let (depth, quoted, escaped) = match c {
+                    _ if quoted && escaped => (depth, true, false),
+                    '\\' if quoted => (depth, true, true),
+                    '"' => (depth, !quoted, false),
+                    _ if quoted => (depth, true, false),
+                    '{' => (depth + 1, false, false),
+                    '}' => (depth - 1, false, false),
+                    _ => (depth, false, false),
+                };
+                self.set_state(ScanState::InStringInterpolation {
+                    depth,
+                    quoted,
+                    escaped,
+                });
+            }
+            ScanState::InStringEscape => {
+                let escaped = match c {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '"' => '"',
+                    '$' => '$',
+                    '\\' => '\\',
+                    'u' => {
+                        self.set_state(ScanState::InStringUnicode);
+                        return;
+                    }
+                    '\0' => return self.add_previous_error(LexErrorKind::UnterminatedString, '"'),
+                    _ => return self.add_escape_error(c, self.next_offset.get()),
+                };
+                self.push_buffer(escaped);
+                self.set_state(ScanState::InString);
+            }
+            ScanState::InStringUnicode => {
+                if c == '{' {
+                    self.set_state(ScanState::InStringUnicodeDigits {
+                        value: 0,
+                        digits: 0,
+                    });
                 } else {
+                    self.add_escape_error('u', self.offset.get());
+                    self.process_input(c);
+                }
+            }
+            ScanState::InStringUnicodeDigits { value, digits } => match c.to_digit(16) {
+                Some(digit) if digits < 6 => {
+                    self.set_state(ScanState::InStringUnicodeDigits {
+                        value: value * 16 + digit,
+                        digits: digits + 1,
+                    });
+                }
+                _ => match char::from_u32(value) {
+                    Some(unicode) if c == '}' && digits > 0 => {
+                        self.push_buffer(unicode);
+                        self.set_state(ScanState::InString);
+                    }
+                    _ if c == '}' => self.add_escape_error('u', self.next_offset.get()),
+                    _ => {
+                        self.add_escape_error('u', self.offset.get());
+                        self.process_input(c);
+                    }
+                },
+            },
+            ScanState::InRawStringStart(hashes) => match c {
+                '#' => self.set_state(ScanState::InRawStringStart(hashes + 1)),
+                '"' => self.set_state(ScanState::InRawString(hashes)),
+                _ => {
+                    self.add_previous_error(LexErrorKind::UnexpectedCharacter, '#');
+                    self.process_input(c);
+                }
+            },
+            ScanState::InRawString(hashes) => match c {
+                '\0' => self.add_previous_error(LexErrorKind::UnterminatedString, '"'),
+                '"' if hashes == 0 => {
+                    self.add_token_with_value(TokenType::String, Some(self.get_buffer()))
+                }
+                '"' => self.set_state(ScanState::InRawStringEnd { hashes, seen: 0 }),
+                _ => self.push_buffer(c),
+            },
+            ScanState::InRawStringEnd { hashes, seen } => {
+                if c == '#' && seen + 1 == hashes {
                     self.add_token_with_value(TokenType::String, Some(self.get_buffer()));
+                } else if c == '#' {
+                    self.set_state(ScanState::InRawStringEnd {
+                        hashes,
+                        seen: seen + 1,
+                    });
+                } else {
+                    // `#` 不够，`"` 与已读到的 `#` 属于字符串内容
+                    self.push_buffer('"');
+                    (0..seen).for_each(|_| self.push_buffer('#'));
+                    self.set_state(ScanState::InRawString(hashes));
+                    self.process_input(c);
                 }
             }
             ScanState::InAnd => {
                 if c == '&' {
                     self.add_token(TokenType::And);
                 } else {
-                    panic!("Expression invalid.");
+                    self.add_previous_error(LexErrorKind::UnexpectedCharacter, '&');
+                    self.process_input(c);
                 }
             }
             ScanState::InOr => {
                 if c == '|' {
                     self.add_token(TokenType::Or);
                 } else {
-                    panic!("Expression invalid.");
+                    self.add_previous_error(LexErrorKind::UnexpectedCharacter, '|');
+                    self.process_input(c);
                 }
             }
         }
     }
 
-    fn line_advance(&self) {
-        self.position
-            .set(Position::new(self.position.get().line + 1));
+    fn position_advance(&self, c: char) {
+        let position = self.position.get();
+        self.position.set(if c == '\n' {
+            Position::new(position.line + 1, 0)
+        } else {
+            Position::new(position.line, position.column + 1)
+        });
+    }
+
+    fn mark_token_start(&self) {
+        self.token_start
+            .set((self.offset.get(), self.position.get()));
     }
 
     fn state_initial(&self) {
@@ -180,8 +607,7 @@ impl LexerScanner {
     }
 
     fn get_buffer(&self) -> String {
-        let buffer = self.current_buffer.borrow().clone();
-        return buffer;
+        self.current_buffer.borrow().clone()
     }
 
     fn push_buffer(&self, c: char) {
@@ -190,50 +616,271 @@ impl LexerScanner {
 
     fn clear_buffer(&self) {
         self.current_buffer.borrow_mut().clear();
+        self.parts.borrow_mut().clear();
     }
 
     fn set_state(&self, state: ScanState) {
         self.current_state.set(state);
     }
 
-    fn add_token_with_value(&self, token_type: TokenType, value: Option<String>) {
-        self.tokens.borrow_mut().push(Token {
+    fn push_token(&self, token_type: TokenType, value: Option<String>, end: usize) {
+        let (start, position) = self.token_start.get();
+        self.push(Token::new(
             token_type,
-            lexeme: value,
-            position: self.position.get(),
-        });
+            value,
+            position,
+            Span::new(start, end),
+        ));
+    }
+
+    fn push(&self, mut token: Token) {
+        token.comments = self.comments.take();
+        self.tokens.borrow_mut().push(token);
         self.clear_buffer();
         self.state_initial();
     }
 
+    /// 当前字符是 token 的最后一个字符
+    fn add_token_with_value(&self, token_type: TokenType, value: Option<String>) {
+        self.push_token(token_type, value, self.next_offset.get());
+    }
+
     fn add_token(&self, token_type: TokenType) {
         self.add_token_with_value(token_type, None);
     }
+
+    /// 读到当前字符才能确定 token 已结束，当前字符不属于该 token
+    fn add_previous_token(&self, token_type: TokenType, value: Option<String>) {
+        self.push_token(token_type, value, self.offset.get());
+    }
+
+    /// 当前字符是字符串结尾的 `"`；含有插值片段时生成 `Interpolation` token
+    fn add_string(&self) {
+        if self.parts.borrow().is_empty() {
+            return self.add_token_with_value(TokenType::String, Some(self.get_buffer()));
+        }
+        let text = self.get_buffer();
+        let mut parts = self.parts.take();
+        if !text.is_empty() {
+            parts.push(StringPart::Text(text));
+        }
+        let (start, position) = self.token_start.get();
+        let mut token = Token::new(
+            TokenType::Interpolation,
+            None,
+            position,
+            Span::new(start, self.next_offset.get()),
+        );
+        token.parts = parts;
+        self.push(token);
+    }
+
+    /// 当前字符是结束插值的 `}`，用嵌套的扫描器扫描缓冲区中的表达式
+    fn add_interpolation(&self) {
+        let (offset, position) = self.segment_start.get();
+        let scanner = LexerScanner::nested(self.get_buffer(), offset, position, self.keep_comments);
+        match scanner.scan() {
+            Ok(tokens) => {
+                // `${x /* c */}` 中最后的注释附加到插值字符串 token 上
+                self.comments
+                    .borrow_mut()
+                    .extend(scanner.trailing_comments());
+                self.parts.borrow_mut().push(StringPart::Expr {
+                    tokens,
+                    span: Span::new(offset, self.offset.get()),
+                })
+            }
+            Err(errors) => self.errors.borrow_mut().extend(errors),
+        }
+        self.current_buffer.borrow_mut().clear();
+        self.set_state(ScanState::InString);
+    }
+
+    /// 注释从 token 起始到 `end`；不保留注释时直接丢弃
+    fn add_comment(&self, kind: CommentKind, end: usize) {
+        if self.keep_comments {
+            let (start, _) = self.token_start.get();
+            self.comments.borrow_mut().push(Comment {
+                kind,
+                text: self.source[start - self.base..end - self.base].to_string(),
+                span: Span::new(start, end),
+            });
+        }
+        self.clear_buffer();
+        self.state_initial();
+    }
+
+    /// 数字 token 结束于当前字符之前，去掉 `_` 分隔符后计算其值
+    fn add_previous_number(&self, token_type: TokenType) {
+        let lexeme = self.get_buffer();
+        let digits: String = lexeme.chars().filter(|c| *c != '_').collect();
+        let literal = match token_type {
+            TokenType::Float => digits.parse().ok().map(Literal::Float),
+            _ => {
+                let (radix, digits) = match digits.get(..2) {
+                    Some("0x") => (16, &digits[2..]),
+                    Some("0o") => (8, &digits[2..]),
+                    Some("0b") => (2, &digits[2..]),
+                    _ => (10, &digits[..]),
+                };
+                // 超出 i64 范围的整数字面量读作 BigInt
+                BigInt::from_str_radix(digits, radix).map(Literal::from)
+            }
+        };
+        let (start, position) = self.token_start.get();
+        let span = Span::new(start, self.offset.get());
+        match literal {
+            Some(literal) => self
+                .push(Token::new(token_type, Some(lexeme), position, span).with_literal(literal)),
+            None => self.push_error(LexErrorKind::InvalidNumber, '_', position, span),
+        }
+    }
+
+    fn push_error(&self, kind: LexErrorKind, character: char, position: Position, span: Span) {
+        self.errors
+            .borrow_mut()
+            .push(LexError::new(kind, character, position, span));
+        self.clear_buffer();
+        self.state_initial();
+    }
+
+    /// 当前字符出错：记录错误并丢弃当前缓冲区，回到初始状态
+    fn add_error(&self, kind: LexErrorKind, character: char) {
+        let span = Span::new(self.offset.get(), self.next_offset.get());
+        self.push_error(kind, character, self.position.get(), span);
+    }
+
+    /// 转义序列出错，错误区间从 `\` 到 `end`；留在字符串中继续扫描，
+    /// 以免把字符串的剩余部分当作代码
+    fn add_escape_error(&self, character: char, end: usize) {
+        let (start, position) = self.escape_start.get();
+        self.errors.borrow_mut().push(LexError::new(
+            LexErrorKind::InvalidEscape,
+            character,
+            position,
+            Span::new(start, end),
+        ));
+        self.set_state(ScanState::InString);
+    }
+
+    /// 当前 token 出错，错误区间从 token 起始到当前字符之前
+    fn add_previous_error(&self, kind: LexErrorKind, character: char) {
+        let (start, position) = self.token_start.get();
+        let span = Span::new(start, self.offset.get());
+        self.push_error(kind, character, position, span);
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    fn new_token(token_type: TokenType) -> Token {
-        Token::new(token_type, None, Position::new(0))
+    /// 单行输入中的 token，列号与字节偏移一致
+    fn new_token(token_type: TokenType, lexeme: Option<&str>, start: usize, end: usize) -> Token {
+        Token::new(
+            token_type,
+            lexeme.map(|lexeme| lexeme.to_string()),
+            Position::new(0, start as u32),
+            Span::new(start, end),
+        )
+    }
+
+    fn number_token(
+        token_type: TokenType,
+        lexeme: &str,
+        literal: Literal,
+        start: usize,
+        end: usize,
+    ) -> Token {
+        new_token(token_type, Some(lexeme), start, end).with_literal(literal)
+    }
+
+    #[test]
+    fn scan_numbers() {
+        let scan = |source: &str| {
+            LexerScanner::new(source.to_string())
+                .scan()
+                .unwrap()
+                .into_iter()
+                .map(|token| (token.token_type, *token.literal.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                (TokenType::Float, Literal::Float(1e10)),
+                (TokenType::Float, Literal::Float(2.5e-3)),
+                (TokenType::Float, Literal::Float(1.5e3)),
+                (TokenType::Integer, Literal::Integer(255)),
+                (TokenType::Integer, Literal::Integer(15)),
+                (TokenType::Integer, Literal::Integer(10)),
+                (TokenType::Integer, Literal::Integer(1_000_000)),
+                (TokenType::Float, Literal::Float(1_000.5)),
+                (TokenType::Integer, Literal::Integer(7)),
+            ],
+            scan("1e10 2.5E-3 15e+2 0xFF 0o17 0b1010 1_000_000 1_000.5 007")
+        );
+        assert_eq!(
+            vec![(
+                TokenType::Integer,
+                Literal::from(BigInt::from_str_radix("ffffffffffffffffff", 16).unwrap())
+            )],
+            scan("0xff_ffff_ffff_ffff_ffff")
+        );
+
+        let tokens = LexerScanner::new("0x1F+2.0".to_string()).scan().unwrap();
+        assert_eq!(
+            vec![
+                number_token(TokenType::Integer, "0x1F", Literal::Integer(31), 0, 4),
+                new_token(TokenType::Plus, None, 4, 5),
+                number_token(TokenType::Float, "2.0", Literal::Float(2.0), 5, 8),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn scan_invalid_numbers() {
+        let errors = |source: &str| {
+            LexerScanner::new(source.to_string())
+                .scan()
+                .unwrap_err()
+                .into_iter()
+                .map(|error| (error.kind, error.character, error.span))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![(LexErrorKind::InvalidNumber, 'e', Span::new(0, 2))],
+            errors("1e;")
+        );
+        assert_eq!(
+            vec![(LexErrorKind::InvalidNumber, '-', Span::new(0, 5))],
+            errors("1.5e-")
+        );
+        assert_eq!(
+            vec![(LexErrorKind::InvalidNumber, 'x', Span::new(0, 2))],
+            errors("0x + 1")
+        );
+        assert_eq!(
+            vec![(LexErrorKind::InvalidDigit, '2', Span::new(4, 5))],
+            errors("0b102")
+        );
     }
+
     #[test]
     fn scan_math_expression() {
         let scanner = LexerScanner::new("(1 + 1) * 3 / 2".to_string());
-        let position = Position::new(0);
         let tokens: Vec<Token> = vec![
-            new_token(TokenType::LeftParen),
-            Token::new(TokenType::Number, Some('1'.to_string()), position),
-            new_token(TokenType::Plus),
-            Token::new(TokenType::Number, Some('1'.to_string()), position),
-            new_token(TokenType::RightParen),
-            new_token(TokenType::Star),
-            Token::new(TokenType::Number, Some('3'.to_string()), position),
-            new_token(TokenType::Slash),
-            Token::new(TokenType::Number, Some('2'.to_string()), position),
+            new_token(TokenType::LeftParen, None, 0, 1),
+            number_token(TokenType::Integer, "1", Literal::Integer(1), 1, 2),
+            new_token(TokenType::Plus, None, 3, 4),
+            number_token(TokenType::Integer, "1", Literal::Integer(1), 5, 6),
+            new_token(TokenType::RightParen, None, 6, 7),
+            new_token(TokenType::Star, None, 8, 9),
+            number_token(TokenType::Integer, "3", Literal::Integer(3), 10, 11),
+            new_token(TokenType::Slash, None, 12, 13),
+            number_token(TokenType::Integer, "2", Literal::Integer(2), 14, 15),
         ];
-        assert_eq!(tokens, scanner.scan());
+        assert_eq!(Ok(tokens), scanner.scan());
     }
 
     #[test]
@@ -241,38 +888,355 @@ mod test {
         let scanner = LexerScanner::new(
             "(1 + 1) * 3 / 2 - 3 != 10001.22 && !false && \"string\" || true;".to_string(),
         );
-        let position = Position::new(0);
         let tokens: Vec<Token> = vec![
-            new_token(TokenType::LeftParen),
-            Token::new(TokenType::Number, Some('1'.to_string()), position),
-            new_token(TokenType::Plus),
-            Token::new(TokenType::Number, Some('1'.to_string()), position),
-            new_token(TokenType::RightParen),
-            new_token(TokenType::Star),
-            Token::new(TokenType::Number, Some('3'.to_string()), position),
-            new_token(TokenType::Slash),
-            Token::new(TokenType::Number, Some('2'.to_string()), position),
-            new_token(TokenType::Minus),
-            Token::new(TokenType::Number, Some('3'.to_string()), position),
-            new_token(TokenType::BangEqual),
-            Token::new(TokenType::Number, Some("10001.22".to_string()), position),
-            new_token(TokenType::And),
-            new_token(TokenType::Bang),
-            Token::new(TokenType::Identifier, Some("false".to_string()), position),
-            new_token(TokenType::And),
-            Token::new(TokenType::String, Some("string".to_string()), position),
-            new_token(TokenType::Or),
-            Token::new(TokenType::Identifier, Some("true".to_string()), position),
-            new_token(TokenType::Semicolon),
+            new_token(TokenType::LeftParen, None, 0, 1),
+            number_token(TokenType::Integer, "1", Literal::Integer(1), 1, 2),
+            new_token(TokenType::Plus, None, 3, 4),
+            number_token(TokenType::Integer, "1", Literal::Integer(1), 5, 6),
+            new_token(TokenType::RightParen, None, 6, 7),
+            new_token(TokenType::Star, None, 8, 9),
+            number_token(TokenType::Integer, "3", Literal::Integer(3), 10, 11),
+            new_token(TokenType::Slash, None, 12, 13),
+            number_token(TokenType::Integer, "2", Literal::Integer(2), 14, 15),
+            new_token(TokenType::Minus, None, 16, 17),
+            number_token(TokenType::Integer, "3", Literal::Integer(3), 18, 19),
+            new_token(TokenType::BangEqual, None, 20, 22),
+            number_token(
+                TokenType::Float,
+                "10001.22",
+                Literal::Float(10001.22),
+                23,
+                31,
+            ),
+            new_token(TokenType::And, None, 32, 34),
+            new_token(TokenType::Bang, None, 35, 36),
+            new_token(TokenType::False, None, 36, 41),
+            new_token(TokenType::And, None, 42, 44),
+            new_token(TokenType::String, Some("string"), 45, 53),
+            new_token(TokenType::Or, None, 54, 56),
+            new_token(TokenType::True, None, 57, 61),
+            new_token(TokenType::Semicolon, None, 61, 62),
         ];
-        let scan_tokens = scanner.scan();
+        let scan_tokens = scanner.scan().unwrap();
         assert_eq!(tokens, scan_tokens);
     }
 
     #[test]
-    #[should_panic]
+    fn scan_multiline_positions() {
+        let scanner = LexerScanner::new("1 +\n  \"é\" <= x".to_string());
+        let tokens = scanner.scan().unwrap();
+        let positions: Vec<(Position, Span)> = tokens
+            .iter()
+            .map(|token| (token.position, token.span))
+            .collect();
+        assert_eq!(
+            vec![
+                (Position::new(0, 0), Span::new(0, 1)),
+                (Position::new(0, 2), Span::new(2, 3)),
+                (Position::new(1, 2), Span::new(6, 10)),
+                (Position::new(1, 6), Span::new(11, 13)),
+                (Position::new(1, 9), Span::new(14, 15)),
+            ],
+            positions
+        );
+    }
+
+    #[test]
+    fn scan_string_escapes() {
+        let scan = |source: &str| {
+            LexerScanner::new(source.to_string())
+                .scan()
+                .unwrap()
+                .into_iter()
+                .map(|token| token.lexeme.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["a\n\tb\"c\\d\r\0", "😀é"],
+            scan(r#""a\n\tb\"c\\d\r\0" "\u{1F600}\u{e9}""#)
+        );
+        assert_eq!(
+            vec!["C:\\path\\n", "say \"hi\"", "a \"# b", ""],
+            scan(r###"r"C:\path\n" r#"say "hi""# r##"a "# b"## r"""###)
+        );
+        assert_eq!(vec!["r", "x"], scan("r x"));
+    }
+
+    #[test]
+    fn scan_multiline_strings() {
+        let tokens = LexerScanner::new("\"a\nb\" r\"\nc\" x".to_string())
+            .scan()
+            .unwrap();
+        let positions: Vec<(Option<&str>, Position, Span)> = tokens
+            .iter()
+            .map(|token| (token.lexeme.as_deref(), token.position, token.span))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("a\nb"), Position::new(0, 0), Span::new(0, 5)),
+                (Some("\nc"), Position::new(1, 3), Span::new(6, 11)),
+                (Some("x"), Position::new(2, 3), Span::new(12, 13)),
+            ],
+            positions
+        );
+    }
+
+    #[test]
+    fn scan_invalid_strings() {
+        let errors = |source: &str| {
+            LexerScanner::new(source.to_string())
+                .scan()
+                .unwrap_err()
+                .into_iter()
+                .map(|error| (error.kind, error.character, error.span))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                (LexErrorKind::InvalidEscape, 'q', Span::new(3, 5)),
+                (LexErrorKind::InvalidEscape, 'u', Span::new(6, 11)),
+            ],
+            errors(r#"1 "\q \u{12G}" 2"#)
+        );
+        assert_eq!(
+            vec![
+                (LexErrorKind::InvalidEscape, 'u', Span::new(1, 3)),
+                (LexErrorKind::InvalidEscape, 'u', Span::new(4, 14)),
+            ],
+            errors(r#""\u \u{110000}""#)
+        );
+        assert_eq!(
+            vec![(LexErrorKind::UnterminatedString, '"', Span::new(0, 5))],
+            errors("\"ab\\\"")
+        );
+        assert_eq!(
+            vec![(LexErrorKind::UnterminatedString, '"', Span::new(0, 7))],
+            errors("r#\"ab\"x")
+        );
+    }
+
+    #[test]
+    fn scan_interpolation() {
+        let tokens = LexerScanner::new("x + \"a${b}\\${c}\"".to_string())
+            .scan()
+            .unwrap();
+        assert_eq!(TokenType::Interpolation, tokens[2].token_type);
+        assert_eq!(Span::new(4, 16), tokens[2].span);
+        assert_eq!(
+            vec![
+                StringPart::Text("a".to_string()),
+                StringPart::Expr {
+                    tokens: vec![new_token(TokenType::Identifier, Some("b"), 8, 9)],
+                    span: Span::new(8, 9),
+                },
+                StringPart::Text("${c}".to_string()),
+            ],
+            tokens[2].parts
+        );
+
+        // 表达式中的字符串、花括号与嵌套插值
+        let source = "\"${ f(\"}\", {1}) } ${\"in${x}\"}\n${ y\n}$\"";
+        let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+        assert_eq!(1, tokens.len());
+        let parts = &tokens[0].parts;
+        assert_eq!(6, parts.len());
+        let StringPart::Expr { tokens: first, .. } = &parts[0] else {
+            panic!("Expect expression part.");
+        };
+        assert_eq!(8, first.len());
+        assert_eq!(Some("}"), first[2].lexeme.as_deref());
+        let StringPart::Expr { tokens: nested, .. } = &parts[2] else {
+            panic!("Expect expression part.");
+        };
+        assert_eq!(TokenType::Interpolation, nested[0].token_type);
+        let StringPart::Expr { tokens: x, .. } = &nested[0].parts[1] else {
+            panic!("Expect expression part.");
+        };
+        assert_eq!(Span::new(25, 26), x[0].span);
+        let StringPart::Expr { tokens: y, .. } = &parts[4] else {
+            panic!("Expect expression part.");
+        };
+        assert_eq!(Position::new(1, 3), y[0].position);
+        assert_eq!(StringPart::Text("\n".to_string()), parts[3]);
+        assert_eq!(StringPart::Text("$".to_string()), parts[5]);
+    }
+
+    #[test]
+    fn scan_invalid_interpolation() {
+        let errors = LexerScanner::new("\"a ${1 # 2}\" + \"${x\"".to_string())
+            .scan()
+            .unwrap_err();
+        assert_eq!(
+            vec![
+                LexError::new(
+                    LexErrorKind::UnexpectedCharacter,
+                    '#',
+                    Position::new(0, 7),
+                    Span::new(7, 8)
+                ),
+                LexError::new(
+                    LexErrorKind::UnterminatedString,
+                    '"',
+                    Position::new(0, 15),
+                    Span::new(15, 20)
+                ),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn scan_comments() {
+        let source = "1 / 2 // half\n/* outer /* inner */ still */ x /**/ // end";
+        let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+        let token_types: Vec<TokenType> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                TokenType::Integer,
+                TokenType::Slash,
+                TokenType::Integer,
+                TokenType::Identifier
+            ],
+            token_types
+        );
+        assert_eq!(Position::new(1, 30), tokens[3].position);
+        assert!(tokens.iter().all(|token| token.comments.is_empty()));
+
+        let scanner = LexerScanner::new(source.to_string()).with_comments();
+        let tokens = scanner.scan().unwrap();
+        assert_eq!(
+            vec![
+                Comment {
+                    kind: CommentKind::Line,
+                    text: "// half".to_string(),
+                    span: Span::new(6, 13),
+                },
+                Comment {
+                    kind: CommentKind::Block,
+                    text: "/* outer /* inner */ still */".to_string(),
+                    span: Span::new(14, 43),
+                },
+            ],
+            tokens[3].comments
+        );
+        let trailing: Vec<String> = scanner
+            .trailing_comments()
+            .into_iter()
+            .map(|comment| comment.text)
+            .collect();
+        assert_eq!(vec!["/**/", "// end"], trailing);
+    }
+
+    #[test]
+    fn scan_unterminated_comment() {
+        let scanner = LexerScanner::new("1 /* a /* b */\n c".to_string());
+        assert_eq!(
+            vec![LexError::new(
+                LexErrorKind::UnterminatedComment,
+                '/',
+                Position::new(0, 2),
+                Span::new(2, 17)
+            )],
+            scanner.scan().unwrap_err()
+        );
+    }
+
+    #[test]
+    fn scan_keywords_and_identifiers() {
+        let scanner = LexerScanner::new("let _x1 = lettuce;".to_string());
+        let tokens: Vec<Token> = vec![
+            new_token(TokenType::Let, None, 0, 3),
+            new_token(TokenType::Identifier, Some("_x1"), 4, 7),
+            new_token(TokenType::Equal, None, 8, 9),
+            new_token(TokenType::Identifier, Some("lettuce"), 10, 17),
+            new_token(TokenType::Semicolon, None, 17, 18),
+        ];
+        assert_eq!(Ok(tokens), scanner.scan());
+
+        let tokens = LexerScanner::new("if else while for in break continue iffy".to_string())
+            .scan()
+            .unwrap();
+        let token_types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                TokenType::If,
+                TokenType::Else,
+                TokenType::While,
+                TokenType::For,
+                TokenType::In,
+                TokenType::Break,
+                TokenType::Continue,
+                TokenType::Identifier,
+            ],
+            token_types
+        );
+    }
+
+    #[test]
+    fn scan_collection_punctuation() {
+        let scanner = LexerScanner::new("[{a: 1}][0]".to_string());
+        let tokens: Vec<Token> = vec![
+            new_token(TokenType::LeftBracket, None, 0, 1),
+            new_token(TokenType::LeftBrace, None, 1, 2),
+            new_token(TokenType::Identifier, Some("a"), 2, 3),
+            new_token(TokenType::Colon, None, 3, 4),
+            number_token(TokenType::Integer, "1", Literal::Integer(1), 5, 6),
+            new_token(TokenType::RightBrace, None, 6, 7),
+            new_token(TokenType::RightBracket, None, 7, 8),
+            new_token(TokenType::LeftBracket, None, 8, 9),
+            number_token(TokenType::Integer, "0", Literal::Integer(0), 9, 10),
+            new_token(TokenType::RightBracket, None, 10, 11),
+        ];
+        assert_eq!(Ok(tokens), scanner.scan());
+    }
+
+    #[test]
     fn scan_invalid_expression() {
         let scanner = LexerScanner::new("2. + 3".to_string());
-        scanner.scan();
+        let errors = scanner.scan().unwrap_err();
+        assert_eq!(
+            vec![LexError::new(
+                LexErrorKind::InvalidNumber,
+                '.',
+                Position::new(0, 0),
+                Span::new(0, 2)
+            )],
+            errors
+        );
+    }
+
+    #[test]
+    fn scan_reports_every_error() {
+        let scanner = LexerScanner::new("1 # 2 & 3\n@ + \"abc".to_string());
+        let errors = scanner.scan().unwrap_err();
+        assert_eq!(
+            vec![
+                LexError::new(
+                    LexErrorKind::UnexpectedCharacter,
+                    '#',
+                    Position::new(0, 2),
+                    Span::new(2, 3)
+                ),
+                LexError::new(
+                    LexErrorKind::UnexpectedCharacter,
+                    '&',
+                    Position::new(0, 6),
+                    Span::new(6, 7)
+                ),
+                LexError::new(
+                    LexErrorKind::UnexpectedCharacter,
+                    '@',
+                    Position::new(1, 0),
+                    Span::new(10, 11)
+                ),
+                LexError::new(
+                    LexErrorKind::UnterminatedString,
+                    '"',
+                    Position::new(1, 4),
+                    Span::new(14, 18)
+                ),
+            ],
+            errors
+        );
     }
 }
@@ -0,0 +1,137 @@
+use std::rc::Rc;
+
+use super::{
+    expr::Expr,
+    token::{Span, Token},
+};
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    /// `expr;`
+    Expression(Expr),
+    /// `print expr;`
+    Print(Expr),
+    /// `let name = initializer;`，省略初始值时为 `None`
+    Let {
+        name: Token,
+        initializer: Option<Expr>,
+    },
+    /// `{ statements }`，在新的作用域中执行
+    Block(Vec<Stmt>),
+    /// `if (condition) then_branch else else_branch`
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    /// `while (condition) body`
+    While { condition: Expr, body: Box<Stmt> },
+    /// `for (initializer; condition; increment) body`，各部分均可省略
+    For {
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Box<Stmt>,
+    },
+    /// `for (name in iterable) body`，遍历列表的元素或映射的键
+    ForIn {
+        name: Token,
+        iterable: Expr,
+        body: Box<Stmt>,
+    },
+    /// `break;`
+    Break(Token),
+    /// `continue;`
+    Continue(Token),
+    /// `fn name(params) { body }`
+    Function(Rc<FunctionDecl>),
+    /// `return value;`，省略返回值时为 `None`
+    Return { keyword: Token, value: Option<Expr> },
+}
+
+/// 函数声明，具名函数与匿名函数共用，由 `Rc` 在语法树与运行时函数值之间共享
+#[derive(Debug)]
+pub struct FunctionDecl {
+    /// 匿名函数为 `None`
+    pub name: Option<Token>,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    /// 从 `fn` 到右花括号
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn accept<T: StmtVisitor>(&self, visitor: &T) -> T::ReturnType {
+        match self {
+            Stmt::Expression(expr) => visitor.visit_expression(expr),
+            Stmt::Print(expr) => visitor.visit_print(expr),
+            Stmt::Let { name, initializer } => visitor.visit_let(name, initializer.as_ref()),
+            Stmt::Block(statements) => visitor.visit_block(statements),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if(condition, then_branch, else_branch.as_deref()),
+            Stmt::While { condition, body } => visitor.visit_while(condition, body),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => visitor.visit_for(
+                initializer.as_deref(),
+                condition.as_ref(),
+                increment.as_ref(),
+                body,
+            ),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => visitor.visit_for_in(name, iterable, body),
+            Stmt::Break(keyword) => visitor.visit_break(keyword),
+            Stmt::Continue(keyword) => visitor.visit_continue(keyword),
+            Stmt::Function(declaration) => visitor.visit_function(declaration),
+            Stmt::Return { keyword, value } => visitor.visit_return(keyword, value.as_ref()),
+        }
+    }
+}
+
+pub trait StmtVisitor {
+    type ReturnType;
+
+    fn visit_expression(&self, expr: &Expr) -> Self::ReturnType;
+
+    fn visit_print(&self, expr: &Expr) -> Self::ReturnType;
+
+    fn visit_let(&self, name: &Token, initializer: Option<&Expr>) -> Self::ReturnType;
+
+    fn visit_block(&self, statements: &[Stmt]) -> Self::ReturnType;
+
+    fn visit_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Self::ReturnType;
+
+    fn visit_while(&self, condition: &Expr, body: &Stmt) -> Self::ReturnType;
+
+    fn visit_for(
+        &self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> Self::ReturnType;
+
+    fn visit_for_in(&self, name: &Token, iterable: &Expr, body: &Stmt) -> Self::ReturnType;
+
+    fn visit_break(&self, keyword: &Token) -> Self::ReturnType;
+
+    fn visit_continue(&self, keyword: &Token) -> Self::ReturnType;
+
+    fn visit_function(&self, declaration: &Rc<FunctionDecl>) -> Self::ReturnType;
+
+    fn visit_return(&self, keyword: &Token, value: Option<&Expr>) -> Self::ReturnType;
+}
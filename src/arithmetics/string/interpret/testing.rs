@@ -0,0 +1,65 @@
+//! 各模块测试共用的辅助函数
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use super::compiler::Compiler;
+use super::expr::Expr;
+use super::interpreter::{Interpreter, RuntimeError};
+use super::literal::NumericMode;
+use super::parser::Parser;
+use super::scanner::LexerScanner;
+use super::stmt::Stmt;
+use super::vm::Vm;
+
+pub fn parse_expr(source: &str) -> Expr {
+    let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+    Parser::new(tokens).parse().unwrap()
+}
+
+pub fn parse_program(source: &str) -> Vec<Stmt> {
+    let tokens = LexerScanner::new(source.to_string()).scan().unwrap();
+    Parser::new(tokens).parse_program().unwrap()
+}
+
+/// 可在测试中读取的共享输出
+#[derive(Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+/// 用树遍历解释器执行，返回输出与结果
+pub fn tree_walk(statements: &[Stmt], mode: NumericMode) -> (String, Result<(), RuntimeError>) {
+    let output = SharedOutput::default();
+    let interpreter = Interpreter::new()
+        .with_mode(mode)
+        .with_output(Box::new(output.clone()));
+    let result = interpreter.execute(statements);
+    (output.contents(), result)
+}
+
+/// 编译后用虚拟机执行，返回输出与结果
+pub fn run_vm(statements: &[Stmt], mode: NumericMode) -> (String, Result<(), RuntimeError>) {
+    let output = SharedOutput::default();
+    let vm = Vm::new()
+        .with_mode(mode)
+        .with_output(Box::new(output.clone()));
+    let chunk = Compiler::new().compile(statements).unwrap();
+    let result = vm.run(Rc::new(chunk)).map(|_| ());
+    (output.contents(), result)
+}
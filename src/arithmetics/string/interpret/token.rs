@@ -1,35 +1,205 @@
+use std::fmt;
+
+use super::literal::Literal;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
-    Number,       // [0-9]+
-    LeftParen,    // (
-    RightParen,   // )
-    Minus,        // -
-    Plus,         // +
-    Star,         // *
-    Slash,        // /
-    Bang,         // !
-    Equal,        // =
-    EqualEqual,   // ==
-    BangEqual,    // !=
-    Greater,      // >
-    GreaterEqual, // >=
-    Less,         // <
-    LessEqual,    // <=
-    Identifier,   // [a-z]+
-    String,       // "[a-z]+"
-    Semicolon,    // ;
-    And,          // &&
-    Or,           // ||
+    Integer,       // 42, 0xFF, 0o17, 0b1010, 1_000
+    Float,         // 1.5, 1e10, 2.5E-3
+    LeftParen,     // (
+    RightParen,    // )
+    LeftBrace,     // {
+    RightBrace,    // }
+    LeftBracket,   // [
+    RightBracket,  // ]
+    Minus,         // -
+    Plus,          // +
+    Star,          // *
+    Slash,         // /
+    Bang,          // !
+    Equal,         // =
+    EqualEqual,    // ==
+    BangEqual,     // !=
+    Greater,       // >
+    GreaterEqual,  // >=
+    Less,          // <
+    LessEqual,     // <=
+    Identifier,    // [a-zA-Z_][a-zA-Z0-9_]*
+    String,        // "[a-z]+"
+    Semicolon,     // ;
+    And,           // &&
+    Or,            // ||
+    Let,           // let
+    Print,         // print
+    If,            // if
+    Else,          // else
+    While,         // while
+    For,           // for
+    In,            // in
+    Break,         // break
+    Continue,      // continue
+    Comma,         // ,
+    Colon,         // :
+    Fn,            // fn
+    Return,        // return
+    True,          // true
+    False,         // false
+    Nil,           // nil
+    Interpolation, // "Hello ${name}!"
+}
+
+/// 保留字，标识符扫描结束时查表
+pub fn keyword(identifier: &str) -> Option<TokenType> {
+    match identifier {
+        "let" => Some(TokenType::Let),
+        "print" => Some(TokenType::Print),
+        "if" => Some(TokenType::If),
+        "else" => Some(TokenType::Else),
+        "while" => Some(TokenType::While),
+        "for" => Some(TokenType::For),
+        "in" => Some(TokenType::In),
+        "break" => Some(TokenType::Break),
+        "continue" => Some(TokenType::Continue),
+        "fn" => Some(TokenType::Fn),
+        "return" => Some(TokenType::Return),
+        "true" => Some(TokenType::True),
+        "false" => Some(TokenType::False),
+        "nil" => Some(TokenType::Nil),
+        _ => None,
+    }
+}
+
+impl TokenType {
+    /// 运算符、标点与关键字在源码中的写法，其余 token 返回 `None`
+    pub fn symbol(&self) -> Option<&'static str> {
+        let text = match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Star => "*",
+            TokenType::Slash => "/",
+            TokenType::Bang => "!",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::BangEqual => "!=",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::Semicolon => ";",
+            TokenType::And => "&&",
+            TokenType::Or => "||",
+            TokenType::Let => "let",
+            TokenType::Print => "print",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::While => "while",
+            TokenType::For => "for",
+            TokenType::In => "in",
+            TokenType::Break => "break",
+            TokenType::Continue => "continue",
+            TokenType::Comma => ",",
+            TokenType::Colon => ":",
+            TokenType::Fn => "fn",
+            TokenType::Return => "return",
+            TokenType::True => "true",
+            TokenType::False => "false",
+            TokenType::Nil => "nil",
+            TokenType::Integer
+            | TokenType::Float
+            | TokenType::Identifier
+            | TokenType::String
+            | TokenType::Interpolation => return None,
+        };
+        Some(text)
+    }
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(symbol) = self.symbol() {
+            return write!(f, "`{}`", symbol);
+        }
+        let text = match self {
+            TokenType::Integer => "integer",
+            TokenType::Float => "float",
+            TokenType::Identifier => "identifier",
+            TokenType::String => "string",
+            _ => "interpolated string",
+        };
+        f.write_str(text)
+    }
+}
+
+/// 插值字符串的片段
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// 已处理转义的文本
+    Text(String),
+    /// `${ ... }` 中的 token，位置与区间都相对于整个源码；`span` 为花括号之间的区间
+    Expr { tokens: Vec<Token>, span: Span },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentKind {
+    /// `// ...`
+    Line,
+    /// `/* ... */`，可以嵌套
+    Block,
+}
+
+/// 注释原文（包括 `//`、`/*`、`*/`）与其区间
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub kind: CommentKind,
+    pub text: String,
+    pub span: Span,
+}
+
+/// 行号与列号均从 0 开始，列号按字符计数
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Position {
     pub line: u32,
+    pub column: u32,
 }
 
 impl Position {
-    pub fn new(line: u32) -> Self {
-        Position { line }
+    pub fn new(line: u32, column: u32) -> Self {
+        Position { line, column }
+    }
+}
+
+/// 源码中的字节区间 `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// 覆盖两个区间的最小区间
+    pub fn merge(self, other: Span) -> Self {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
     }
 }
 
@@ -37,15 +207,37 @@ impl Position {
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Option<String>,
+    /// 起始位置
     pub position: Position,
+    pub span: Span,
+    /// 数字 token 在扫描时已计算出的值，装箱以免 `Token` 过大
+    pub literal: Option<Box<Literal>>,
+    /// 紧挨在 token 之前的注释，仅在 `LexerScanner::with_comments` 时保留
+    pub comments: Vec<Comment>,
+    /// `Interpolation` token 的各个片段
+    pub parts: Vec<StringPart>,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, value: Option<String>, position: Position) -> Self {
+    pub fn new(
+        token_type: TokenType,
+        value: Option<String>,
+        position: Position,
+        span: Span,
+    ) -> Self {
         Token {
             token_type,
             lexeme: value,
             position,
+            span,
+            literal: None,
+            comments: vec![],
+            parts: vec![],
         }
     }
+
+    pub fn with_literal(mut self, literal: Literal) -> Self {
+        self.literal = Some(Box::new(literal));
+        self
+    }
 }
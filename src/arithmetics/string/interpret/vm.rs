@@ -0,0 +1,718 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use super::builtins;
+use super::bytecode::{Chunk, OpCode, Prototype};
+use super::collection::{List, Map};
+use super::environment::Environment;
+use super::function::Function;
+use super::interpreter::{self, RuntimeError, RuntimeErrorKind};
+use super::literal::{Literal, NumericMode};
+use super::stmt::FunctionDecl;
+use super::token::TokenType;
+
+/// 调用帧，`base` 为被调用函数在栈中的位置，返回时栈截断到这里
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    base: usize,
+    /// 当前作用域
+    environment: Rc<Environment>,
+}
+
+/// 字节码不一定来自 `Compiler`，指令与操作数都要检查
+impl Frame {
+    fn read_byte(&mut self) -> Result<u8, RuntimeError> {
+        let byte = *self
+            .chunk
+            .code
+            .get(self.ip)
+            .ok_or_else(|| invalid("unexpected end of code"))?;
+        self.ip += 1;
+        Ok(byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, RuntimeError> {
+        match self.chunk.code.get(self.ip..self.ip + 2) {
+            Some(&[high, low]) => {
+                self.ip += 2;
+                Ok(u16::from_be_bytes([high, low]))
+            }
+            _ => Err(invalid("unexpected end of code")),
+        }
+    }
+
+    fn constant(&self, index: usize) -> Result<&Literal, RuntimeError> {
+        self.chunk
+            .constants
+            .get(index)
+            .ok_or_else(|| invalid("constant index out of range"))
+    }
+
+    /// 变量名常量
+    fn name(&self, index: usize) -> Result<&str, RuntimeError> {
+        match self.constant(index)? {
+            Literal::String(name) => Ok(name),
+            other => Err(invalid(&format!(
+                "expected a variable name, found {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// 跳转到 `distance` 之外，越界的目标在读取下一条指令时报错
+    fn jump(&mut self, distance: usize, forward: bool) -> Result<(), RuntimeError> {
+        self.ip = match forward {
+            true => self.ip + distance,
+            false => self
+                .ip
+                .checked_sub(distance)
+                .ok_or_else(|| invalid("jump before the start of code"))?,
+        };
+        Ok(())
+    }
+}
+
+#[cold]
+fn invalid(message: &str) -> RuntimeError {
+    RuntimeError::new(RuntimeErrorKind::InvalidBytecode(message.to_string()))
+}
+
+#[cold]
+fn undefined(name: &str) -> RuntimeError {
+    RuntimeError::new(RuntimeErrorKind::UndefinedVariable(name.to_string()))
+}
+
+/// 向外第 `depth` 层作用域
+fn scope(environment: &Environment, depth: usize) -> Result<&Environment, RuntimeError> {
+    environment
+        .ancestor(depth)
+        .ok_or_else(|| invalid("scope depth out of range"))
+}
+
+fn slot_name(scope: &Environment, index: usize) -> Result<&str, RuntimeError> {
+    scope
+        .slot_name(index)
+        .ok_or_else(|| invalid("slot index out of range"))
+}
+
+/// 执行 `Compiler` 生成的字节码的栈式虚拟机，结果与 `Interpreter` 一致
+///
+/// 函数调用不占用 Rust 的栈，不需要像 `Interpreter` 那样限制栈的用量。
+pub struct Vm {
+    mode: NumericMode,
+    /// 全局作用域
+    environment: RefCell<Rc<Environment>>,
+    output: RefCell<Box<dyn Write>>,
+    max_depth: usize,
+    /// 函数体的字节码，以函数声明的地址为键，同时持有声明以免地址被复用
+    chunks: RefCell<HashMap<*const FunctionDecl, Prototype>>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm {
+            mode: NumericMode::default(),
+            environment: RefCell::new(builtins::globals()),
+            output: RefCell::new(Box::new(io::stdout())),
+            max_depth: Vm::MAX_DEPTH,
+            chunks: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Vm {
+    /// 默认的调用嵌套上限，与 `Interpreter::MAX_DEPTH` 相同；调用帧与作用域都在堆上，
+    /// 每层只占几百字节
+    pub const MAX_DEPTH: usize = 10_000;
+
+    pub fn new() -> Self {
+        Vm::default()
+    }
+
+    pub fn with_mode(mut self, mode: NumericMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_output(mut self, output: Box<dyn Write>) -> Self {
+        self.output = RefCell::new(output);
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// 读取全局变量
+    pub fn get(&self, name: &str) -> Option<Literal> {
+        self.environment.borrow().get(name)
+    }
+
+    /// 丢弃所有全局变量与已编译的函数
+    pub fn reset(&self) {
+        self.environment.replace(builtins::globals());
+        self.chunks.borrow_mut().clear();
+    }
+
+    /// 执行 `chunk`，返回其 `Return` 指令的值；全局变量在多次调用之间保留
+    pub fn run(&self, chunk: Rc<Chunk>) -> Result<Literal, RuntimeError> {
+        let mut stack = Vec::new();
+        let mut frames = vec![Frame {
+            chunk,
+            ip: 0,
+            base: 0,
+            environment: self.environment.borrow().clone(),
+        }];
+        loop {
+            let (depth, offset) = (frames.len(), frames.last().expect("call frame").ip);
+            match self.step(&mut frames, &mut stack) {
+                Ok(Some(value)) => return Ok(value),
+                Ok(None) => {}
+                // 出错的指令不会改变调用帧
+                Err(error) => {
+                    return Err(match frames[depth - 1].chunk.span(offset) {
+                        Some(span) => error.at(span),
+                        None => error,
+                    })
+                }
+            }
+        }
+    }
+
+    /// 执行一条指令，最外层的 `Return` 返回结果；内联到 `run` 的循环中以减少分派开销
+    #[inline(always)]
+    fn step(
+        &self,
+        frames: &mut Vec<Frame>,
+        stack: &mut Vec<Literal>,
+    ) -> Result<Option<Literal>, RuntimeError> {
+        let depth = frames.len() - 1;
+        let frame = frames.last_mut().expect("call frame");
+        let byte = frame.read_byte()?;
+        let op =
+            OpCode::from_byte(byte).ok_or_else(|| invalid(&format!("unknown opcode {}", byte)))?;
+        match op {
+            OpCode::Constant => {
+                let index = frame.read_u16()? as usize;
+                stack.push(frame.constant(index)?.clone());
+            }
+            OpCode::Nil => stack.push(Literal::None),
+            OpCode::True => stack.push(Literal::Bool(true)),
+            OpCode::False => stack.push(Literal::Bool(false)),
+            OpCode::Pop => {
+                pop(stack)?;
+            }
+            OpCode::GetVar => {
+                let globals = self.environment.borrow();
+                let index = frame.read_u16()? as usize;
+                let name = frame.name(index)?;
+                stack.push(globals.get(name).ok_or_else(|| undefined(name))?);
+            }
+            OpCode::SetVar => {
+                let globals = self.environment.borrow();
+                let index = frame.read_u16()? as usize;
+                let name = frame.name(index)?;
+                if !globals.assign(name, peek(stack)?.clone()) {
+                    return Err(undefined(name));
+                }
+            }
+            OpCode::DefineVar => {
+                let index = frame.read_u16()? as usize;
+                frame.environment.define(frame.name(index)?, pop(stack)?);
+            }
+            // 槽位尚未定义时（在声明之前访问）与 `Interpreter` 一样按名称到外层查找
+            OpCode::GetLocal => {
+                let depth = frame.read_u16()? as usize;
+                let index = frame.read_u16()? as usize;
+                let scope = scope(&frame.environment, depth)?;
+                let value = match scope.get_slot(index) {
+                    Some(value) => value,
+                    None => {
+                        let name = slot_name(scope, index)?;
+                        scope
+                            .ancestor(1)
+                            .and_then(|outer| outer.get(name))
+                            .ok_or_else(|| undefined(name))?
+                    }
+                };
+                stack.push(value);
+            }
+            OpCode::SetLocal => {
+                let depth = frame.read_u16()? as usize;
+                let index = frame.read_u16()? as usize;
+                let scope = scope(&frame.environment, depth)?;
+                let value = peek(stack)?.clone();
+                if !scope.assign_slot(index, value.clone()) {
+                    let name = slot_name(scope, index)?;
+                    let assigned = scope
+                        .ancestor(1)
+                        .is_some_and(|outer| outer.assign(name, value));
+                    if !assigned {
+                        return Err(undefined(name));
+                    }
+                }
+            }
+            OpCode::DefineLocal => {
+                let index = frame.read_u16()? as usize;
+                if !frame.environment.define_slot(index, pop(stack)?) {
+                    return Err(invalid("slot index out of range"));
+                }
+            }
+            OpCode::PushScope => {
+                let index = frame.read_u16()? as usize;
+                let names = frame
+                    .chunk
+                    .scopes
+                    .get(index)
+                    .ok_or_else(|| invalid("scope index out of range"))?
+                    .clone();
+                let environment = Environment::with_slots(names, frame.environment.clone());
+                frame.environment = Rc::new(environment);
+            }
+            OpCode::PopScope => {
+                frame.environment = frame
+                    .environment
+                    .enclosing()
+                    .ok_or_else(|| invalid("no scope to pop"))?;
+            }
+            OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual => {
+                let right = pop(stack)?;
+                let left = pop(stack)?;
+                stack.push(interpreter::binary(operator(op), left, right, self.mode)?);
+            }
+            OpCode::Negate => {
+                let value = pop(stack)?.neg_with(self.mode)?;
+                stack.push(value);
+            }
+            OpCode::Not => {
+                let value = !pop(stack)?;
+                stack.push(value);
+            }
+            OpCode::Truthy => {
+                let value = pop(stack)?.is_truthy();
+                stack.push(Literal::Bool(value));
+            }
+            OpCode::Print => {
+                let value = pop(stack)?;
+                writeln!(self.output.borrow_mut(), "{}", value)
+                    .map_err(|error| RuntimeError::new(RuntimeErrorKind::Io(error.to_string())))?;
+            }
+            OpCode::Interpolate => {
+                let count = frame.read_u16()? as usize;
+                let text = split_off(stack, count)?
+                    .into_iter()
+                    .map(|part| part.to_string())
+                    .collect();
+                stack.push(Literal::String(text));
+            }
+            OpCode::BuildList => {
+                let count = frame.read_u16()? as usize;
+                let values = split_off(stack, count)?;
+                stack.push(Literal::List(Rc::new(List::new(values))));
+            }
+            OpCode::BuildMap => {
+                let count = frame.read_u16()? as usize;
+                let map = Map::new();
+                let mut entries = split_off(stack, count * 2)?.into_iter();
+                while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                    map.insert(key, value);
+                }
+                stack.push(Literal::Map(Rc::new(map)));
+            }
+            OpCode::GetIndex => {
+                let index = pop(stack)?;
+                let value = pop(stack)?.index(&index)?;
+                stack.push(value);
+            }
+            OpCode::SetIndex => {
+                let value = pop(stack)?;
+                let index = pop(stack)?;
+                pop(stack)?.set_index(index, value.clone())?;
+                stack.push(value);
+            }
+            OpCode::Iterate => {
+                let values = pop(stack)?.iterate()?;
+                stack.push(Literal::List(Rc::new(List::new(values))));
+                stack.push(Literal::Integer(0));
+            }
+            // 栈顶为 `Iterate` 压入的列表与下标
+            OpCode::ForNext => {
+                let distance = frame.read_u16()? as usize;
+                let next = match stack.len().checked_sub(2).map(|top| &stack[top..]) {
+                    Some([Literal::List(values), Literal::Integer(index)]) => {
+                        values.get(*index as usize).map(|value| (value, index + 1))
+                    }
+                    _ => return Err(invalid("`ForNext` expects an iterator on the stack")),
+                };
+                match next {
+                    Some((value, index)) => {
+                        let top = stack.len() - 1;
+                        stack[top] = Literal::Integer(index);
+                        stack.push(value);
+                    }
+                    None => frame.jump(distance, true)?,
+                }
+            }
+            OpCode::Jump => {
+                let distance = frame.read_u16()? as usize;
+                frame.jump(distance, true)?;
+            }
+            OpCode::JumpIfFalse => {
+                let distance = frame.read_u16()? as usize;
+                if !pop(stack)?.is_truthy() {
+                    frame.jump(distance, true)?;
+                }
+            }
+            OpCode::Loop => {
+                let distance = frame.read_u16()? as usize;
+                frame.jump(distance, false)?;
+            }
+            OpCode::Closure => {
+                let index = frame.read_u16()? as usize;
+                let prototype = frame
+                    .chunk
+                    .functions
+                    .get(index)
+                    .ok_or_else(|| invalid("function index out of range"))?;
+                self.chunks
+                    .borrow_mut()
+                    .entry(Rc::as_ptr(&prototype.declaration))
+                    .or_insert_with(|| prototype.clone());
+                let function =
+                    Function::new(prototype.declaration.clone(), frame.environment.clone());
+                stack.push(Literal::Function(Rc::new(function)));
+            }
+            OpCode::Call => {
+                let count = frame.read_byte()? as usize;
+                let base = stack
+                    .len()
+                    .checked_sub(count + 1)
+                    .ok_or_else(|| invalid("missing call operands"))?;
+                let function = match &stack[base] {
+                    Literal::Function(function) => function.clone(),
+                    Literal::Native(function) => {
+                        let function = function.clone();
+                        let arguments = stack.split_off(base + 1);
+                        stack.pop();
+                        stack.push(function.call(&arguments)?);
+                        return Ok(None);
+                    }
+                    other => {
+                        return Err(RuntimeError::new(RuntimeErrorKind::NotCallable(
+                            other.type_name(),
+                        )))
+                    }
+                };
+                if count != function.arity() {
+                    return Err(RuntimeError::new(RuntimeErrorKind::Arity {
+                        expected: function.arity(),
+                        found: count,
+                    }));
+                }
+                if depth >= self.max_depth {
+                    return Err(RuntimeError::new(RuntimeErrorKind::StackOverflow {
+                        depth: self.max_depth,
+                    }));
+                }
+                let chunk = self.chunk(&function.declaration)?;
+                let environment =
+                    Environment::with_slots(chunk.locals.clone(), function.closure.clone());
+                for (&index, argument) in chunk.params.iter().zip(stack.drain(base + 1..)) {
+                    if !environment.define_slot(index, argument) {
+                        return Err(invalid("parameter slot out of range"));
+                    }
+                }
+                frames.push(Frame {
+                    chunk,
+                    ip: 0,
+                    base,
+                    environment: Rc::new(environment),
+                });
+            }
+            OpCode::Return => {
+                let value = pop(stack)?;
+                let frame = frames.pop().expect("call frame");
+                if frames.is_empty() {
+                    return Ok(Some(value));
+                }
+                stack.truncate(frame.base);
+                stack.push(value);
+            }
+        }
+        Ok(None)
+    }
+
+    /// 由 `Closure` 指令创建的函数，其字节码已经登记
+    fn chunk(&self, declaration: &Rc<FunctionDecl>) -> Result<Rc<Chunk>, RuntimeError> {
+        let chunks = self.chunks.borrow();
+        let prototype = chunks
+            .get(&Rc::as_ptr(declaration))
+            .ok_or_else(|| invalid("function was not created by `Closure`"))?;
+        Ok(prototype.chunk.clone())
+    }
+}
+
+fn pop(stack: &mut Vec<Literal>) -> Result<Literal, RuntimeError> {
+    stack
+        .pop()
+        .ok_or_else(|| invalid("operand stack underflow"))
+}
+
+fn peek(stack: &[Literal]) -> Result<&Literal, RuntimeError> {
+    stack
+        .last()
+        .ok_or_else(|| invalid("operand stack underflow"))
+}
+
+/// 弹出栈顶的 `count` 个值，保持原有顺序
+fn split_off(stack: &mut Vec<Literal>, count: usize) -> Result<Vec<Literal>, RuntimeError> {
+    let start = stack
+        .len()
+        .checked_sub(count)
+        .ok_or_else(|| invalid("operand stack underflow"))?;
+    Ok(stack.split_off(start))
+}
+
+fn operator(op: OpCode) -> TokenType {
+    match op {
+        OpCode::Add => TokenType::Plus,
+        OpCode::Subtract => TokenType::Minus,
+        OpCode::Multiply => TokenType::Star,
+        OpCode::Divide => TokenType::Slash,
+        OpCode::Equal => TokenType::EqualEqual,
+        OpCode::NotEqual => TokenType::BangEqual,
+        OpCode::Greater => TokenType::Greater,
+        OpCode::GreaterEqual => TokenType::GreaterEqual,
+        OpCode::Less => TokenType::Less,
+        _ => TokenType::LessEqual,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::compiler::Compiler;
+    use super::super::interpreter::Interpreter;
+    use std::time::Instant;
+
+    use super::super::stmt::Stmt;
+    use super::super::testing::{parse_expr, parse_program, run_vm, tree_walk, SharedOutput};
+    use super::super::token::Span;
+    use super::*;
+
+    /// 虚拟机与树遍历解释器的输出和错误（包括出错位置）完全一致
+    fn assert_same(source: &str) {
+        let statements = parse_program(source);
+        for mode in [NumericMode::Checked, NumericMode::Exact] {
+            assert_eq!(
+                tree_walk(&statements, mode),
+                run_vm(&statements, mode),
+                "{}",
+                source
+            );
+        }
+    }
+
+    fn evaluate(source: &str) -> Result<Literal, RuntimeError> {
+        let expr = parse_expr(source);
+        Vm::new().run(Rc::new(Compiler::new().compile_expr(&expr).unwrap()))
+    }
+
+    #[test]
+    fn vm_evaluate_expression() {
+        assert_eq!(Ok(Literal::Integer(3)), evaluate("(1 + 1) * 3 / 2"));
+        assert_eq!(
+            Ok(Literal::Bool(true)),
+            evaluate("1 < 2 && !(2 <= 1) || nil")
+        );
+        assert_eq!(Ok(Literal::String("a1".to_string())), evaluate("\"a${1}\""));
+    }
+
+    #[test]
+    fn vm_matches_tree_walker() {
+        let programs = [
+            "print (1 + 1) * 3 / 2; print 1 - 2 * 4; print 1.5 + 2; print --2;",
+            "print 0xFF + 0b1010 + 1_000; print 2.5e3 == 2500; print 1 / 3 + 1 / 6;",
+            "print \"foo\" + \"bar\"; print 3 >= 2 * 2 != 1 < 2; print !0; print \"a\" == \"b\";",
+            "print 9223372036854775807 + 1; print -(-9223372036854775807 - 1);",
+            "print nil || 0; print 1 && \"\"; print false || nil; print true && 2;",
+            "let a = 1; { let a = 2; print a; a = 3; print a; } print a;",
+            "let a; print a; a = b = 1;",
+            "let i = 0; while (i < 5) { i = i + 1; if (i == 2) continue; if (i == 4) break; print i; }",
+            "for (let i = 0; i < 3; i = i + 1) { let j = i * 2; print j; } print i;",
+            "for (let i = 0; i < 10; i = i + 1) { { if (i == 1) continue; } if (i > 3) { break; } print i; }",
+            "let n = 0; for (;;) { n = n + 1; if (n > 2) break; } print n;",
+            "fn fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(15);",
+            "fn counter() { let n = 0; return fn () { n = n + 1; return n; }; }\
+             let a = counter(); let b = counter(); print a(); print a(); print b();",
+            "fn outer() { let x = \"outer\"; fn inner() { print x; } return inner; } outer()();",
+            "fn f() { for (let i = 0; ; i = i + 1) { while (true) { if (i == 3) return i; break; } } }\
+             print f(); print f;",
+            "fn noop() {} print noop(); print fn (x) { return x * 2; }(21);",
+            "let name = \"vm\"; print \"hello ${name}, ${1 + 2} ${\"${nil}\"} \\${x}\";",
+            "print 1 + \"a\";",
+            "print -\"a\";",
+            "print 1 / 0;",
+            "print undefined;",
+            "missing = 1;",
+            "let x = 1; x();",
+            "fn f(a) {} f(1, 2);",
+            "fn f() { return 1 + nil; } print \"before\"; f();",
+            "let xs = [1, \"a\", [2]]; xs[0] = xs[2][0] + 1; print xs; print len(xs); print len(\"héllo\");",
+            "let m = {\"a\": 1}; m[\"b\"] = m[\"a\"] + 1; m[1.0] = nil; print m; print m[1]; print len(m);",
+            "let sum = 0; for (x in [1, 2, 3, 4, 5]) { if (x == 2) continue; if (x == 5) break; sum = sum + x; } print sum;",
+            "fn keys(m) { let fs = []; for (k in m) { fs = [fn () { return k; }, fs]; } return fs; }\
+             let fs = keys({\"a\": 1, \"b\": 2}); print fs[0](); print fs[1][0]();",
+            "fn find(xs, y) { for (x in xs) { for (z in [x]) { if (z == y) return x; } } return nil; }\
+             print find([3, 4], 4); print find([], 1);",
+            "print [1][1];",
+            "print [1][\"0\"];",
+            "print {}[\"k\"];",
+            "print 1[0];",
+            "for (x in 1) print x;",
+            "print len(nil);",
+            "len(1, 2);",
+            "let a = 1; { print a; let a = 2; print a; a = 3; } print a;",
+            "let x = 1; fn f() { x = 2; let x = 3; return x; } print f(); print x;",
+            "fn f(a, a) { return a; } print f(1, 2);",
+            "fn f() { fn g() { return h(); } fn h() { return 1; } return g(); } print f();",
+            "fn f() { print g; fn g() {} } f();",
+            "for (let i = 0; i < 2; i = i + 1) { let i = 5; print i; }",
+        ];
+        for program in programs {
+            assert_same(program);
+        }
+    }
+
+    /// 手工构造的字节码出错时报告 `InvalidBytecode`，而不是 panic
+    #[test]
+    fn vm_rejects_invalid_bytecode() {
+        let run = |code: &[u8], constants: Vec<Literal>| {
+            let mut chunk = Chunk::new("test");
+            for (offset, &byte) in code.iter().enumerate() {
+                chunk.write(byte, Span::new(offset, offset + 1));
+            }
+            chunk.constants = constants;
+            match Vm::new().run(Rc::new(chunk)).unwrap_err() {
+                RuntimeError {
+                    kind: RuntimeErrorKind::InvalidBytecode(message),
+                    span,
+                } => (message, span),
+                other => panic!("unexpected error {:?}", other),
+            }
+        };
+        assert_eq!(
+            ("unknown opcode 255".to_string(), Some(Span::new(1, 2))),
+            run(&[OpCode::Nil as u8, 255], vec![])
+        );
+        assert_eq!(
+            "expected a variable name, found Integer",
+            run(&[OpCode::GetVar as u8, 0, 0], vec![Literal::Integer(1)]).0
+        );
+        assert_eq!(
+            "constant index out of range",
+            run(&[OpCode::Constant as u8, 0, 1], vec![Literal::Integer(1)]).0
+        );
+        assert_eq!(
+            "`ForNext` expects an iterator on the stack",
+            run(&[OpCode::Nil as u8, OpCode::ForNext as u8, 0, 0], vec![]).0
+        );
+        assert_eq!(
+            "operand stack underflow",
+            run(&[OpCode::Add as u8], vec![]).0
+        );
+        assert_eq!(
+            "scope depth out of range",
+            run(&[OpCode::GetLocal as u8, 0, 9, 0, 0], vec![]).0
+        );
+        assert_eq!(
+            "no scope to pop",
+            run(&[OpCode::PopScope as u8, OpCode::PopScope as u8], vec![]).0
+        );
+        assert_eq!(
+            "jump before the start of code",
+            run(&[OpCode::Loop as u8, 0, 9], vec![]).0
+        );
+        assert_eq!(
+            "unexpected end of code",
+            run(&[OpCode::Nil as u8], vec![]).0
+        );
+    }
+
+    #[test]
+    fn vm_keeps_globals_between_runs() {
+        let vm = Vm::new();
+        let compile = |source: &str| {
+            let statements = parse_program(source);
+            Rc::new(Compiler::new().compile(&statements).unwrap())
+        };
+        vm.run(compile("let a = 1; fn inc() { a = a + 1; }"))
+            .unwrap();
+        vm.run(compile("inc(); inc();")).unwrap();
+        assert_eq!(Some(Literal::Integer(3)), vm.get("a"));
+        // 出错后仍回到全局作用域
+        assert!(vm.run(compile("{ let b = 1; b + nil; }")).is_err());
+        vm.run(compile("let c = a;")).unwrap();
+        assert_eq!(Some(Literal::Integer(3)), vm.get("c"));
+        assert_eq!(None, vm.get("b"));
+        vm.reset();
+        assert_eq!(None, vm.get("a"));
+    }
+
+    /// 耗时对比：`cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn vm_faster_than_tree_walker() {
+        let statements = parse_program(
+            "fn fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(22);\
+             let s = 0; for (let i = 0; i < 200000; i = i + 1) { let j = i * 2; s = s + j; } print s;\
+             { let k = 0; let t = 0; while (k < 200000) { k = k + 1; t = t + k; } print t; }",
+        );
+        let time = |run: fn(&[Stmt], NumericMode) -> _| {
+            let start = Instant::now();
+            let (output, result): (String, Result<(), RuntimeError>) =
+                run(&statements, NumericMode::Exact);
+            result.unwrap();
+            (output, start.elapsed())
+        };
+        let (expected, tree) = time(tree_walk);
+        let (output, vm) = time(run_vm);
+        println!("tree-walker {:?}, vm {:?}", tree, vm);
+        assert_eq!(expected, output);
+        assert!(vm < tree, "tree-walker {:?}, vm {:?}", tree, vm);
+    }
+
+    #[test]
+    fn vm_stack_overflow_limit() {
+        let statements = parse_program("fn f(n) { return f(n + 1); } f(0);");
+        let chunk = Rc::new(Compiler::new().compile(&statements).unwrap());
+        // 调用不占用 Rust 的栈，默认上限远高于 `Interpreter`
+        let error = Vm::new().run(chunk.clone()).unwrap_err();
+        assert_eq!(
+            RuntimeErrorKind::StackOverflow {
+                depth: Vm::MAX_DEPTH
+            },
+            error.kind
+        );
+        // 上限相同时与树遍历解释器的错误一致
+        let output = SharedOutput::default();
+        let interpreter = Interpreter::new()
+            .with_max_depth(50)
+            .with_output(Box::new(output.clone()));
+        let expected = interpreter.execute(&statements).unwrap_err();
+        let error = Vm::new().with_max_depth(50).run(chunk).unwrap_err();
+        assert_eq!(expected, error);
+    }
+}
@@ -1,11 +1,74 @@
-mod arithmetics;
+use std::env;
+use std::io;
+use std::process;
+
+use rust_arithmetic::arithmetics::string::{
+    atoi::Atoi,
+    interpret::{
+        diagnostics::Diagnostic,
+        interpreter::Interpreter,
+        repl::Repl,
+        runner::{self, Script, ScriptError},
+        scanner::LexerScanner,
+    },
+};
 
-use arithmetics::string::{atoi::Atoi, interpret::scanner::LexerScanner};
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // 递归较深的脚本需要比主线程更大的栈
+    runner::with_large_stack(
+        || match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] | ["repl"] => repl(),
+            ["demo"] => demo(),
+            [path] => run(path),
+            _ => {
+                eprintln!("usage: rust_arithmetic [repl | demo | <script> | -]");
+                process::exit(ScriptError::USAGE);
+            }
+        },
+    )
+}
+
+fn interpreter() -> Interpreter {
+    Interpreter::new().with_stack_budget(runner::STACK_BUDGET)
+}
+
+/// 执行脚本，诊断信息输出到标准错误，并以出错阶段对应的退出码退出
+fn run(path: &str) {
+    let script = match Script::load(path) {
+        Ok(script) => script,
+        Err(error) => {
+            eprintln!("error: cannot read `{}`: {}", path, error);
+            process::exit(error.exit_code());
+        }
+    };
+    if let Err(error) = script.run(&interpreter()) {
+        eprint!("{}", script.render(&error));
+        process::exit(error.exit_code());
+    }
+}
+
+fn repl() {
+    println!("rust_arithmetic repl, `:help` for commands, `:quit` to exit");
+    let repl = Repl::new(interpreter());
+    if let Err(error) = repl.run(io::stdin().lock(), &mut io::stdout()) {
+        eprintln!("error: {}", error);
+        process::exit(ScriptError::Io(error).exit_code());
+    }
+}
+
+fn demo() {
     let atoi = Atoi::new(" -22 asdb 33".to_string());
     println!("atoi res: {}", atoi.parse());
 
-    let scanner = LexerScanner::new("\"23423423\" == \"222\"".to_string());
-    let tokens = scanner.scan();
-    println!("Tokens: {:#?}", tokens);
+    let source = "\"23423423\" == \"222\"";
+    let scanner = LexerScanner::new(source.to_string());
+    match scanner.scan() {
+        Ok(tokens) => println!("Tokens: {:#?}", tokens),
+        Err(errors) => {
+            for error in &errors {
+                eprint!("{}", Diagnostic::from(error).render("input", source));
+            }
+        }
+    }
 }